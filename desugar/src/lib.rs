@@ -0,0 +1,782 @@
+//! `#[desugar]`：把简单的 async fn 展开成显式的状态机
+//!
+//! 生成的代码和 `SimpleCoroutine`、`GreetCoroutine` 手写的状态机是同一种形状：
+//!
+//! - 一个 enum，`Unresumed` 保存参数，每个 `.await` 对应一个 `Suspend0..N`，
+//!   里面是跨这个 await 存活的局部变量和正在 await 的子 future，最后是 `Returned` 和 `Panicked`
+//! - poll 时用 `mem::replace` 取出当前状态（执行期间是 `Panicked`），执行到下一个暂停点，再写回新的状态
+//! - 原来的函数变成一个普通函数，只构造 `Unresumed`，不执行函数体
+//!
+//! 状态机的类型名是函数名转成驼峰再加 `Coroutine`：`greet_twice` 生成 `GreetTwiceCoroutine`。
+//!
+//! 过程宏看不到类型，所以只支持 async fn 的一个子集：
+//!
+//! - 参数是 `name: T`，没有泛型和生命周期参数，状态机里的类型都是 `'static` 的
+//! - `.await` 只能出现在语句里：`future.await;`、`let x: T = future.await;`、`x = future.await;`
+//! - 包含 `.await` 的控制流只支持 `if` / `else`、`loop` 和 `while`，循环里只用不带标签的 `break` / `continue`
+//! - 跨 await 存活的局部变量要写出类型，且不能遮蔽同名变量
+//! - 不支持 `?`
+//!
+//! 和编译器的区别：子 future 的类型写不出来，所以装箱成 `Pin<Box<dyn Future>>`；
+//! 局部变量在暂停点被移动进状态，所以必须是 `Unpin` 的，也不能跨 await 借用其他局部变量。
+//! 一个局部变量在暂停点之后还被用到（按变量名判断）才保存，否则在暂停时就被 drop，
+//! 而编译器会把有析构函数的变量一直保存到作用域结束。
+
+use std::collections::HashSet;
+
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2, TokenTree};
+use quote::{ToTokens, format_ident, quote};
+use syn::visit::{self, Visit};
+use syn::visit_mut::{self, VisitMut};
+use syn::{
+    Block, Error, Expr, ExprAsync, ExprAwait, ExprClosure, ExprIf, FnArg, Ident, Item, ItemFn, Lifetime, Pat,
+    ReturnType, Stmt, Type, parse_macro_input, parse_quote,
+};
+
+/// 把 async fn 展开成显式的状态机，见 crate 文档
+#[proc_macro_attribute]
+pub fn desugar(attr: TokenStream, item: TokenStream) -> TokenStream {
+    if !attr.is_empty() {
+        return Error::new(Span::call_site(), "#[desugar] 不接受参数")
+            .to_compile_error()
+            .into();
+    }
+    let func = parse_macro_input!(item as ItemFn);
+    expand(func).unwrap_or_else(Error::into_compile_error).into()
+}
+
+fn expand(func: ItemFn) -> syn::Result<TokenStream2> {
+    let sig = &func.sig;
+    if sig.asyncness.is_none() {
+        return Err(Error::new_spanned(sig.fn_token, "#[desugar] 只能用在 async fn 上"));
+    }
+    if !sig.generics.params.is_empty() || sig.generics.where_clause.is_some() {
+        return Err(Error::new_spanned(&sig.generics, "#[desugar] 不支持泛型和生命周期参数"));
+    }
+    let mut args = Vec::new();
+    for input in &sig.inputs {
+        let FnArg::Typed(arg) = input else {
+            return Err(Error::new_spanned(input, "#[desugar] 不支持 self 参数"));
+        };
+        let (name, _) = binding(&arg.pat)?;
+        args.push(Local {
+            name,
+            ty: Some((*arg.ty).clone()),
+        });
+    }
+    let output = match &sig.output {
+        ReturnType::Default => quote!(()),
+        ReturnType::Type(_, ty) => ty.to_token_stream(),
+    };
+
+    let codegen = Codegen::new(&sig.ident);
+
+    // 函数体里的 return 要先把状态设为 Returned
+    let mut body = (*func.block).clone();
+    RewriteReturn { codegen: &codegen }.visit_block_mut(&mut body);
+    let tail = match body.stmts.last() {
+        Some(Stmt::Expr(expr, None)) if !contains_await(expr) => {
+            let expr = expr.to_token_stream();
+            body.stmts.pop();
+            expr
+        }
+        _ => quote!(()),
+    };
+    let mut lower = Lower::default();
+    let mut nodes = lower.block(&body.stmts)?;
+    nodes.push(Node {
+        src: tail,
+        kind: NodeKind::Return,
+    });
+
+    let mut sites = Vec::new();
+    let mut scope = args.iter().map(|arg| (arg.clone(), 0)).collect();
+    collect_sites(&nodes, &mut Vec::new(), &mut scope, &mut sites);
+    let mut codegen = codegen;
+    let mut variants = Vec::new();
+    for site in &sites {
+        let live = site.live();
+        let mut fields = Vec::new();
+        for local in &live {
+            let Some(ty) = &local.ty else {
+                return Err(Error::new_spanned(
+                    &local.name,
+                    format!("`{}` 跨 .await 存活，需要写出类型", local.name),
+                ));
+            };
+            let name = &local.name;
+            fields.push(quote!(#name: #ty));
+        }
+        let awaited = match site.target {
+            Target::Discard => quote!(()),
+            Target::Let(local) => local.ty.to_token_stream(),
+            Target::Assign(place) => site.assigned_type(place)?.to_token_stream(),
+        };
+        let variant = format_ident!("Suspend{}", site.id);
+        variants.push(quote! {
+            #variant {
+                #(#fields,)*
+                __awaitee: ::std::pin::Pin<::std::boxed::Box<dyn ::std::future::Future<Output = #awaited>>>,
+            }
+        });
+        codegen.live.push(live.into_iter().map(|local| local.name).collect());
+    }
+
+    let name = &codegen.name;
+    let arg_names: Vec<_> = args.iter().map(|arg| &arg.name).collect();
+    let arg_types: Vec<_> = args.iter().map(|arg| arg.ty.as_ref().unwrap()).collect();
+    let this = &codegen.this;
+    let cx = &codegen.cx;
+    let resume = &codegen.resume;
+    let discard = &codegen.discard;
+    let start = codegen.structural(&nodes);
+    let mut arms = Vec::new();
+    for site in &sites {
+        arms.push(codegen.resume_arm(site)?);
+    }
+    let discard_item = sites.iter().any(|site| matches!(site.target, Target::Discard)).then(|| {
+        quote! {
+            // `future.await;` 丢弃输出，子 future 的输出统一成 ()
+            struct #discard<F>(F);
+
+            impl<F: ::std::future::Future> ::std::future::Future for #discard<F> {
+                type Output = ();
+
+                fn poll(
+                    self: ::std::pin::Pin<&mut Self>,
+                    cx: &mut ::std::task::Context<'_>,
+                ) -> ::std::task::Poll<()> {
+                    ::std::future::Future::poll(unsafe { self.map_unchecked_mut(|this| &mut this.0) }, cx).map(drop)
+                }
+            }
+        }
+    });
+
+    let attrs = &func.attrs;
+    let vis = &func.vis;
+    let ident = &sig.ident;
+    let doc = format!("`{ident}` 展开后的状态机，由 `#[desugar]` 生成");
+    Ok(quote! {
+        #[doc = #doc]
+        #vis enum #name {
+            Unresumed { #(#arg_names: #arg_types),* },
+            #(#variants,)*
+            Returned,
+            Panicked,
+        }
+
+        impl ::std::future::Future for #name {
+            type Output = #output;
+
+            #[allow(unreachable_code, unused_mut, unused_variables)]
+            fn poll(
+                self: ::std::pin::Pin<&mut Self>,
+                #cx: &mut ::std::task::Context<'_>,
+            ) -> ::std::task::Poll<#output> {
+                #discard_item
+                let #this = self.get_mut();
+                #resume: loop {
+                    match ::std::mem::replace(#this, #name::Panicked) {
+                        #name::Unresumed { #(mut #arg_names),* } => { #start }
+                        #(#arms)*
+                        #name::Returned => ::std::panic!("`async fn` resumed after completion"),
+                        #name::Panicked => ::std::panic!("`async fn` resumed after panicking"),
+                    }
+                }
+            }
+        }
+
+        #(#attrs)*
+        #vis fn #ident(#(#arg_names: #arg_types),*) -> #name {
+            #name::Unresumed { #(#arg_names),* }
+        }
+    })
+}
+
+/// 一个局部变量（或参数）；没有写类型时 `ty` 是 `None`
+#[derive(Clone)]
+struct Local {
+    name: Ident,
+    ty: Option<Type>,
+}
+
+/// 函数体中的一条语句
+struct Node {
+    // 原来的代码，用来判断变量之后是否还会被用到；`Plain` 和 `Let` 原样输出
+    src: TokenStream2,
+    kind: NodeKind,
+}
+
+enum NodeKind {
+    // 不包含 .await 的语句
+    Plain,
+    // 不包含 .await 的 let
+    Let(Local),
+    // 暂停点
+    Await { id: usize, target: Target, future: Expr },
+    If {
+        cond: Expr,
+        then: Vec<Node>,
+        els: Option<Vec<Node>>,
+    },
+    // `loop`（cond 是 None）或 `while`
+    Loop {
+        id: usize,
+        cond: Option<Expr>,
+        body: Vec<Node>,
+    },
+    // 函数体末尾：`src` 是返回值
+    Return,
+}
+
+/// `.await` 的结果放到哪里
+enum Target {
+    // `future.await;`
+    Discard,
+    // `let x: T = future.await;`
+    Let(Local),
+    // `x = future.await;`
+    Assign(Expr),
+}
+
+/// 把语法树转换成 `Node`，按出现的顺序给暂停点和循环编号
+#[derive(Default)]
+struct Lower {
+    awaits: usize,
+    loops: usize,
+}
+
+impl Lower {
+    fn block(&mut self, stmts: &[Stmt]) -> syn::Result<Vec<Node>> {
+        stmts.iter().map(|stmt| self.stmt(stmt)).collect()
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) -> syn::Result<Node> {
+        let src = stmt.to_token_stream();
+        match stmt {
+            Stmt::Local(local) => {
+                let (name, ty) = binding(&local.pat)?;
+                let Some(init) = &local.init else {
+                    return Ok(Node {
+                        src,
+                        kind: NodeKind::Let(Local { name, ty }),
+                    });
+                };
+                let diverge_awaits = init.diverge.as_ref().is_some_and(|(_, expr)| contains_await(expr));
+                if !contains_await(&init.expr) && !diverge_awaits {
+                    return Ok(Node {
+                        src,
+                        kind: NodeKind::Let(Local { name, ty }),
+                    });
+                }
+                let future = match &*init.expr {
+                    Expr::Await(awaited) if !diverge_awaits && !contains_await(&awaited.base) => &awaited.base,
+                    _ => return Err(unsupported(stmt)),
+                };
+                let Some(ty) = ty else {
+                    return Err(Error::new_spanned(&local.pat, "`let x: T = future.await;` 需要写出类型"));
+                };
+                Ok(Node {
+                    src,
+                    kind: NodeKind::Await {
+                        id: self.next_await(),
+                        target: Target::Let(Local { name, ty: Some(ty) }),
+                        future: (**future).clone(),
+                    },
+                })
+            }
+            Stmt::Expr(expr, _) if contains_await(expr) => {
+                let kind = match expr {
+                    Expr::Await(awaited) if !contains_await(&awaited.base) => NodeKind::Await {
+                        id: self.next_await(),
+                        target: Target::Discard,
+                        future: (*awaited.base).clone(),
+                    },
+                    Expr::Assign(assign)
+                        if !contains_await(&assign.left)
+                            && let Expr::Await(awaited) = &*assign.right
+                            && !contains_await(&awaited.base) =>
+                    {
+                        NodeKind::Await {
+                            id: self.next_await(),
+                            target: Target::Assign((*assign.left).clone()),
+                            future: (*awaited.base).clone(),
+                        }
+                    }
+                    Expr::If(expr_if) => return self.if_chain(expr_if),
+                    Expr::Loop(expr_loop) if expr_loop.label.is_none() => NodeKind::Loop {
+                        id: self.next_loop(),
+                        cond: None,
+                        body: self.block(&expr_loop.body.stmts)?,
+                    },
+                    Expr::While(expr_while) if expr_while.label.is_none() && !contains_await(&expr_while.cond) => {
+                        NodeKind::Loop {
+                            id: self.next_loop(),
+                            cond: Some((*expr_while.cond).clone()),
+                            body: self.block(&expr_while.body.stmts)?,
+                        }
+                    }
+                    _ => return Err(unsupported(expr)),
+                };
+                Ok(Node { src, kind })
+            }
+            // 块中间不带分号的表达式（比如最后一个 `if`）也当作语句
+            Stmt::Expr(expr, None) => Ok(Node {
+                src: quote!(#expr;),
+                kind: NodeKind::Plain,
+            }),
+            Stmt::Macro(mac) if mac.semi_token.is_none() => Ok(Node {
+                src: quote!(#mac;),
+                kind: NodeKind::Plain,
+            }),
+            Stmt::Expr(..) | Stmt::Macro(_) | Stmt::Item(_) => Ok(Node {
+                src,
+                kind: NodeKind::Plain,
+            }),
+        }
+    }
+
+    fn if_chain(&mut self, expr_if: &ExprIf) -> syn::Result<Node> {
+        if contains_await(&expr_if.cond) {
+            return Err(unsupported(&expr_if.cond));
+        }
+        let then = self.block(&expr_if.then_branch.stmts)?;
+        let els = match expr_if.else_branch.as_ref().map(|(_, els)| &**els) {
+            None => None,
+            Some(Expr::Block(block)) if block.label.is_none() => Some(self.block(&block.block.stmts)?),
+            Some(Expr::If(nested)) => Some(vec![self.if_chain(nested)?]),
+            Some(other) => return Err(unsupported(other)),
+        };
+        Ok(Node {
+            src: expr_if.to_token_stream(),
+            kind: NodeKind::If {
+                cond: (*expr_if.cond).clone(),
+                then,
+                els,
+            },
+        })
+    }
+
+    fn next_await(&mut self) -> usize {
+        self.awaits += 1;
+        self.awaits - 1
+    }
+
+    fn next_loop(&mut self) -> usize {
+        self.loops += 1;
+        self.loops - 1
+    }
+}
+
+/// 暂停点之后接着执行的代码，从里到外
+#[derive(Clone, Copy)]
+enum Frame<'a> {
+    // 同一个块中剩下的语句
+    Rest(&'a [Node]),
+    // 所在的循环：这一轮结束后回到循环开头
+    Loop(&'a Node),
+}
+
+impl Frame<'_> {
+    fn src(&self) -> TokenStream2 {
+        match self {
+            Frame::Rest(nodes) => nodes.iter().map(|node| node.src.clone()).collect(),
+            Frame::Loop(node) => node.src.clone(),
+        }
+    }
+}
+
+/// 一个暂停点和它的上下文
+struct Site<'a> {
+    id: usize,
+    target: &'a Target,
+    // 从外到里
+    frames: Vec<Frame<'a>>,
+    // 在作用域中的局部变量，和声明它的块在 `frames` 中的位置
+    scope: Vec<(Local, usize)>,
+}
+
+impl Site<'_> {
+    /// 需要保存在这个状态里的局部变量：暂停点之后、离开它的作用域之前还会被用到
+    ///
+    /// 循环里声明的变量在下一轮会被重新声明，所以只看它所在的块之内的 frame。
+    fn live(&self) -> Vec<Local> {
+        let mut used = Vec::new();
+        for frame in &self.frames {
+            let mut names = HashSet::new();
+            idents(frame.src(), &mut names);
+            used.push(names);
+        }
+        let mut assigned = HashSet::new();
+        if let Target::Assign(place) = self.target {
+            idents(place.to_token_stream(), &mut assigned);
+        }
+        let mut live: Vec<Local> = Vec::new();
+        for (local, depth) in &self.scope {
+            let name = local.name.to_string();
+            live.retain(|other| other.name != local.name);
+            if assigned.contains(&name) || used[*depth..].iter().any(|names| names.contains(&name)) {
+                live.push(local.clone());
+            }
+        }
+        live
+    }
+
+    /// `x = future.await;` 中 `x` 的类型
+    fn assigned_type(&self, place: &Expr) -> syn::Result<&Type> {
+        let error = || Error::new_spanned(place, "`x = future.await;` 的 `x` 必须是写出了类型的局部变量");
+        let Expr::Path(path) = place else {
+            return Err(error());
+        };
+        let name = path.path.get_ident().ok_or_else(error)?;
+        self.scope
+            .iter()
+            .rev()
+            .find(|(local, _)| local.name == *name)
+            .and_then(|(local, _)| local.ty.as_ref())
+            .ok_or_else(error)
+    }
+}
+
+fn collect_sites<'a>(
+    nodes: &'a [Node],
+    frames: &mut Vec<Frame<'a>>,
+    scope: &mut Vec<(Local, usize)>,
+    sites: &mut Vec<Site<'a>>,
+) {
+    let mark = scope.len();
+    let depth = frames.len();
+    for (i, node) in nodes.iter().enumerate() {
+        let rest = &nodes[i + 1..];
+        match &node.kind {
+            NodeKind::Let(local) => scope.push((local.clone(), depth)),
+            NodeKind::Await { id, target, .. } => {
+                let mut site_frames = frames.clone();
+                site_frames.push(Frame::Rest(rest));
+                sites.push(Site {
+                    id: *id,
+                    target,
+                    frames: site_frames,
+                    scope: scope.clone(),
+                });
+                if let Target::Let(local) = target {
+                    scope.push((local.clone(), depth));
+                }
+            }
+            NodeKind::If { then, els, .. } => {
+                frames.push(Frame::Rest(rest));
+                collect_sites(then, frames, scope, sites);
+                if let Some(els) = els {
+                    collect_sites(els, frames, scope, sites);
+                }
+                frames.pop();
+            }
+            NodeKind::Loop { body, .. } => {
+                frames.push(Frame::Rest(rest));
+                frames.push(Frame::Loop(node));
+                collect_sites(body, frames, scope, sites);
+                frames.truncate(depth);
+            }
+            NodeKind::Plain | NodeKind::Return => {}
+        }
+    }
+    scope.truncate(mark);
+}
+
+/// 生成代码用到的名字；`this`、`cx` 等用 mixed_site，函数体里的代码看不到它们
+struct Codegen {
+    name: Ident,
+    this: Ident,
+    cx: Ident,
+    awaitee: Ident,
+    output: Ident,
+    discard: Ident,
+    resume: Lifetime,
+    // 每个暂停点保存的局部变量
+    live: Vec<Vec<Ident>>,
+}
+
+impl Codegen {
+    fn new(func: &Ident) -> Self {
+        let camel: String = func
+            .to_string()
+            .split('_')
+            .filter(|part| !part.is_empty())
+            .map(|part| {
+                let mut chars = part.chars();
+                let first = chars.next().unwrap().to_ascii_uppercase();
+                std::iter::once(first).chain(chars).collect::<String>()
+            })
+            .collect();
+        let hygienic = |name: &str| Ident::new(name, Span::mixed_site());
+        Self {
+            name: format_ident!("{}Coroutine", camel),
+            this: hygienic("this"),
+            cx: hygienic("cx"),
+            awaitee: hygienic("awaitee"),
+            output: hygienic("output"),
+            discard: hygienic("Discard"),
+            resume: Lifetime::new("'resume", Span::mixed_site()),
+            live: Vec::new(),
+        }
+    }
+
+    /// 函数返回：先把状态设为 Returned
+    fn ret(&self, value: TokenStream2) -> TokenStream2 {
+        let Codegen { name, this, output, .. } = self;
+        quote! {{
+            let #output = #value;
+            *#this = #name::Returned;
+            return ::std::task::Poll::Ready(#output);
+        }}
+    }
+
+    /// 从头执行一段语句，直到第一个暂停点（后面的语句在恢复时执行）或者返回
+    fn structural(&self, nodes: &[Node]) -> TokenStream2 {
+        let mut code = TokenStream2::new();
+        for node in nodes {
+            match &node.kind {
+                NodeKind::Plain | NodeKind::Let(_) => code.extend(node.src.clone()),
+                NodeKind::Await { id, target, future } => {
+                    code.extend(self.suspend(*id, target, future));
+                    break;
+                }
+                NodeKind::If { cond, then, els } => {
+                    let then = self.structural(then);
+                    let els = els.as_ref().map(|els| {
+                        let els = self.structural(els);
+                        quote!(else { #els })
+                    });
+                    code.extend(quote!(if #cond { #then } #els));
+                }
+                NodeKind::Loop { cond, body, .. } => {
+                    let body = self.structural(body);
+                    code.extend(match cond {
+                        Some(cond) => quote!(while #cond { #body }),
+                        None => quote!(loop { #body }),
+                    });
+                }
+                NodeKind::Return => {
+                    code.extend(self.ret(node.src.clone()));
+                    break;
+                }
+            }
+        }
+        code
+    }
+
+    /// 执行到 `.await`：创建子 future，连同存活的局部变量一起保存到状态里，然后立刻 poll 它
+    fn suspend(&self, id: usize, target: &Target, future: &Expr) -> TokenStream2 {
+        let Codegen {
+            name,
+            this,
+            discard,
+            resume,
+            ..
+        } = self;
+        let variant = format_ident!("Suspend{}", id);
+        let live = &self.live[id];
+        let future = match target {
+            Target::Discard => quote!(#discard(#future)),
+            Target::Let(_) | Target::Assign(_) => future.to_token_stream(),
+        };
+        quote! {{
+            *#this = #name::#variant {
+                #(#live,)*
+                __awaitee: ::std::boxed::Box::pin(#future),
+            };
+            continue #resume;
+        }}
+    }
+
+    /// 恢复执行：poll 子 future，完成后从暂停点接着执行
+    fn resume_arm(&self, site: &Site) -> syn::Result<TokenStream2> {
+        let Codegen {
+            name,
+            this,
+            cx,
+            awaitee,
+            output,
+            ..
+        } = self;
+        let variant = format_ident!("Suspend{}", site.id);
+        let live = &self.live[site.id];
+        let poll = quote! {
+            match ::std::future::Future::poll(#awaitee.as_mut(), #cx) {
+                ::std::task::Poll::Ready(#output) => #output,
+                ::std::task::Poll::Pending => {
+                    *#this = #name::#variant { #(#live,)* __awaitee: #awaitee };
+                    return ::std::task::Poll::Pending;
+                }
+            }
+        };
+        let bind = match site.target {
+            Target::Discard => quote!(let () = #poll;),
+            Target::Let(Local { name, ty }) => quote!(let mut #name: #ty = #poll;),
+            Target::Assign(place) => quote!(#place = #poll;),
+        };
+
+        let mut code = TokenStream2::new();
+        for frame in site.frames.iter().rev() {
+            match frame {
+                Frame::Rest(nodes) => code.extend(self.structural(nodes)),
+                Frame::Loop(node) => {
+                    let NodeKind::Loop { id, .. } = node.kind else {
+                        unreachable!()
+                    };
+                    // 这一轮剩下的代码放在带标签的块里：continue 跳出这个块，回到循环开头；
+                    // break 跳过整个循环
+                    let exit = Lifetime::new(&format!("'exit{id}"), Span::mixed_site());
+                    let next = Lifetime::new(&format!("'next{id}"), Span::mixed_site());
+                    let mut block: Block = syn::parse2(quote!({ #code }))?;
+                    RewriteLoopControl {
+                        exit: &exit,
+                        next: &next,
+                    }
+                    .visit_block_mut(&mut block);
+                    let stmts = &block.stmts;
+                    let again = self.structural(std::slice::from_ref(node));
+                    code = quote! {
+                        #exit: {
+                            #next: { #(#stmts)* }
+                            #again
+                        }
+                    };
+                }
+            }
+        }
+        // 子 future 完成后立刻 drop，和 `.await` 结束时一样
+        Ok(quote! {
+            #name::#variant { #(mut #live,)* __awaitee: mut #awaitee } => {
+                #bind
+                drop(#awaitee);
+                #code
+            }
+        })
+    }
+}
+
+/// 把 `return value` 改成先设置 Returned 再返回 `Poll::Ready(value)`
+struct RewriteReturn<'a> {
+    codegen: &'a Codegen,
+}
+
+impl VisitMut for RewriteReturn<'_> {
+    fn visit_expr_mut(&mut self, expr: &mut Expr) {
+        match expr {
+            Expr::Closure(_) | Expr::Async(_) => {}
+            Expr::Return(ret) => {
+                if let Some(value) = &mut ret.expr {
+                    self.visit_expr_mut(value);
+                }
+                let value = match &ret.expr {
+                    Some(value) => value.to_token_stream(),
+                    None => quote!(()),
+                };
+                let code = self.codegen.ret(value);
+                *expr = parse_quote!(#code);
+            }
+            _ => visit_mut::visit_expr_mut(self, expr),
+        }
+    }
+
+    fn visit_item_mut(&mut self, _: &mut Item) {}
+}
+
+/// 把指向当前循环的 `break` / `continue` 改成跳出带标签的块
+struct RewriteLoopControl<'a> {
+    exit: &'a Lifetime,
+    next: &'a Lifetime,
+}
+
+impl VisitMut for RewriteLoopControl<'_> {
+    fn visit_expr_mut(&mut self, expr: &mut Expr) {
+        match expr {
+            // 里面的 break / continue 属于内层的循环
+            Expr::Closure(_) | Expr::Async(_) | Expr::Loop(_) | Expr::While(_) | Expr::ForLoop(_) => {}
+            Expr::Break(brk) if brk.label.is_none() => brk.label = Some(self.exit.clone()),
+            Expr::Continue(cont) if cont.label.is_none() => {
+                let next = self.next;
+                *expr = parse_quote!(break #next);
+            }
+            _ => visit_mut::visit_expr_mut(self, expr),
+        }
+    }
+
+    fn visit_item_mut(&mut self, _: &mut Item) {}
+}
+
+/// 表达式中有没有 `.await`（不算闭包、async 块和嵌套的函数里的）
+fn contains_await(expr: &Expr) -> bool {
+    struct Finder(bool);
+
+    impl Visit<'_> for Finder {
+        fn visit_expr_await(&mut self, _: &ExprAwait) {
+            self.0 = true;
+        }
+
+        fn visit_expr_closure(&mut self, _: &ExprClosure) {}
+
+        fn visit_expr_async(&mut self, _: &ExprAsync) {}
+
+        fn visit_item(&mut self, _: &Item) {}
+    }
+
+    let mut finder = Finder(false);
+    visit::visit_expr(&mut finder, expr);
+    finder.0
+}
+
+/// `x`、`mut x` 或 `x: T`
+fn binding(pat: &Pat) -> syn::Result<(Ident, Option<Type>)> {
+    match pat {
+        Pat::Ident(ident) if ident.by_ref.is_none() && ident.subpat.is_none() => Ok((ident.ident.clone(), None)),
+        Pat::Type(typed) => {
+            let (name, _) = binding(&typed.pat)?;
+            Ok((name, Some((*typed.ty).clone())))
+        }
+        _ => Err(Error::new_spanned(pat, "#[desugar] 只支持 `x` 或 `x: T` 形式的绑定")),
+    }
+}
+
+fn unsupported(tokens: impl ToTokens) -> Error {
+    Error::new_spanned(
+        tokens,
+        "#[desugar] 只支持这些形式的 .await：`future.await;`、`let x: T = future.await;`、`x = future.await;`，\
+         以及包含它们的 if、loop 和 while 语句",
+    )
+}
+
+/// 代码中出现的所有标识符，包括格式化字符串里内联的变量（`format!("{name}")`）
+fn idents(tokens: TokenStream2, names: &mut HashSet<String>) {
+    for token in tokens {
+        match token {
+            TokenTree::Ident(ident) => {
+                names.insert(ident.to_string());
+            }
+            TokenTree::Group(group) => idents(group.stream(), names),
+            TokenTree::Literal(lit) => {
+                let lit = lit.to_string();
+                if !lit.ends_with('"') {
+                    continue;
+                }
+                let mut rest = lit.as_str();
+                while let Some(start) = rest.find('{') {
+                    rest = &rest[start + 1..];
+                    if let Some(escaped) = rest.strip_prefix('{') {
+                        rest = escaped;
+                        continue;
+                    }
+                    let end = rest
+                        .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+                        .unwrap_or(rest.len());
+                    if end > 0 && matches!(rest[end..].chars().next(), Some('}' | ':')) {
+                        names.insert(rest[..end].to_string());
+                    }
+                }
+            }
+            TokenTree::Punct(_) => {}
+        }
+    }
+}
@@ -0,0 +1,317 @@
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::time::Duration;
+
+use super::super::custom_waker::AsyncTimerFuture;
+use super::super::simple_executor::SimpleExecutor;
+
+/// 没有任何接收方，值原样返回
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SendError<T>(pub T);
+
+impl<T> fmt::Display for SendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "broadcast 没有接收方")
+    }
+}
+
+impl<T: fmt::Debug> std::error::Error for SendError<T> {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecvError {
+    /// 所有发送方都已经 drop，并且已经收完了所有值
+    Closed,
+    /// 接收方太慢，这么多个值在被读到之前就被覆盖了；下一次 recv 从最旧的值继续
+    Lagged(u64),
+}
+
+impl fmt::Display for RecvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RecvError::Closed => write!(f, "broadcast 发送方已关闭"),
+            RecvError::Lagged(skipped) => write!(f, "broadcast 接收方落后，跳过了 {skipped} 个值"),
+        }
+    }
+}
+
+impl std::error::Error for RecvError {}
+
+/// 环形缓冲区和等待中的接收方
+///
+/// 每个值有一个递增的位置，`buffer[0]` 的位置是 `head`。
+/// 缓冲区满时丢弃最旧的值，还没读到它的接收方下次 recv 时得到 `Lagged`。
+///
+/// 等待方式和 `SharedState` 一样：接收方读不到新值时把 waker 存进 `wakers`，
+/// 发送方写入后取出所有 waker 调用。
+struct State<T> {
+    buffer: VecDeque<T>,
+    head: u64,
+    capacity: usize,
+    senders: usize,
+    receivers: usize,
+    wakers: HashMap<usize, Waker>,
+    next_receiver_id: usize,
+}
+
+impl<T> State<T> {
+    fn tail(&self) -> u64 {
+        self.head + self.buffer.len() as u64
+    }
+
+    fn new_receiver(&mut self) -> usize {
+        self.receivers += 1;
+        self.next_receiver_id += 1;
+        self.next_receiver_id
+    }
+
+    fn take_wakers(&mut self) -> Vec<Waker> {
+        self.wakers.drain().map(|(_, waker)| waker).collect()
+    }
+}
+
+type Shared<T> = Arc<Mutex<State<T>>>;
+
+/// 创建广播 channel：每个接收方都会收到每一个值（的克隆）
+///
+/// `capacity` 是缓冲区能保存的值的数量，太慢的接收方会错过被覆盖的值。
+pub fn channel<T: Clone>(capacity: usize) -> (Sender<T>, Receiver<T>) {
+    assert!(capacity > 0, "capacity 必须大于 0");
+    let mut state = State {
+        buffer: VecDeque::with_capacity(capacity),
+        head: 0,
+        capacity,
+        senders: 1,
+        receivers: 0,
+        wakers: HashMap::new(),
+        next_receiver_id: 0,
+    };
+    let id = state.new_receiver();
+    let shared = Arc::new(Mutex::new(state));
+    (
+        Sender {
+            shared: shared.clone(),
+        },
+        Receiver {
+            shared,
+            id,
+            next: 0,
+        },
+    )
+}
+
+pub struct Sender<T> {
+    shared: Shared<T>,
+}
+
+impl<T: Clone> Sender<T> {
+    /// 发送一个值，返回收到它的接收方数量；没有接收方时失败
+    ///
+    /// 不会等待：缓冲区满时直接覆盖最旧的值。
+    pub fn send(&self, value: T) -> Result<usize, SendError<T>> {
+        let (receivers, wakers) = {
+            let mut state = self.shared.lock().unwrap();
+            if state.receivers == 0 {
+                return Err(SendError(value));
+            }
+            if state.buffer.len() == state.capacity {
+                state.buffer.pop_front();
+                state.head += 1;
+            }
+            state.buffer.push_back(value);
+            (state.receivers, state.take_wakers())
+        };
+        for waker in wakers {
+            waker.wake();
+        }
+        Ok(receivers)
+    }
+
+    /// 新的接收方，只会收到之后发送的值
+    pub fn subscribe(&self) -> Receiver<T> {
+        let mut state = self.shared.lock().unwrap();
+        let id = state.new_receiver();
+        let next = state.tail();
+        Receiver {
+            shared: self.shared.clone(),
+            id,
+            next,
+        }
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        self.shared.lock().unwrap().senders += 1;
+        Self {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let wakers = {
+            let mut state = self.shared.lock().unwrap();
+            state.senders -= 1;
+            if state.senders > 0 {
+                return;
+            }
+            state.take_wakers()
+        };
+        for waker in wakers {
+            waker.wake();
+        }
+    }
+}
+
+pub struct Receiver<T> {
+    shared: Shared<T>,
+    id: usize,
+    // 下一个要读的位置
+    next: u64,
+}
+
+impl<T: Clone> Receiver<T> {
+    /// 接收下一个值
+    pub fn recv(&mut self) -> Recv<'_, T> {
+        Recv { receiver: self }
+    }
+}
+
+impl<T> Clone for Receiver<T> {
+    /// 新的接收方从同一个位置开始读
+    fn clone(&self) -> Self {
+        let id = self.shared.lock().unwrap().new_receiver();
+        Self {
+            shared: self.shared.clone(),
+            id,
+            next: self.next,
+        }
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        let mut state = self.shared.lock().unwrap();
+        state.receivers -= 1;
+        state.wakers.remove(&self.id);
+    }
+}
+
+/// `Receiver::recv` 返回的 future
+pub struct Recv<'a, T> {
+    receiver: &'a mut Receiver<T>,
+}
+
+impl<T: Clone> Future for Recv<'_, T> {
+    type Output = Result<T, RecvError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let receiver = &mut self.get_mut().receiver;
+        let mut state = receiver.shared.lock().unwrap();
+        if receiver.next < state.head {
+            let skipped = state.head - receiver.next;
+            receiver.next = state.head;
+            return Poll::Ready(Err(RecvError::Lagged(skipped)));
+        }
+        if receiver.next < state.tail() {
+            let value = state.buffer[(receiver.next - state.head) as usize].clone();
+            receiver.next += 1;
+            return Poll::Ready(Ok(value));
+        }
+        if state.senders == 0 {
+            return Poll::Ready(Err(RecvError::Closed));
+        }
+        state.wakers.insert(receiver.id, cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+/// 每 10ms 广播一个值，一共 10 个
+async fn publish(sender: Sender<u32>) {
+    for value in 0..10 {
+        AsyncTimerFuture::new(Duration::from_millis(10)).await;
+        sender.send(value).unwrap();
+    }
+}
+
+/// 每收到一个值后处理 `delay`，返回收到的值和被跳过的值的数量
+async fn consume(mut receiver: Receiver<u32>, delay: Duration) -> (Vec<u32>, u64) {
+    let (mut values, mut skipped) = (Vec::new(), 0);
+    loop {
+        match receiver.recv().await {
+            Ok(value) => {
+                values.push(value);
+                if !delay.is_zero() {
+                    AsyncTimerFuture::new(delay).await;
+                }
+            }
+            // 落后之后立刻从最旧的值继续读
+            Err(RecvError::Lagged(n)) => skipped += n,
+            Err(RecvError::Closed) => return (values, skipped),
+        }
+    }
+}
+
+fn check(name: &str, fast: &(Vec<u32>, u64), slow: &(Vec<u32>, u64)) {
+    println!("{name}: 快的接收方 {:?}", fast.0);
+    println!("{name}: 慢的接收方 {:?}，跳过 {} 个", slow.0, slow.1);
+    assert_eq!(fast.0, (0..10).collect::<Vec<_>>());
+    assert_eq!(fast.1, 0);
+    // 慢的接收方落后了，但收到的加上跳过的正好是全部
+    assert!(slow.1 > 0);
+    assert_eq!(slow.0.len() as u64 + slow.1, 10);
+    assert!(slow.0.windows(2).all(|pair| pair[0] < pair[1]));
+}
+
+/// 测试 broadcast channel
+///
+/// 一个发送方每 10ms 发送一个值，缓冲区只能放 2 个值：
+/// - 快的接收方收到每一个值
+/// - 慢的接收方处理一个值要 35ms，被覆盖的值通过 `Lagged` 报告
+/// - 发送方 drop 后，两个接收方都得到 `Closed`
+///
+/// 同样的代码分别在 SimpleExecutor 和 tokio 的 current_thread 运行时上运行。
+pub fn test_broadcast() {
+    println!("\n=== broadcast 示例：环形缓冲区和落后报告 ===");
+
+    // SimpleExecutor（虚拟时钟）
+    let executor = SimpleExecutor::new_paused();
+    let (tx, fast) = channel(2);
+    let slow = tx.subscribe();
+    let fast = executor.spawn(consume(fast, Duration::ZERO));
+    let slow = executor.spawn(consume(slow, Duration::from_millis(35)));
+    let (fast, slow) = executor.block_on(async {
+        publish(tx).await;
+        (fast.await.unwrap(), slow.await.unwrap())
+    });
+    check("SimpleExecutor", &fast, &slow);
+
+    // tokio current_thread
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    let (fast, slow) = runtime.block_on(async {
+        let (tx, fast) = channel(2);
+        let slow = tx.subscribe();
+        let fast = tokio::spawn(consume(fast, Duration::ZERO));
+        let slow = tokio::spawn(consume(slow, Duration::from_millis(35)));
+        publish(tx).await;
+        (fast.await.unwrap(), slow.await.unwrap())
+    });
+    check("tokio", &fast, &slow);
+
+    // 没有接收方时发送失败
+    let (tx, rx) = channel(1);
+    drop(rx);
+    assert_eq!(tx.send(1), Err(SendError(1)));
+
+    println!("\n关键点：");
+    println!("- 每个值发给所有接收方，缓冲区满时覆盖最旧的值");
+    println!("- 落后的接收方收到 Lagged(n)，然后从最旧的值继续，不会让发送方等待");
+    println!("- 接收方读不到新值时保存 waker，发送方写入后唤醒所有等待的接收方");
+}
@@ -0,0 +1,485 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::thread;
+use std::time::Duration;
+
+use super::super::custom_waker::AsyncTimerFuture;
+use super::super::simple_executor::SimpleExecutor;
+use super::super::sync::waiters::WaiterQueue;
+use super::block_on_thread;
+
+/// 接收方已经关闭，没有发出去的值原样返回
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SendError<T>(pub T);
+
+impl<T> fmt::Display for SendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "mpsc 接收方已关闭")
+    }
+}
+
+impl<T: fmt::Debug> std::error::Error for SendError<T> {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrySendError<T> {
+    /// 有界 channel 已满
+    Full(T),
+    Closed(T),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TryRecvError {
+    Empty,
+    /// 所有发送方都已经 drop，并且缓冲区已经取空
+    Disconnected,
+}
+
+/// channel 的共享状态
+///
+/// - 接收方取不到值时保存 `rx_waker`，发送方放入值后调用它
+/// - 有界 channel 满了时，发送方在 `send_waiters` 中排队；接收方取走一个值，
+///   就把空出来的位置预留给队首的发送方（`reserved`），再唤醒它。
+///   和 `AsyncMutex` 一样直接转交，后来的发送方不会插队。
+struct Chan<T> {
+    queue: VecDeque<T>,
+    // None 表示无界
+    capacity: Option<usize>,
+    reserved: usize,
+    senders: usize,
+    rx_closed: bool,
+    rx_waker: Option<Waker>,
+    send_waiters: WaiterQueue<()>,
+}
+
+impl<T> Chan<T> {
+    fn has_room(&self) -> bool {
+        match self.capacity {
+            Some(capacity) => self.queue.len() + self.reserved < capacity,
+            None => true,
+        }
+    }
+
+    /// 放入一个值，返回要唤醒的接收方
+    fn push(&mut self, value: T) -> Option<Waker> {
+        self.queue.push_back(value);
+        self.rx_waker.take()
+    }
+
+    /// 有空位并且有发送方在排队时，把空位预留给队首，返回它的 waker
+    fn grant(&mut self) -> Option<Waker> {
+        if self.send_waiters.is_empty() || !self.has_room() {
+            return None;
+        }
+        self.reserved += 1;
+        self.send_waiters.pop_front().map(|waiter| waiter.waker)
+    }
+}
+
+type Shared<T> = Arc<Mutex<Chan<T>>>;
+
+fn new_chan<T>(capacity: Option<usize>) -> Shared<T> {
+    Arc::new(Mutex::new(Chan {
+        queue: VecDeque::new(),
+        capacity,
+        reserved: 0,
+        senders: 1,
+        rx_closed: false,
+        rx_waker: None,
+        send_waiters: WaiterQueue::new(),
+    }))
+}
+
+fn wake(waker: Option<Waker>) {
+    if let Some(waker) = waker {
+        waker.wake();
+    }
+}
+
+/// 创建有界 channel：缓冲区满时 `send` 等待接收方取走值（背压）
+pub fn channel<T>(capacity: usize) -> (Sender<T>, Receiver<T>) {
+    assert!(capacity > 0, "capacity 必须大于 0");
+    let chan = new_chan(Some(capacity));
+    (
+        Sender { chan: chan.clone() },
+        Receiver { chan },
+    )
+}
+
+/// 创建无界 channel：`send` 总是立刻完成
+pub fn unbounded_channel<T>() -> (UnboundedSender<T>, Receiver<T>) {
+    let chan = new_chan(None);
+    (
+        UnboundedSender { chan: chan.clone() },
+        Receiver { chan },
+    )
+}
+
+/// 增加发送方计数；最后一个发送方 drop 时唤醒接收方，让它知道不会再有值了
+fn clone_sender<T>(chan: &Shared<T>) -> Shared<T> {
+    chan.lock().unwrap().senders += 1;
+    chan.clone()
+}
+
+fn drop_sender<T>(chan: &Shared<T>) {
+    let waker = {
+        let mut chan = chan.lock().unwrap();
+        chan.senders -= 1;
+        if chan.senders > 0 {
+            return;
+        }
+        chan.rx_waker.take()
+    };
+    wake(waker);
+}
+
+/// 有界 channel 的发送方
+pub struct Sender<T> {
+    chan: Shared<T>,
+}
+
+impl<T> Sender<T> {
+    /// 发送一个值；缓冲区满时等待
+    pub fn send(&self, value: T) -> Send<'_, T> {
+        Send {
+            chan: &self.chan,
+            value: Some(value),
+            waiter: None,
+        }
+    }
+
+    pub fn try_send(&self, value: T) -> Result<(), TrySendError<T>> {
+        let waker = {
+            let mut chan = self.chan.lock().unwrap();
+            if chan.rx_closed {
+                return Err(TrySendError::Closed(value));
+            }
+            if !chan.send_waiters.is_empty() || !chan.has_room() {
+                return Err(TrySendError::Full(value));
+            }
+            chan.push(value)
+        };
+        wake(waker);
+        Ok(())
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.chan.lock().unwrap().rx_closed
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        Self {
+            chan: clone_sender(&self.chan),
+        }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        drop_sender(&self.chan);
+    }
+}
+
+/// `Sender::send` 返回的 future
+pub struct Send<'a, T> {
+    chan: &'a Shared<T>,
+    value: Option<T>,
+    // 排队后的编号；不在队列中说明已经为它预留了空位
+    waiter: Option<u64>,
+}
+
+// value 只会被移动，不会被 pin 住
+impl<T> Unpin for Send<'_, T> {}
+
+impl<T> Future for Send<'_, T> {
+    type Output = Result<(), SendError<T>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let mut chan = this.chan.lock().unwrap();
+        if chan.rx_closed {
+            // close() 已经清空了等待队列，不再需要 Drop 处理排队状态
+            this.waiter = None;
+            let value = this.value.take().expect("Send 在完成后被 poll");
+            return Poll::Ready(Err(SendError(value)));
+        }
+        match this.waiter {
+            None => {
+                if !chan.send_waiters.is_empty() || !chan.has_room() {
+                    this.waiter = Some(chan.send_waiters.push((), cx.waker()));
+                    return Poll::Pending;
+                }
+            }
+            Some(id) => {
+                if chan.send_waiters.update(id, cx.waker()) {
+                    return Poll::Pending;
+                }
+                // 预留的空位归我们了
+                this.waiter = None;
+                chan.reserved -= 1;
+            }
+        }
+        let value = this.value.take().expect("Send 在完成后被 poll");
+        let waker = chan.push(value);
+        drop(chan);
+        wake(waker);
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<T> Drop for Send<'_, T> {
+    fn drop(&mut self) {
+        let Some(id) = self.waiter else {
+            return;
+        };
+        let waker = {
+            let mut chan = self.chan.lock().unwrap();
+            if chan.rx_closed {
+                // close() 已经清空了等待队列，并没有为我们预留空位
+                return;
+            }
+            if chan.send_waiters.remove(id) {
+                None
+            } else {
+                // 已经为我们预留了空位，但不会再发送了：转交给下一个发送方
+                chan.reserved -= 1;
+                chan.grant()
+            }
+        };
+        wake(waker);
+    }
+}
+
+/// 无界 channel 的发送方
+pub struct UnboundedSender<T> {
+    chan: Shared<T>,
+}
+
+impl<T> UnboundedSender<T> {
+    /// 发送一个值，不需要等待，可以在任何线程中调用
+    pub fn send(&self, value: T) -> Result<(), SendError<T>> {
+        let waker = {
+            let mut chan = self.chan.lock().unwrap();
+            if chan.rx_closed {
+                return Err(SendError(value));
+            }
+            chan.push(value)
+        };
+        wake(waker);
+        Ok(())
+    }
+}
+
+impl<T> Clone for UnboundedSender<T> {
+    fn clone(&self) -> Self {
+        Self {
+            chan: clone_sender(&self.chan),
+        }
+    }
+}
+
+impl<T> Drop for UnboundedSender<T> {
+    fn drop(&mut self) {
+        drop_sender(&self.chan);
+    }
+}
+
+/// 接收方（有界和无界 channel 共用）
+pub struct Receiver<T> {
+    chan: Shared<T>,
+}
+
+impl<T> Receiver<T> {
+    /// 接收下一个值；所有发送方都 drop 并且缓冲区取空后返回 `None`
+    pub fn recv(&mut self) -> Recv<'_, T> {
+        Recv { receiver: self }
+    }
+
+    pub fn try_recv(&mut self) -> Result<T, TryRecvError> {
+        let (result, waker) = {
+            let mut chan = self.chan.lock().unwrap();
+            match chan.queue.pop_front() {
+                Some(value) => (Ok(value), chan.grant()),
+                None if chan.senders == 0 => (Err(TryRecvError::Disconnected), None),
+                None => (Err(TryRecvError::Empty), None),
+            }
+        };
+        wake(waker);
+        result
+    }
+
+    /// 在普通线程上阻塞等待下一个值
+    pub fn blocking_recv(&mut self) -> Option<T> {
+        block_on_thread(self.recv())
+    }
+
+    /// 缓冲区中的值的数量
+    pub fn len(&self) -> usize {
+        self.chan.lock().unwrap().queue.len()
+    }
+
+    /// 关闭接收方：之后的发送都会失败，已经在缓冲区中的值仍然可以收到
+    pub fn close(&mut self) {
+        let wakers: Vec<_> = {
+            let mut chan = self.chan.lock().unwrap();
+            chan.rx_closed = true;
+            // 等待中的发送方都会得到 SendError
+            std::iter::from_fn(|| chan.send_waiters.pop_front())
+                .map(|waiter| waiter.waker)
+                .collect()
+        };
+        for waker in wakers {
+            waker.wake();
+        }
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        self.close();
+        // 缓冲区中的值在锁外释放
+        let values = std::mem::take(&mut self.chan.lock().unwrap().queue);
+        drop(values);
+    }
+}
+
+/// `Receiver::recv` 返回的 future
+pub struct Recv<'a, T> {
+    receiver: &'a mut Receiver<T>,
+}
+
+impl<T> Future for Recv<'_, T> {
+    type Output = Option<T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        let (value, waker) = {
+            let mut chan = self.receiver.chan.lock().unwrap();
+            match chan.queue.pop_front() {
+                Some(value) => (value, chan.grant()),
+                None if chan.senders == 0 => return Poll::Ready(None),
+                None => {
+                    // 和检查队列在同一把锁内保存 waker，发送方不会错过它
+                    chan.rx_waker = Some(cx.waker().clone());
+                    return Poll::Pending;
+                }
+            }
+        };
+        wake(waker);
+        Poll::Ready(Some(value))
+    }
+}
+
+/// 测试 mpsc channel
+///
+/// 1. 有界 channel 的背压：容量为 2，生产者很快、消费者很慢，缓冲区从不超过 2 个值
+/// 2. 无界 channel：多个普通线程发送，tokio 接收，所有发送方 drop 后 `recv` 返回 `None`
+/// 3. 关闭：接收方 drop 后，正在等待空位的发送方得到 `SendError`；
+///    排队中的 `send` 在关闭后直接被 drop 也不会弄乱预留的空位
+pub fn test_mpsc() {
+    println!("\n=== mpsc 示例：有界、无界 channel ===");
+
+    // 1. 背压（SimpleExecutor，虚拟时钟）
+    let executor = SimpleExecutor::new_paused();
+    let start = executor.now();
+    let (tx, mut rx) = channel(2);
+    let producer = executor.spawn(async move {
+        for i in 0..10 {
+            tx.send(i).await.unwrap();
+        }
+    });
+    let received = executor.block_on(async {
+        let mut received = Vec::new();
+        while let Some(value) = rx.recv().await {
+            assert!(rx.len() <= 2, "缓冲区超过了容量");
+            // 慢速消费者
+            AsyncTimerFuture::new(Duration::from_millis(10)).await;
+            received.push(value);
+        }
+        producer.await.unwrap();
+        received
+    });
+    println!(
+        "有界 channel 收到 {:?}，虚拟时间 {:?}",
+        received,
+        executor.now() - start
+    );
+    assert_eq!(received, (0..10).collect::<Vec<_>>());
+    assert_eq!(executor.now() - start, Duration::from_millis(100));
+
+    // 2. 多个普通线程 -> tokio
+    let (tx, mut rx) = unbounded_channel();
+    let producers: Vec<_> = (0..4)
+        .map(|id| {
+            let tx = tx.clone();
+            thread::spawn(move || {
+                for i in 0..100 {
+                    tx.send(id * 1000 + i).unwrap();
+                }
+            })
+        })
+        .collect();
+    drop(tx);
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    let (count, sum) = runtime.block_on(async {
+        let (mut count, mut sum) = (0, 0);
+        while let Some(value) = rx.recv().await {
+            count += 1;
+            sum += value;
+        }
+        (count, sum)
+    });
+    for producer in producers {
+        producer.join().unwrap();
+    }
+    println!("tokio 从 4 个线程收到 {} 个值", count);
+    assert_eq!(count, 400);
+    assert_eq!(sum, (0..4).map(|id| id * 1000 * 100 + 4950).sum::<i32>());
+
+    // 3. 接收方关闭时，等待中的发送方被唤醒并得到 SendError
+    let (tx, rx) = channel(1);
+    tx.try_send(1).unwrap();
+    assert_eq!(tx.try_send(2), Err(TrySendError::Full(2)));
+    let observer = tx.clone();
+    let sender = thread::spawn(move || block_on_thread(tx.send(3)));
+    thread::sleep(Duration::from_millis(20));
+    drop(rx);
+    let result = sender.join().unwrap();
+    println!("接收方关闭后，等待中的 send 返回 {:?}", result);
+    assert_eq!(result, Err(SendError(3)));
+    assert!(observer.is_closed());
+    assert_eq!(observer.try_send(4), Err(TrySendError::Closed(4)));
+
+    // 排队中的 send 在接收方关闭后没有再被 poll 就被 drop（比如被 select 取消）
+    let (tx, mut rx) = channel(1);
+    tx.try_send(1).unwrap();
+    let mut pending = tx.send(2);
+    let waker = Waker::noop();
+    assert!(Pin::new(&mut pending).poll(&mut Context::from_waker(waker)).is_pending());
+    rx.close();
+    drop(pending);
+    assert_eq!(tx.chan.lock().unwrap().reserved, 0);
+    assert_eq!(rx.try_recv(), Ok(1));
+
+    // 发送方全部 drop：接收方取完缓冲区后得到 None
+    let (tx, mut rx) = channel(4);
+    assert_eq!(rx.try_recv(), Err(TryRecvError::Empty));
+    tx.try_send("first").unwrap();
+    tx.try_send("last").unwrap();
+    drop(tx);
+    assert_eq!(rx.try_recv(), Ok("first"));
+    assert_eq!(rx.blocking_recv(), Some("last"));
+    assert_eq!(rx.try_recv(), Err(TryRecvError::Disconnected));
+    assert_eq!(rx.blocking_recv(), None);
+
+    println!("\n关键点：");
+    println!("- 接收方取不到值时保存 waker，发送方放入值后唤醒它");
+    println!("- 有界 channel 满时发送方排队等待，取走一个值就把空位转交给队首的发送方");
+    println!("- 任意一方关闭都会唤醒另一方，不会永远等待");
+}
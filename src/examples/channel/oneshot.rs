@@ -0,0 +1,218 @@
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::thread;
+use std::time::Duration;
+
+use super::super::simple_executor::SimpleExecutor;
+use super::block_on_thread;
+
+/// 发送方在发送之前被 drop
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecvError;
+
+impl fmt::Display for RecvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "oneshot 发送方已关闭")
+    }
+}
+
+impl std::error::Error for RecvError {}
+
+/// 一次性 channel 的共享状态
+///
+/// 和 `AsyncTimerFuture` 的 `SharedState` 是同一个模式，只是把 `completed: bool`
+/// 换成了 `value: Option<T>`：发送方写入值并调用接收方的 waker，接收方在 poll 时注入 waker。
+struct Shared<T> {
+    value: Option<T>,
+    // 发送方已经 send 或被 drop
+    tx_closed: bool,
+    rx_closed: bool,
+    rx_waker: Option<Waker>,
+    // 发送方在 `closed()` 中等待接收方关闭
+    tx_waker: Option<Waker>,
+}
+
+/// 创建一个只能发送一个值的 channel
+///
+/// 不依赖任何运行时：发送方可以在普通线程、tokio 或 `SimpleExecutor` 中，接收方也一样。
+pub fn channel<T>() -> (Sender<T>, Receiver<T>) {
+    let shared = Arc::new(Mutex::new(Shared {
+        value: None,
+        tx_closed: false,
+        rx_closed: false,
+        rx_waker: None,
+        tx_waker: None,
+    }));
+    (
+        Sender {
+            shared: shared.clone(),
+        },
+        Receiver { shared },
+    )
+}
+
+pub struct Sender<T> {
+    shared: Arc<Mutex<Shared<T>>>,
+}
+
+impl<T> Sender<T> {
+    /// 发送值；接收方已经关闭时把值原样返回
+    pub fn send(self, value: T) -> Result<(), T> {
+        let mut shared = self.shared.lock().unwrap();
+        if shared.rx_closed {
+            return Err(value);
+        }
+        shared.value = Some(value);
+        // 标记关闭和唤醒交给 Drop
+        Ok(())
+    }
+
+    /// 接收方是否已经关闭（被 drop 或调用了 `close`）
+    pub fn is_closed(&self) -> bool {
+        self.shared.lock().unwrap().rx_closed
+    }
+
+    /// 等待接收方关闭，用于在没有人需要结果时提前放弃计算
+    pub fn closed(&mut self) -> Closed<'_, T> {
+        Closed { sender: self }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let waker = {
+            let mut shared = self.shared.lock().unwrap();
+            shared.tx_closed = true;
+            shared.tx_waker = None;
+            shared.rx_waker.take()
+        };
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+/// `Sender::closed` 返回的 future
+pub struct Closed<'a, T> {
+    sender: &'a mut Sender<T>,
+}
+
+impl<T> Future for Closed<'_, T> {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let mut shared = self.sender.shared.lock().unwrap();
+        if shared.rx_closed {
+            return Poll::Ready(());
+        }
+        shared.tx_waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+/// 接收方本身就是 future，输出发送的值
+pub struct Receiver<T> {
+    shared: Arc<Mutex<Shared<T>>>,
+}
+
+impl<T> Receiver<T> {
+    /// 关闭接收方：之后的 `send` 会失败，已经发送的值仍然可以收到
+    pub fn close(&mut self) {
+        let waker = {
+            let mut shared = self.shared.lock().unwrap();
+            shared.rx_closed = true;
+            shared.tx_waker.take()
+        };
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+
+    /// 在普通线程上阻塞等待
+    pub fn blocking_recv(self) -> Result<T, RecvError> {
+        block_on_thread(self)
+    }
+}
+
+impl<T> Future for Receiver<T> {
+    type Output = Result<T, RecvError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut shared = self.shared.lock().unwrap();
+        if let Some(value) = shared.value.take() {
+            return Poll::Ready(Ok(value));
+        }
+        if shared.tx_closed {
+            return Poll::Ready(Err(RecvError));
+        }
+        shared.rx_waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        self.close();
+        // 没有被取走的值随 Receiver 一起释放
+        self.shared.lock().unwrap().value = None;
+    }
+}
+
+/// 测试 oneshot channel
+///
+/// 1. 普通线程发送，SimpleExecutor 接收：就像 `AsyncTimerFuture` 的后台线程，
+///    只是传回的是一个值而不是 `completed` 标志
+/// 2. tokio 中的任务发送，另一个普通线程阻塞接收
+/// 3. 关闭：发送方被 drop 时接收方得到 `RecvError`，接收方被 drop 时发送方的 `closed()` 完成
+pub fn test_oneshot() {
+    println!("\n=== oneshot 示例：只发送一个值的 channel ===");
+
+    // 1. 普通线程 -> SimpleExecutor
+    let (tx, rx) = channel();
+    thread::spawn(move || {
+        thread::sleep(Duration::from_millis(50));
+        tx.send("后台线程的计算结果").unwrap();
+    });
+    let result = SimpleExecutor::new().block_on(rx);
+    println!("SimpleExecutor 收到: {:?}", result);
+    assert_eq!(result, Ok("后台线程的计算结果"));
+
+    // 2. tokio 任务 -> 普通线程
+    let (tx, rx) = channel();
+    let receiver = thread::spawn(move || rx.blocking_recv());
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_time()
+        .build()
+        .unwrap();
+    runtime.block_on(async {
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        tx.send(42).unwrap();
+    });
+    let result = receiver.join().unwrap();
+    println!("普通线程收到: {:?}", result);
+    assert_eq!(result, Ok(42));
+
+    // 3. 关闭
+    let (tx, rx) = channel::<i32>();
+    drop(tx);
+    assert_eq!(SimpleExecutor::new().block_on(rx), Err(RecvError));
+
+    let (mut tx, rx) = channel::<i32>();
+    let dropper = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(20));
+        drop(rx);
+    });
+    runtime.block_on(tx.closed());
+    assert!(tx.is_closed());
+    assert_eq!(tx.send(1), Err(1));
+    dropper.join().unwrap();
+    println!("发送方和接收方都能发现对方已经关闭");
+
+    println!("\n关键点：");
+    println!("- 共享状态里保存值和 waker，和 SharedState 是同一个模式");
+    println!("- 只依赖 Waker：普通线程、tokio、SimpleExecutor 都可以收发");
+    println!("- 任意一方被 drop 时唤醒另一方，不会永远等待");
+}
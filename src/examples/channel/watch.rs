@@ -0,0 +1,294 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::future::Future;
+use std::ops::Deref;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::{Context, Poll, Waker};
+use std::time::Duration;
+
+use super::super::custom_waker::AsyncTimerFuture;
+use super::super::simple_executor::SimpleExecutor;
+
+/// 没有任何接收方，值原样返回
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SendError<T>(pub T);
+
+impl<T> fmt::Display for SendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "watch 没有接收方")
+    }
+}
+
+impl<T: fmt::Debug> std::error::Error for SendError<T> {}
+
+/// 发送方已经 drop，值不会再变化
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecvError;
+
+impl fmt::Display for RecvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "watch 发送方已关闭")
+    }
+}
+
+impl std::error::Error for RecvError {}
+
+/// 最新的值、它的版本号，以及等待变化的接收方的 waker
+///
+/// 只保存最新的值：接收方来不及看的中间值直接被覆盖，不会积压。
+struct State<T> {
+    value: T,
+    version: u64,
+    sender_closed: bool,
+    receivers: usize,
+    wakers: HashMap<usize, Waker>,
+    next_receiver_id: usize,
+}
+
+type Shared<T> = Arc<Mutex<State<T>>>;
+
+/// 创建 watch channel：接收方总能读到最新的值，并且可以等待它变化
+pub fn channel<T>(initial: T) -> (Sender<T>, Receiver<T>) {
+    let shared = Arc::new(Mutex::new(State {
+        value: initial,
+        version: 0,
+        sender_closed: false,
+        receivers: 1,
+        wakers: HashMap::new(),
+        next_receiver_id: 1,
+    }));
+    (
+        Sender {
+            shared: shared.clone(),
+        },
+        Receiver {
+            shared,
+            id: 1,
+            seen: 0,
+        },
+    )
+}
+
+/// 读取当前值期间持有锁，不要跨过 await 持有
+pub struct Ref<'a, T> {
+    guard: MutexGuard<'a, State<T>>,
+}
+
+impl<T> Deref for Ref<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.guard.value
+    }
+}
+
+pub struct Sender<T> {
+    shared: Shared<T>,
+}
+
+impl<T> Sender<T> {
+    /// 替换当前值并通知所有接收方；没有接收方时失败
+    pub fn send(&self, value: T) -> Result<(), SendError<T>> {
+        let wakers: Vec<_> = {
+            let mut state = self.shared.lock().unwrap();
+            if state.receivers == 0 {
+                return Err(SendError(value));
+            }
+            state.value = value;
+            state.version += 1;
+            state.wakers.drain().map(|(_, waker)| waker).collect()
+        };
+        for waker in wakers {
+            waker.wake();
+        }
+        Ok(())
+    }
+
+    pub fn borrow(&self) -> Ref<'_, T> {
+        Ref {
+            guard: self.shared.lock().unwrap(),
+        }
+    }
+
+    pub fn subscribe(&self) -> Receiver<T> {
+        let mut state = self.shared.lock().unwrap();
+        state.receivers += 1;
+        state.next_receiver_id += 1;
+        Receiver {
+            shared: self.shared.clone(),
+            id: state.next_receiver_id,
+            seen: state.version,
+        }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let wakers: Vec<_> = {
+            let mut state = self.shared.lock().unwrap();
+            state.sender_closed = true;
+            state.wakers.drain().map(|(_, waker)| waker).collect()
+        };
+        for waker in wakers {
+            waker.wake();
+        }
+    }
+}
+
+pub struct Receiver<T> {
+    shared: Shared<T>,
+    id: usize,
+    // 已经看过的版本
+    seen: u64,
+}
+
+impl<T> Receiver<T> {
+    /// 读取当前值，不改变"已看过"的版本
+    pub fn borrow(&self) -> Ref<'_, T> {
+        Ref {
+            guard: self.shared.lock().unwrap(),
+        }
+    }
+
+    /// 读取当前值，并标记为已看过
+    pub fn borrow_and_update(&mut self) -> Ref<'_, T> {
+        let guard = self.shared.lock().unwrap();
+        self.seen = guard.version;
+        Ref { guard }
+    }
+
+    /// 等待一个还没看过的新值；发送方 drop 后返回 `RecvError`
+    pub fn changed(&mut self) -> Changed<'_, T> {
+        Changed { receiver: self }
+    }
+}
+
+impl<T> Clone for Receiver<T> {
+    fn clone(&self) -> Self {
+        let mut state = self.shared.lock().unwrap();
+        state.receivers += 1;
+        state.next_receiver_id += 1;
+        Self {
+            shared: self.shared.clone(),
+            id: state.next_receiver_id,
+            seen: self.seen,
+        }
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        let mut state = self.shared.lock().unwrap();
+        state.receivers -= 1;
+        state.wakers.remove(&self.id);
+    }
+}
+
+/// `Receiver::changed` 返回的 future
+pub struct Changed<'a, T> {
+    receiver: &'a mut Receiver<T>,
+}
+
+impl<T> Future for Changed<'_, T> {
+    type Output = Result<(), RecvError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let receiver = &mut self.get_mut().receiver;
+        let mut state = receiver.shared.lock().unwrap();
+        if state.version != receiver.seen {
+            receiver.seen = state.version;
+            return Poll::Ready(Ok(()));
+        }
+        if state.sender_closed {
+            return Poll::Ready(Err(RecvError));
+        }
+        state.wakers.insert(receiver.id, cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+/// 每 10ms 更新一次配置版本，一共 5 次
+async fn update_config(sender: Sender<String>) {
+    for version in 1..=5 {
+        AsyncTimerFuture::new(Duration::from_millis(10)).await;
+        sender.send(format!("config-v{version}")).unwrap();
+    }
+}
+
+/// 每次看到变化时记录当前值，处理一次需要 `delay`
+async fn watch_config(mut receiver: Receiver<String>, delay: Duration) -> Vec<String> {
+    let mut seen = Vec::new();
+    while receiver.changed().await.is_ok() {
+        seen.push(receiver.borrow_and_update().clone());
+        if !delay.is_zero() {
+            AsyncTimerFuture::new(delay).await;
+        }
+    }
+    seen
+}
+
+fn check(name: &str, fast: &[String], slow: &[String]) {
+    println!("{name}: 快的接收方看到 {:?}", fast);
+    println!("{name}: 慢的接收方看到 {:?}", slow);
+    assert_eq!(fast.len(), 5);
+    // 慢的接收方跳过了中间的值，但最后一定看到最新的值
+    assert!(slow.len() < 5);
+    assert_eq!(slow.last().map(String::as_str), Some("config-v5"));
+}
+
+/// 测试 watch channel
+///
+/// 发送方每 10ms 更新一次配置：
+/// - 快的接收方看到每一个版本
+/// - 慢的接收方处理一次要 25ms，中间的版本被覆盖，但最后总能看到最新的版本
+/// - 发送方 drop 后，`changed()` 返回错误，接收方退出循环
+///
+/// 同样的代码分别在 SimpleExecutor 和 tokio 的 current_thread 运行时上运行。
+pub fn test_watch() {
+    println!("\n=== watch 示例：最新值和变化通知 ===");
+
+    // SimpleExecutor（虚拟时钟）
+    let executor = SimpleExecutor::new_paused();
+    let (tx, fast) = channel("config-v0".to_string());
+    let slow = tx.subscribe();
+    let fast = executor.spawn(watch_config(fast, Duration::ZERO));
+    let slow = executor.spawn(watch_config(slow, Duration::from_millis(25)));
+    let (fast, slow) = executor.block_on(async {
+        update_config(tx).await;
+        (fast.await.unwrap(), slow.await.unwrap())
+    });
+    check("SimpleExecutor", &fast, &slow);
+
+    // tokio current_thread
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    let (fast, slow) = runtime.block_on(async {
+        let (tx, fast) = channel("config-v0".to_string());
+        let slow = tx.subscribe();
+        let fast = tokio::spawn(watch_config(fast, Duration::ZERO));
+        let slow = tokio::spawn(watch_config(slow, Duration::from_millis(25)));
+        update_config(tx).await;
+        (fast.await.unwrap(), slow.await.unwrap())
+    });
+    check("tokio", &fast, &slow);
+
+    // borrow 不改变已看过的版本
+    let (tx, mut rx) = channel(0);
+    tx.send(1).unwrap();
+    assert_eq!(*rx.borrow(), 1);
+    assert_eq!(*tx.borrow(), 1);
+    let mut cx = Context::from_waker(Waker::noop());
+    assert!(Pin::new(&mut rx.changed()).poll(&mut cx).is_ready());
+    assert!(Pin::new(&mut rx.changed()).poll(&mut cx).is_pending());
+    let clone = rx.clone();
+    drop(rx);
+    drop(clone);
+    assert_eq!(tx.send(2), Err(SendError(2)));
+
+    println!("\n关键点：");
+    println!("- 只保存最新的值和版本号，慢的接收方跳过中间值，不会积压");
+    println!("- changed() 比较版本号，没有新版本时保存 waker 等待");
+    println!("- 发送方 drop 时唤醒所有接收方，changed() 返回错误");
+}
@@ -0,0 +1,59 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// 时钟抽象：定时器驱动和 executor 通过它读取"现在"
+///
+/// - `SystemClock`：真实时间
+/// - `MockClock`：虚拟时间，只有被显式推进时才前进
+pub trait Clock: Send + Sync {
+    fn now(&self) -> Instant;
+
+    /// 如果是虚拟时钟，返回它，供定时器驱动推进时间
+    fn as_mock(&self) -> Option<&MockClock> {
+        None
+    }
+}
+
+/// 真实时钟，直接使用 `Instant::now()`
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// 虚拟时钟：类似 tokio 的 `start_paused`
+///
+/// 时间停在创建时刻，只有调用 `advance_to` 才会前进。
+/// 配合 `SimpleExecutor::new_paused()` 使用时，executor 发现所有任务都在等待，
+/// 就直接把时间推进到下一个定时器的到期时刻，不需要真的等待。
+pub struct MockClock {
+    base: Instant,
+    offset: Mutex<Duration>,
+}
+
+impl MockClock {
+    pub fn new() -> Self {
+        Self {
+            base: Instant::now(),
+            offset: Mutex::new(Duration::ZERO),
+        }
+    }
+
+    /// 推进到 `instant`；早于当前时刻时什么也不做
+    pub fn advance_to(&self, instant: Instant) {
+        let mut offset = self.offset.lock().unwrap();
+        *offset = (*offset).max(instant.saturating_duration_since(self.base));
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> Instant {
+        self.base + *self.offset.lock().unwrap()
+    }
+
+    fn as_mock(&self) -> Option<&MockClock> {
+        Some(self)
+    }
+}
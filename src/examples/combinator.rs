@@ -0,0 +1,542 @@
+use std::cell::Cell;
+use std::future::Future;
+use std::mem;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use super::custom_waker::AsyncTimerFuture;
+use super::pin_project::pin_project;
+use super::simple_executor::SimpleExecutor;
+
+/// 一个 future，或者它已经产生的结果
+///
+/// join 系列的基础：每个子 future 完成后把结果保存下来，之后不再 poll 它，
+/// 等所有子 future 都完成时再一起取走结果。
+pub enum MaybeDone<F: Future> {
+    Future(F),
+    Done(F::Output),
+    Gone,
+}
+
+impl<F: Future> MaybeDone<F> {
+    pub fn new(future: F) -> Self {
+        MaybeDone::Future(future)
+    }
+
+    /// 还没完成时 poll 一次；已经有结果时返回 `true`
+    pub fn poll_done(self: Pin<&mut Self>, cx: &mut Context<'_>) -> bool {
+        // 只有 Future 变体里的值是被 pin 住的，结果可以自由移动
+        let this = unsafe { self.get_unchecked_mut() };
+        match this {
+            MaybeDone::Future(future) => {
+                let future = unsafe { Pin::new_unchecked(future) };
+                match future.poll(cx) {
+                    Poll::Ready(output) => {
+                        // 原地覆盖会先 drop 掉 future，不会移动它
+                        *this = MaybeDone::Done(output);
+                        true
+                    }
+                    Poll::Pending => false,
+                }
+            }
+            MaybeDone::Done(_) => true,
+            MaybeDone::Gone => panic!("MaybeDone 的结果已经被取走"),
+        }
+    }
+
+    pub fn output(&self) -> Option<&F::Output> {
+        match self {
+            MaybeDone::Done(output) => Some(output),
+            _ => None,
+        }
+    }
+
+    pub fn take_output(self: Pin<&mut Self>) -> Option<F::Output> {
+        let this = unsafe { self.get_unchecked_mut() };
+        match this {
+            MaybeDone::Done(_) => match mem::replace(this, MaybeDone::Gone) {
+                MaybeDone::Done(output) => Some(output),
+                _ => unreachable!(),
+            },
+            _ => None,
+        }
+    }
+}
+
+/// 同时等待两个 future，输出两个结果
+pub fn join<A: Future, B: Future>(a: A, b: B) -> Join<A, B> {
+    Join {
+        a: MaybeDone::new(a),
+        b: MaybeDone::new(b),
+    }
+}
+
+pin_project! {
+    pub struct Join<A: Future, B: Future> {
+        #[pin]
+        a: MaybeDone<A>,
+        #[pin]
+        b: MaybeDone<B>,
+    }
+}
+
+impl<A: Future, B: Future> Future for Join<A, B> {
+    type Output = (A::Output, B::Output);
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // 结构化 pin：两个字段都不会被移动
+        let this = self.project();
+        let (mut a, mut b) = (this.a, this.b);
+        // 两个都要 poll，不能因为第一个没完成就跳过第二个
+        let a_done = a.as_mut().poll_done(cx);
+        let b_done = b.as_mut().poll_done(cx);
+        if !(a_done && b_done) {
+            return Poll::Pending;
+        }
+        Poll::Ready((a.take_output().unwrap(), b.take_output().unwrap()))
+    }
+}
+
+/// 同时等待任意数量的同类型 future，按传入的顺序输出结果
+pub fn join_all<I>(futures: I) -> JoinAll<I::Item>
+where
+    I: IntoIterator,
+    I::Item: Future,
+{
+    JoinAll {
+        futures: futures.into_iter().map(MaybeDone::new).collect(),
+    }
+}
+
+pub struct JoinAll<F: Future> {
+    // 放在堆上，JoinAll 本身移动时子 future 不会移动
+    futures: Box<[MaybeDone<F>]>,
+}
+
+impl<F: Future> Future for JoinAll<F> {
+    type Output = Vec<F::Output>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // 安全性：宏不能投影切片的元素，所以手写；子 future 在堆上，从不被移出 Box，JoinAll 本身可以是 Unpin
+        let this = self.get_mut();
+        let mut done = true;
+        for future in this.futures.iter_mut() {
+            done &= unsafe { Pin::new_unchecked(future) }.poll_done(cx);
+        }
+        if !done {
+            return Poll::Pending;
+        }
+        let outputs = this
+            .futures
+            .iter_mut()
+            .map(|future| unsafe { Pin::new_unchecked(future) }.take_output().unwrap())
+            .collect();
+        Poll::Ready(outputs)
+    }
+}
+
+/// 同时等待两个返回 `Result` 的 future：都成功时输出两个值，任何一个失败就立刻返回错误
+///
+/// 失败时另一个 future 随 `TryJoin` 一起被 drop，也就是被取消。
+pub fn try_join<A, B>(a: A, b: B) -> TryJoin<A, B>
+where
+    A: Future,
+    B: Future,
+{
+    TryJoin {
+        a: MaybeDone::new(a),
+        b: MaybeDone::new(b),
+    }
+}
+
+pin_project! {
+    pub struct TryJoin<A: Future, B: Future> {
+        #[pin]
+        a: MaybeDone<A>,
+        #[pin]
+        b: MaybeDone<B>,
+    }
+}
+
+impl<A, B, T, U, E> Future for TryJoin<A, B>
+where
+    A: Future<Output = Result<T, E>>,
+    B: Future<Output = Result<U, E>>,
+{
+    type Output = Result<(T, U), E>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let (mut a, mut b) = (this.a, this.b);
+        let a_done = a.as_mut().poll_done(cx);
+        if let Some(Err(_)) = a.output() {
+            return Poll::Ready(Err(a.take_output().unwrap().err().unwrap()));
+        }
+        let b_done = b.as_mut().poll_done(cx);
+        if let Some(Err(_)) = b.output() {
+            return Poll::Ready(Err(b.take_output().unwrap().err().unwrap()));
+        }
+        if !(a_done && b_done) {
+            return Poll::Pending;
+        }
+        match (a.take_output(), b.take_output()) {
+            (Some(Ok(a)), Some(Ok(b))) => Poll::Ready(Ok((a, b))),
+            _ => unreachable!(),
+        }
+    }
+}
+
+/// `select` 的结果：先完成的是哪一个
+#[derive(Debug, PartialEq, Eq)]
+pub enum Either<A, B> {
+    Left(A),
+    Right(B),
+}
+
+static NEXT_START: AtomicUsize = AtomicUsize::new(0);
+
+/// select 每次 poll 从哪个分支开始
+///
+/// 总是从第一个分支开始的话，第一个分支一直就绪时后面的分支永远轮不到（饿死），
+/// 所以默认每次换一个起点；`biased` 时按书写顺序，优先级由调用方决定。
+pub fn select_start(branches: usize, biased: bool) -> usize {
+    if biased {
+        0
+    } else {
+        NEXT_START.fetch_add(1, Ordering::Relaxed) % branches
+    }
+}
+
+/// 等待两个 future 中先完成的一个，另一个随 `Select` 一起被 drop
+///
+/// 两个都就绪时轮流选择，不会总是偏向 `a`。
+pub fn select<A: Future, B: Future>(a: A, b: B) -> Select<A, B> {
+    Select { a, b, biased: false }
+}
+
+/// 和 `select` 一样，但两个都就绪时总是选择 `a`
+pub fn select_biased<A: Future, B: Future>(a: A, b: B) -> Select<A, B> {
+    Select { a, b, biased: true }
+}
+
+pin_project! {
+    pub struct Select<A, B> {
+        #[pin]
+        a: A,
+        #[pin]
+        b: B,
+        biased: bool,
+    }
+}
+
+impl<A: Future, B: Future> Future for Select<A, B> {
+    type Output = Either<A::Output, B::Output>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let (mut a, mut b) = (this.a, this.b);
+        let start = select_start(2, *this.biased);
+        for index in [start, 1 - start] {
+            if index == 0 {
+                if let Poll::Ready(output) = a.as_mut().poll(cx) {
+                    return Poll::Ready(Either::Left(output));
+                }
+            } else if let Poll::Ready(output) = b.as_mut().poll(cx) {
+                return Poll::Ready(Either::Right(output));
+            }
+        }
+        Poll::Pending
+    }
+}
+
+/// 等待一组 future 中先完成的一个，输出它的结果、下标和剩下的 future
+///
+/// 剩下的 future 还没完成，可以继续传给下一次 `select_all`，所以要求 `Unpin`（用 `Box::pin` 包一层）。
+pub fn select_all<I>(futures: I) -> SelectAll<I::Item>
+where
+    I: IntoIterator,
+    I::Item: Future + Unpin,
+{
+    let futures: Vec<_> = futures.into_iter().collect();
+    assert!(!futures.is_empty(), "select_all 至少需要一个 future");
+    SelectAll { futures }
+}
+
+pub struct SelectAll<F> {
+    futures: Vec<F>,
+}
+
+impl<F: Future + Unpin> Future for SelectAll<F> {
+    type Output = (F::Output, usize, Vec<F>);
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let len = self.futures.len();
+        let start = select_start(len, false);
+        for offset in 0..len {
+            let index = (start + offset) % len;
+            if let Poll::Ready(output) = Pin::new(&mut self.futures[index]).poll(cx) {
+                self.futures.remove(index);
+                return Poll::Ready((output, index, mem::take(&mut self.futures)));
+            }
+        }
+        Poll::Pending
+    }
+}
+
+// 宏放在子模块里：直接在这里 `pub(crate) use join;` 会和同名的函数 `join` 冲突
+mod macros {
+    /// 同时等待任意数量、任意类型的 future，输出结果的元组
+    ///
+    /// 展开成嵌套的 `Join`：`join!(a, b, c)` 就是 `join(a, join(b, c))`，
+    /// 再把输出的 `(a, (b, c))` 拆成 `(a, b, c)`。只能在 async 代码中使用（展开后包含 `.await`）。
+    ///
+    /// 宏每递归展开一次，生成的 `out` 就属于一个新的卫生上下文，
+    /// 所以每个结果都有自己的局部变量，不会互相覆盖。
+    macro_rules! join {
+        // 嵌套的 future：join(a, join(b, c))
+        (@future $fut:expr) => { $fut };
+        (@future $head:expr, $($rest:expr),+) => {
+            $crate::examples::combinator::join($head, $crate::examples::combinator::join!(@future $($rest),+))
+        };
+        // 和嵌套的输出形状相同的模式：(a, (b, c))
+        (@pattern $out:ident) => { $out };
+        (@pattern $head:ident $($rest:ident)+) => {
+            ($head, $crate::examples::combinator::join!(@pattern $($rest)+))
+        };
+        (@munch [$(($out:ident $fut:expr))*]) => {{
+            let $crate::examples::combinator::join!(@pattern $($out)*) =
+                $crate::examples::combinator::join!(@future $($fut),*).await;
+            ($($out,)*)
+        }};
+        (@munch [$($acc:tt)*] $head:expr, $($rest:expr,)*) => {
+            $crate::examples::combinator::join!(@munch [$($acc)* (out $head)] $($rest,)*)
+        };
+        ($($fut:expr),+ $(,)?) => {
+            $crate::examples::combinator::join!(@munch [] $($fut,)+)
+        };
+    }
+
+    /// 和 `join!` 一样，但每个 future 都返回 `Result`：全部成功时输出 `Ok(元组)`，
+    /// 任何一个失败就立刻返回那个错误，其余的 future 被 drop（取消）
+    ///
+    /// 展开成嵌套的 `TryJoin`：`try_join!(a, b, c)` 就是 `try_join(a, try_join(b, c))`。
+    macro_rules! try_join {
+        (@future $fut:expr) => { $fut };
+        (@future $head:expr, $($rest:expr),+) => {
+            $crate::examples::combinator::try_join(
+                $head,
+                $crate::examples::combinator::try_join!(@future $($rest),+),
+            )
+        };
+        (@munch [$(($out:ident $fut:expr))*]) => {
+            match $crate::examples::combinator::try_join!(@future $($fut),*).await {
+                ::std::result::Result::Ok($crate::examples::combinator::join!(@pattern $($out)*)) => {
+                    ::std::result::Result::Ok(($($out,)*))
+                }
+                ::std::result::Result::Err(error) => ::std::result::Result::Err(error),
+            }
+        };
+        (@munch [$($acc:tt)*] $head:expr, $($rest:expr,)*) => {
+            $crate::examples::combinator::try_join!(@munch [$($acc)* (out $head)] $($rest,)*)
+        };
+        ($($fut:expr),+ $(,)?) => {
+            $crate::examples::combinator::try_join!(@munch [] $($fut,)+)
+        };
+    }
+
+    /// 等待多个分支中先完成的一个，执行它的处理代码，其余分支的 future 被 drop
+    ///
+    /// ```ignore
+    /// select! {
+    ///     value = channel.recv() => println!("收到 {value:?}"),
+    ///     _ = AsyncTimerFuture::new(timeout) => println!("超时"),
+    /// }
+    /// ```
+    ///
+    /// 展开成嵌套的 `Select`：`select(a, select(b, c))`，输出 `Either<A, Either<B, C>>`。
+    /// 每一层的 `Select` 每次 poll 都换一边先 poll（见 `select_start`），
+    /// 需要按书写顺序决定优先级时用 `select_biased!`。
+    ///
+    /// 先等到胜出的分支、drop 掉其余分支的 future，再执行处理代码：处理代码里可以 `.await`，
+    /// 输掉的分支不会继续占着锁的等待队列或者定时器。模式必须是不可反驳的（irrefutable）。
+    macro_rules! select {
+        // 嵌套的 future；$select 是 select 或 select_biased
+        (@future $select:ident ($fut:expr)) => { $fut };
+        (@future $select:ident ($head:expr) $(($rest:expr))+) => {
+            $crate::examples::combinator::$select(
+                $head,
+                $crate::examples::combinator::select!(@future $select $(($rest))+),
+            )
+        };
+        // 按嵌套的 Either 找到胜出的分支，执行它的处理代码
+        (@dispatch $output:ident ($p:pat) ($body:expr)) => {
+            match $output {
+                $p => $body,
+            }
+        };
+        (@dispatch $output:ident ($p:pat) ($body:expr) $($rest:tt)+) => {
+            match $output {
+                $crate::examples::combinator::Either::Left($p) => $body,
+                $crate::examples::combinator::Either::Right(rest) => {
+                    $crate::examples::combinator::select!(@dispatch rest $($rest)+)
+                }
+            }
+        };
+        (@select $select:ident $($p:pat = $fut:expr => $body:expr,)+) => {{
+            // 这条语句结束时整个 Select 已经被 drop，输掉的分支随之取消
+            let output = $crate::examples::combinator::select!(@future $select $(($fut))+).await;
+            $crate::examples::combinator::select!(@dispatch output $(($p) ($body))+)
+        }};
+        ($($p:pat = $fut:expr => $body:expr),+ $(,)?) => {
+            $crate::examples::combinator::select!(@select select $($p = $fut => $body,)+)
+        };
+    }
+
+    /// 和 `select!` 一样，但总是按书写顺序 poll：多个分支同时就绪时选择最前面的
+    macro_rules! select_biased {
+        ($($p:pat = $fut:expr => $body:expr),+ $(,)?) => {
+            $crate::examples::combinator::select!(@select select_biased $($p = $fut => $body,)+)
+        };
+    }
+
+    pub(crate) use {join, select, select_biased, try_join};
+}
+pub(crate) use macros::{join, select, select_biased, try_join};
+
+/// drop 时把标志设为 `true`
+struct SetOnDrop<'a>(&'a Cell<bool>);
+
+impl Drop for SetOnDrop<'_> {
+    fn drop(&mut self) {
+        self.0.set(true);
+    }
+}
+
+/// 等待 `ms` 毫秒后返回 `value`
+async fn after(ms: u64, value: u32) -> u32 {
+    AsyncTimerFuture::new(Duration::from_millis(ms)).await;
+    value
+}
+
+/// 等待 `ms` 毫秒后返回 `result`
+async fn fallible(ms: u64, result: Result<u32, &'static str>) -> Result<u32, &'static str> {
+    AsyncTimerFuture::new(Duration::from_millis(ms)).await;
+    result
+}
+
+/// 测试 join / try_join / select 系列组合子
+///
+/// 全部运行在 SimpleExecutor 的虚拟时钟上，不依赖 tokio：
+/// 1. `join!` / `join` / `join_all`：并发等待，总用时等于最慢的那个
+/// 2. `try_join!` / `try_join`：一个失败就立刻返回，不等其余的
+/// 3. `select!` / `select_biased!` / `select` / `select_all`：先完成的胜出；
+///    同时就绪时 `select!` 轮流选择，`select_biased!` 总是选第一个；
+///    处理代码执行之前，输掉的分支已经被 drop
+pub fn test_combinators() {
+    println!("\n=== 组合子示例：不依赖 tokio 的 join / select ===");
+    let executor = SimpleExecutor::new_paused();
+
+    // 1. join
+    let start = executor.now();
+    let (a, b, c) = executor.block_on(async { join!(after(30, 1), after(10, 2), after(20, 3)) });
+    assert_eq!((a, b, c), (1, 2, 3));
+    assert_eq!(executor.now() - start, Duration::from_millis(30));
+
+    let start = executor.now();
+    let pair = executor.block_on(join(after(10, 1), after(20, 2)));
+    let all = executor.block_on(join_all((1..=5).map(|i| after(10 * u64::from(6 - i), i))));
+    assert_eq!(pair, (1, 2));
+    assert_eq!(all, vec![1, 2, 3, 4, 5]);
+    assert_eq!(executor.now() - start, Duration::from_millis(70));
+    println!("join!: {:?}，join_all: {:?}，并发等待，用时等于最慢的那个", (a, b, c), all);
+
+    // 2. try_join
+    let start = executor.now();
+    let ok = executor.block_on(async { try_join!(fallible(10, Ok(1)), fallible(20, Ok(2))) });
+    assert_eq!(ok, Ok((1, 2)));
+    let err = executor.block_on(async {
+        try_join!(fallible(50, Ok(1)), fallible(10, Err("第二个失败")), fallible(50, Ok(3)))
+    });
+    assert_eq!(err, Err("第二个失败"));
+    let err = executor.block_on(try_join(fallible(10, Err("a 失败")), fallible(50, Ok(2))));
+    assert_eq!(err, Err("a 失败"));
+    // 20ms（都成功）+ 10ms + 10ms：失败时不等其余的 future
+    assert_eq!(executor.now() - start, Duration::from_millis(40));
+    println!("try_join!: {:?}，第一个错误立刻返回", err);
+
+    // 3. select
+    let start = executor.now();
+    let winner = executor.block_on(async {
+        select! {
+            value = after(20, 1) => format!("慢的分支 {value}"),
+            value = after(10, 2) => format!("快的分支 {value}"),
+        }
+    });
+    assert_eq!(winner, "快的分支 2");
+    assert_eq!(executor.now() - start, Duration::from_millis(10));
+    println!("select!: {winner} 胜出，另一个分支被取消");
+
+    // 三个分支：处理代码执行之前，输掉的分支已经被 drop
+    let loser_dropped = Cell::new(false);
+    let winner = executor.block_on(async {
+        select! {
+            _ = after(30, 1) => unreachable!(),
+            value = after(10, 2) => {
+                assert!(loser_dropped.get(), "处理代码执行时输掉的分支还活着");
+                // 处理代码里可以 await
+                after(5, value).await
+            },
+            _ = async {
+                let _guard = SetOnDrop(&loser_dropped);
+                after(20, 3).await
+            } => unreachable!(),
+        }
+    });
+    assert_eq!(winner, 2);
+    assert!(loser_dropped.get());
+
+    // 两个分支都已经就绪
+    let (mut first, mut second) = (0, 0);
+    let mut biased_first = 0;
+    for _ in 0..10 {
+        executor.block_on(async {
+            select! {
+                _ = std::future::ready(()) => first += 1,
+                _ = std::future::ready(()) => second += 1,
+            }
+            select_biased! {
+                _ = std::future::ready(()) => biased_first += 1,
+                _ = std::future::ready(()) => {},
+            }
+        });
+    }
+    println!("同时就绪 10 次：select! 选择了 {first} 次第一个、{second} 次第二个；select_biased! 选择了 {biased_first} 次第一个");
+    assert!(first > 0 && second > 0);
+    assert_eq!(biased_first, 10);
+
+    let either = executor.block_on(select(after(20, 1), after(10, 2)));
+    assert_eq!(either, Either::Right(2));
+    let either = executor.block_on(select_biased(std::future::ready(1), std::future::ready(2)));
+    assert_eq!(either, Either::Left(1));
+
+    // select_all：依次取出先完成的，剩下的继续等待
+    let mut pending: Vec<_> = [30, 10, 20]
+        .into_iter()
+        .map(|ms| Box::pin(after(ms, ms as u32)))
+        .collect();
+    let mut order = Vec::new();
+    while !pending.is_empty() {
+        let (value, _, rest) = executor.block_on(select_all(pending));
+        order.push(value);
+        pending = rest;
+    }
+    assert_eq!(order, vec![10, 20, 30]);
+    println!("select_all 依次完成: {:?}", order);
+
+    println!("\n关键点：");
+    println!("- join 把每个 future 包进 MaybeDone，全部完成后一起取出结果；join! 就是嵌套的 Join");
+    println!("- try_join 遇到第一个错误立刻返回，drop 其余的 future 就是取消它们");
+    println!("- select 默认轮换起始分支避免饿死，select_biased 按书写顺序决定优先级");
+}
@@ -2,23 +2,40 @@ use std::future::Future;
 use std::pin::Pin;
 use std::sync::{Arc, Mutex};
 use std::task::{Context, Poll, Waker};
-use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
+
+use super::clock::{MockClock, SystemClock};
+use super::simple_executor::SimpleExecutor;
+use super::timer::{TimerDriver, TimerKey};
+use super::waker::CountingWaker;
 
 /// 自定义 Future：演示 Waker 的实际用途
 /// 
 /// 这个 Future 模拟一个异步操作：
-/// 1. 第一次 poll 时，保存 waker 并返回 Pending
-/// 2. 在后台线程中，模拟异步操作（等待一段时间）
-/// 3. 操作完成后，调用 waker 通知 executor
+/// 1. 创建时，向定时器驱动（见 `timer.rs` 的时间轮）登记到期时刻
+/// 2. 第一次 poll 时，保存 waker 并返回 Pending
+/// 3. 到期后，定时器驱动线程设置完成标志，调用 waker 通知 executor
 /// 4. Executor 收到通知后，再次 poll，这次返回 Ready
+///
+/// 早期版本为每个定时器启动一个线程并 `thread::sleep`，几千个定时器就撑不住了；
+/// 现在所有定时器共享同一个驱动线程，future 本身的用法不变。
+///
+/// drop 时会从时间轮中取消登记，之后驱动线程不会再调用它保存的 waker。
+///
+/// 到期时刻按当前线程的定时器驱动的时钟计算：在 `SimpleExecutor::new_paused()`
+/// 的 `enter()` 范围内创建时使用虚拟时钟，测试时不需要真的等待。
 pub struct AsyncTimerFuture {
     shared_state: Arc<Mutex<SharedState>>,
+    driver: Arc<TimerDriver>,
+    key: TimerKey,
 }
 
-struct SharedState {
-    completed: bool,
-    waker: Option<Waker>,
+/// future 和唤醒它的一方（定时器驱动）共享的状态
+pub(crate) struct SharedState {
+    pub(crate) completed: bool,
+    pub(crate) waker: Option<Waker>,
+    // 每次 reset 加一；驱动登记时记下当时的值，到期时值不同说明这次到期已经过时了
+    pub(crate) generation: u64,
 }
 
 impl AsyncTimerFuture {
@@ -28,27 +45,45 @@ impl AsyncTimerFuture {
             // 注意：waker 初始化为 None，此时还没有 Context，无法获取 waker
             // waker 会在第一次 poll() 时被注入（见 poll() 方法的注释）
             waker: None,
+            generation: 0,
         }));
 
-        // 在后台线程中模拟异步操作
-        let state_clone = shared_state.clone();
-        thread::spawn(move || {
-            // 模拟异步操作：等待指定时间
-            thread::sleep(duration);
-            
-            // 操作完成，设置标志并唤醒任务
-            let mut state = state_clone.lock().unwrap();
-            state.completed = true;
-            
-            // 关键：调用 waker 通知 executor 可以重新 poll 了
-            // 注意：此时 waker 应该已经被 poll() 方法注入（见 poll() 方法的注释）
-            if let Some(waker) = state.waker.take() {
-                println!("[后台线程] 异步操作完成，唤醒 executor...");
-                waker.wake();
-            }
-        });
-
-        Self { shared_state }
+        // 向定时器驱动登记：到期时驱动线程会设置 completed 并调用 waker
+        // 注意：此时 waker 还没有被注入，驱动线程只在到期时才读取它
+        let driver = TimerDriver::current();
+        let key = driver.register(driver.now() + duration, shared_state.clone());
+
+        Self {
+            shared_state,
+            driver,
+            key,
+        }
+    }
+
+    /// 重新设置到期时刻，不需要创建新的 future
+    ///
+    /// 还在等待的定时器会被移到新的槽位；已经完成的定时器会重新开始等待。
+    /// 已经保存的 waker 保持不变，到期时照常唤醒。
+    ///
+    /// 驱动可能已经把旧的登记从时间轮中取出、正准备在锁外唤醒它；
+    /// 这里增加 `generation`，那次过时的到期就不会让定时器按旧的时刻完成。
+    pub fn reset(&mut self, deadline: Instant) {
+        {
+            let mut state = self.shared_state.lock().unwrap();
+            state.completed = false;
+            state.generation += 1;
+        }
+        self.driver.reset(self.key, deadline, self.shared_state.clone());
+    }
+}
+
+impl Drop for AsyncTimerFuture {
+    fn drop(&mut self) {
+        // 先从时间轮中取消，驱动线程就不会再处理这个定时器
+        self.driver.cancel(self.key);
+        // 再丢弃保存的 waker：如果驱动线程刚好在 fire 这个定时器，
+        // 它持有 SharedState 的锁，这里会等它唤醒完成后才继续
+        self.shared_state.lock().unwrap().waker = None;
     }
 }
 
@@ -80,14 +115,15 @@ impl Future for AsyncTimerFuture {
             // ```
             // 1. new() 创建 future
             //    └─> shared_state.waker = None
-            //    └─> 启动后台线程（等待中）
+            //    └─> 在定时器驱动的时间轮中登记到期时刻
             //
             // 2. tokio executor 第一次调用 poll()
             //    └─> 传入 Context（包含 waker）
             //    └─> poll() 中：state.waker = Some(cx.waker().clone())  ← 注入时机
             //    └─> 返回 Poll::Pending
             //
-            // 3. 后台线程完成等待
+            // 3. 定时器到期，驱动线程处理这个槽位
+            //    └─> state.completed = true
             //    └─> state.waker.take() 获取 waker
             //    └─> waker.wake() 通知 executor
             //
@@ -99,7 +135,7 @@ impl Future for AsyncTimerFuture {
             // ## 4. 为什么每次 poll 都要更新 waker
             // - Future 可能在 executor 的任务之间移动
             // - 每次 poll 时的 cx.waker() 可能指向不同的任务
-            // - 如果不更新，后台线程唤醒的可能是旧任务，而不是当前任务
+            // - 如果不更新，驱动线程唤醒的可能是旧任务，而不是当前任务
             // - 这会导致 executor 运行错误的任务，或者任务永远不会被唤醒
             //
             // ## 5. 性能考虑
@@ -114,24 +150,39 @@ impl Future for AsyncTimerFuture {
 }
 
 /// 测试自定义 Waker 的实际用途（使用 tokio 运行时，通过 await）
+///
+/// 定时器使用虚拟时钟：tokio 不知道我们的时钟什么时候该前进，
+/// 所以用 `join!` 的第二个分支在 future 挂起后把虚拟时间推进到到期时刻。
+/// 整个例子瞬间完成，虚拟时间正好前进 1 秒。
 pub async fn test_custom_waker() {
     println!("\n=== 自定义 Waker 示例：展示实际用途（使用 await） ===");
     
     println!("\n场景：模拟一个异步定时器 Future");
-    println!("1. Future 在后台线程中等待 1 秒");
+    println!("1. Future 在定时器驱动中登记 1 秒后到期（虚拟时钟）");
     println!("2. 第一次 poll 返回 Pending，并保存 waker");
-    println!("3. 1 秒后，后台线程调用 waker.wake()");
+    println!("3. 虚拟时间推进 1 秒后，定时器驱动调用 waker.wake()");
     println!("4. Tokio executor 收到通知，重新 poll future");
     println!("5. 这次 poll 返回 Ready\n");
     
-    let future = AsyncTimerFuture::new(Duration::from_secs(1));
+    let driver = TimerDriver::new(Arc::new(MockClock::new()));
+    let future = {
+        let _guard = driver.enter();
+        AsyncTimerFuture::new(Duration::from_secs(1))
+    };
     
     let start = std::time::Instant::now();
-    let result = future.await;
+    let virtual_start = driver.now();
+    let (result, _) = tokio::join!(future, async {
+        // 先让出一次，保证 future 已经被 poll 过（waker 已经保存）
+        tokio::task::yield_now().await;
+        driver.advance_to_next();
+    });
     let elapsed = start.elapsed();
+    let virtual_elapsed = driver.now() - virtual_start;
     
     println!("\n结果: {}", result);
-    println!("总耗时: {:?} (包含等待时间)", elapsed);
+    println!("虚拟时间: {:?}，真实耗时: {:?}", virtual_elapsed, elapsed);
+    assert_eq!(virtual_elapsed, Duration::from_secs(1));
     
     println!("\n关键点：");
     println!("- Waker 是连接异步操作完成和 executor 重新 poll 的桥梁");
@@ -183,3 +234,132 @@ pub fn test_custom_waker_with_block_on() {
     println!("- 在 async 函数中，使用 await 更常见，不需要显式创建 Runtime");
     println!("\n注意：如果在 tokio 运行时内部，应该使用 Handle::current().block_on()");
 }
+
+/// 到期时重置另一个定时器：它和自己同时到期，已经被驱动从时间轮中取出、还没有被唤醒
+struct ResetOnWake {
+    timer: Arc<Mutex<AsyncTimerFuture>>,
+    deadline: Instant,
+}
+
+impl std::task::Wake for ResetOnWake {
+    fn wake(self: Arc<Self>) {
+        self.timer.lock().unwrap().reset(self.deadline);
+    }
+}
+
+/// 测试 AsyncTimerFuture 的取消和重置
+///
+/// 1、2、4 使用单独的定时器驱动和虚拟时钟，不受其他示例的定时器影响，也不需要真的等待：
+///
+/// 1. poll 一次后 drop：定时器从时间轮中移除，时间推进到它的到期时刻之后只唤醒了没有被 drop 的那个
+/// 2. 创建并 drop 一千个定时器：时间轮中没有残留，之后也没有可以到期的定时器；
+///    真实时钟的驱动在 executor drop 后也不会留下线程
+/// 3. reset：把 1 秒的定时器改成 100ms 后到期
+/// 4. 驱动已经取出、正要唤醒的定时器被 reset：按新的时刻完成，不会被旧的到期提前完成
+pub fn test_timer_cancellation() {
+    println!("\n=== AsyncTimerFuture 示例：drop 时取消，reset 重新设置到期时刻 ===");
+
+    let driver = TimerDriver::new(Arc::new(MockClock::new()));
+    let counter = CountingWaker::new();
+    let waker = Waker::from(counter.clone());
+    let mut cx = Context::from_waker(&waker);
+    let start = driver.now();
+
+    // 1. 两个定时器都 poll 一次（保存 waker），drop 先到期的那个
+    let (mut cancelled, mut kept) = {
+        let _guard = driver.enter();
+        (
+            AsyncTimerFuture::new(Duration::from_millis(50)),
+            AsyncTimerFuture::new(Duration::from_millis(100)),
+        )
+    };
+    assert!(Pin::new(&mut cancelled).poll(&mut cx).is_pending());
+    assert!(Pin::new(&mut kept).poll(&mut cx).is_pending());
+    assert_eq!(driver.pending(), 2);
+    drop(cancelled);
+    assert_eq!(driver.pending(), 1);
+
+    // 下一个到期的是 100ms 的定时器：50ms 的已经不在时间轮中了
+    assert!(driver.advance_to_next());
+    assert_eq!(driver.now() - start, Duration::from_millis(100));
+    println!("drop 后经过到期时刻，唤醒次数: {}（只有没被 drop 的那个）", counter.wakes());
+    assert_eq!(counter.wakes(), 1);
+    assert!(Pin::new(&mut kept).poll(&mut cx).is_ready());
+    assert_eq!(driver.pending(), 0);
+
+    // 2. 一千个定时器：drop 后不会留下登记，也不会再唤醒任何 waker
+    let futures: Vec<_> = {
+        let _guard = driver.enter();
+        (0..1000)
+            .map(|_| AsyncTimerFuture::new(Duration::from_secs(60)))
+            .collect()
+    };
+    assert_eq!(driver.pending(), 1000);
+    drop(futures);
+    assert_eq!(driver.pending(), 0);
+    // 没有任何定时器可以到期了
+    assert!(!driver.advance_to_next());
+    assert_eq!(counter.wakes(), 1);
+    println!("创建并 drop 1000 个定时器后，时间轮中剩下: {}", driver.pending());
+
+    // 真实时钟：定时器共用驱动的一个后台线程，executor drop 后这个线程也退出了
+    let executor = SimpleExecutor::with_clock(Arc::new(SystemClock));
+    let real_driver = {
+        let _guard = executor.enter();
+        let futures: Vec<_> = (0..1000)
+            .map(|_| AsyncTimerFuture::new(Duration::from_secs(60)))
+            .collect();
+        let real_driver = TimerDriver::current();
+        assert_eq!(real_driver.pending(), 1000);
+        drop(futures);
+        assert_eq!(real_driver.pending(), 0);
+        real_driver
+    };
+    assert!(real_driver.has_background_thread());
+    drop(executor);
+    assert!(!real_driver.has_background_thread());
+    println!("executor drop 后，驱动的后台线程已经退出");
+
+    // 3. reset：把 1 秒的定时器提前到 100ms
+    let executor = SimpleExecutor::new();
+    let start = Instant::now();
+    let mut future = AsyncTimerFuture::new(Duration::from_secs(1));
+    future.reset(start + Duration::from_millis(100));
+    let result = executor.block_on(future);
+    let elapsed = start.elapsed();
+    println!("reset 后的结果: {}，耗时: {:?}", result, elapsed);
+    assert!(elapsed >= Duration::from_millis(100) && elapsed < Duration::from_secs(1));
+
+    // 4. 两个定时器在同一个 tick 到期：第一个的 waker 把第二个 reset 到 50ms，
+    //    这时第二个已经被取出，驱动接着就会唤醒它
+    let start = driver.now();
+    let (mut first, second) = {
+        let _guard = driver.enter();
+        (
+            AsyncTimerFuture::new(Duration::from_millis(10)),
+            Arc::new(Mutex::new(AsyncTimerFuture::new(Duration::from_millis(10)))),
+        )
+    };
+    let reset_waker = Waker::from(Arc::new(ResetOnWake {
+        timer: second.clone(),
+        deadline: start + Duration::from_millis(50),
+    }));
+    assert!(Pin::new(&mut first).poll(&mut Context::from_waker(&reset_waker)).is_pending());
+    assert!(Pin::new(&mut *second.lock().unwrap()).poll(&mut cx).is_pending());
+    assert!(driver.advance_to_next());
+    assert_eq!(driver.now() - start, Duration::from_millis(10));
+    // 旧的到期被忽略：第二个定时器还在等待，也没有被唤醒
+    assert!(Pin::new(&mut *second.lock().unwrap()).poll(&mut cx).is_pending());
+    assert_eq!(counter.wakes(), 1);
+    assert!(driver.advance_to_next());
+    assert_eq!(driver.now() - start, Duration::from_millis(50));
+    assert_eq!(counter.wakes(), 2);
+    assert!(Pin::new(&mut *second.lock().unwrap()).poll(&mut cx).is_ready());
+    println!("已经取出的定时器被 reset 后，在新的时刻 {:?} 完成", driver.now() - start);
+
+    println!("\n关键点：");
+    println!("- drop 时从时间轮取消登记，并在 SharedState 的锁内丢弃 waker");
+    println!("- 所有定时器共享一个驱动线程，drop 不会留下任何线程");
+    println!("- reset 只是把定时器移到新的槽位，不需要创建新的 future");
+    println!("- reset 增加 generation，已经在路上的旧的到期不会让定时器提前完成");
+}
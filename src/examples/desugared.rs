@@ -0,0 +1,280 @@
+use std::cell::RefCell;
+use std::future::{Future, poll_fn, ready};
+use std::pin::{Pin, pin};
+use std::rc::Rc;
+use std::task::{Context, Poll, Waker};
+use std::time::Duration;
+
+use desugar::desugar;
+
+use super::greet_coroutine::poll_after_completion;
+use super::quiet_panic::panic_message;
+use super::simple_executor::SimpleExecutor;
+use super::timer::{now, sleep};
+
+type Log = Rc<RefCell<Vec<String>>>;
+
+/// 用同一个函数体定义两个函数：`#[desugar]` 展开成的状态机，和编译器生成的 async fn
+macro_rules! desugar_and_compile {
+    ($(#[$attr:meta])* fn $desugared:ident / $compiled:ident($($arg:ident: $ty:ty),*) -> $output:ty { $($body:tt)* }) => {
+        $(#[$attr])*
+        #[desugar]
+        pub async fn $desugared($($arg: $ty),*) -> $output { $($body)* }
+
+        $(#[$attr])*
+        pub async fn $compiled($($arg: $ty),*) -> $output { $($body)* }
+    };
+}
+
+/// 等待 `ms` 毫秒，返回实际等待的时间
+async fn timed_sleep(ms: u64) -> u64 {
+    let start = now();
+    sleep(Duration::from_millis(ms)).await;
+    (now() - start).as_millis() as u64
+}
+
+desugar_and_compile! {
+    /// 直线代码：和 `greet` 一样，`greeting` 跨两个 await 存活，`farewell` 只跨第二个
+    fn greet_twice / greet_twice_compiled(name: String, log: Log) -> usize {
+        let greeting: String = format!("Hello, {name}!");
+        log.borrow_mut().push(greeting.clone());
+        sleep(Duration::from_millis(100)).await;
+        let farewell: String = format!("Goodbye, {name}!");
+        log.borrow_mut().push(farewell.clone());
+        sleep(Duration::from_millis(200)).await;
+        greeting.len() + farewell.len()
+    }
+}
+
+desugar_and_compile! {
+    /// 只有一个分支里有 await，结果赋值给外面声明的变量
+    fn wait_if / wait_if_compiled(ms: u64, log: Log) -> u64 {
+        let mut waited: u64 = 0;
+        if ms > 0 {
+            log.borrow_mut().push(format!("等待 {ms}ms"));
+            waited = timed_sleep(ms).await;
+        } else {
+            log.borrow_mut().push("不需要等待".to_string());
+        }
+        log.borrow_mut().push(format!("等了 {waited}ms"));
+        waited
+    }
+}
+
+desugar_and_compile! {
+    /// 循环里的 await：await 之后的 `continue` 回到循环开头，`break` 跳出循环
+    fn countdown / countdown_compiled(from: u32, log: Log) -> u64 {
+        let mut n: u32 = from;
+        let mut total: u64 = 0;
+        while n > 0 {
+            n -= 1;
+            if n == 2 {
+                log.borrow_mut().push("跳过 2".to_string());
+                continue;
+            }
+            let waited: u64 = timed_sleep(10).await;
+            total += waited;
+            if n.is_multiple_of(2) {
+                continue;
+            }
+            log.borrow_mut().push(format!("{n}: {total}ms"));
+        }
+        loop {
+            sleep(Duration::from_millis(5)).await;
+            total += 5;
+            if total >= 60 {
+                break;
+            }
+        }
+        total
+    }
+}
+
+desugar_and_compile! {
+    /// await 之后 panic
+    fn fail_after / fail_after_compiled(ms: u64) -> u64 {
+        let waited: u64 = ready(ms).await;
+        if waited > 0 {
+            panic!("{waited}ms 之后失败");
+        }
+        waited
+    }
+}
+
+// 下面几个函数列出了每个状态的所有字段：宏保存的变量和这里写的不一样时，编译不通过
+
+fn greet_twice_state(state: &GreetTwiceCoroutine) -> &'static str {
+    match state {
+        GreetTwiceCoroutine::Unresumed { name: _, log: _ } => "Unresumed { name, log }",
+        GreetTwiceCoroutine::Suspend0 {
+            name: _,
+            log: _,
+            greeting: _,
+            __awaitee: _,
+        } => "Suspend0 { name, log, greeting }",
+        GreetTwiceCoroutine::Suspend1 {
+            greeting: _,
+            farewell: _,
+            __awaitee: _,
+        } => "Suspend1 { greeting, farewell }",
+        GreetTwiceCoroutine::Returned => "Returned",
+        GreetTwiceCoroutine::Panicked => "Panicked",
+    }
+}
+
+fn wait_if_state(state: &WaitIfCoroutine) -> &'static str {
+    match state {
+        WaitIfCoroutine::Unresumed { ms: _, log: _ } => "Unresumed { ms, log }",
+        WaitIfCoroutine::Suspend0 {
+            log: _,
+            waited: _,
+            __awaitee: _,
+        } => "Suspend0 { log, waited }",
+        WaitIfCoroutine::Returned => "Returned",
+        WaitIfCoroutine::Panicked => "Panicked",
+    }
+}
+
+fn countdown_state(state: &CountdownCoroutine) -> &'static str {
+    match state {
+        CountdownCoroutine::Unresumed { from: _, log: _ } => "Unresumed { from, log }",
+        CountdownCoroutine::Suspend0 {
+            log: _,
+            n: _,
+            total: _,
+            __awaitee: _,
+        } => "Suspend0 { log, n, total }",
+        CountdownCoroutine::Suspend1 { total: _, __awaitee: _ } => "Suspend1 { total }",
+        CountdownCoroutine::Returned => "Returned",
+        CountdownCoroutine::Panicked => "Panicked",
+    }
+}
+
+/// 用同一个 waker 一起 poll 编译器生成的和展开的 future，每次 poll 两者的结果都必须相同
+///
+/// 返回两者的输出，以及每次 poll 之后展开的状态机停在哪个状态。
+fn trace<A: Future, B: Future + Unpin>(
+    executor: &SimpleExecutor,
+    compiled: A,
+    desugared: &mut B,
+    state: fn(&B) -> &'static str,
+) -> (A::Output, B::Output, Vec<&'static str>) {
+    executor.block_on(async {
+        let mut compiled = pin!(compiled);
+        let mut states = Vec::new();
+        let (x, y) = poll_fn(|cx| {
+            let result = (compiled.as_mut().poll(cx), Pin::new(&mut *desugared).poll(cx));
+            states.push(state(desugared));
+            match result {
+                (Poll::Ready(x), Poll::Ready(y)) => Poll::Ready((x, y)),
+                (Poll::Pending, Poll::Pending) => Poll::Pending,
+                _ => panic!("第 {} 次 poll 时两个 future 的结果不同", states.len()),
+            }
+        })
+        .await;
+        (x, y, states)
+    })
+}
+
+/// 测试 `#[desugar]` 生成的状态机
+///
+/// 同一个函数体分别用 `#[desugar]` 和编译器生成 future，在虚拟时钟上一起 poll：
+/// 每次 poll 的结果、日志、输出和用时都相同。同时检查 COMPILE.md 中的说法：
+/// 1. 每个 `.await` 对应一个 `Suspend` 状态，第一次 poll 执行到第一个 await
+/// 2. 每个状态只保存之后还会用到的局部变量（`greet_twice` 的 `Suspend1` 不再保存 `name` 和 `log`）
+/// 3. 只在一个分支里 await 时，走另一个分支不会经过任何 `Suspend` 状态
+/// 4. 循环里的 await 每一轮都回到同一个状态
+/// 5. 完成后再 poll，或者 panic 之后再 poll，panic 的消息和编译器生成的一样
+pub fn test_desugared() {
+    println!("\n=== #[desugar] 示例：过程宏把 async fn 展开成状态机 ===");
+    let executor = SimpleExecutor::new_paused();
+
+    // 1. 直线代码
+    let (compiled_log, desugared_log) = (Log::default(), Log::default());
+    let mut compiled = pin!(greet_twice_compiled("Ferris".to_string(), compiled_log.clone()));
+    let mut desugared = greet_twice("Ferris".to_string(), desugared_log.clone());
+    assert_eq!(greet_twice_state(&desugared), "Unresumed { name, log }");
+    let start = executor.now();
+    let (compiled_output, desugared_output, states) =
+        trace(&executor, compiled.as_mut(), &mut desugared, greet_twice_state);
+    println!(
+        "greet_twice: 输出 {} / {}，用时 {:?}，状态 {:?}",
+        compiled_output,
+        desugared_output,
+        executor.now() - start,
+        states
+    );
+    assert_eq!(compiled_output, desugared_output);
+    assert_eq!(compiled_log, desugared_log);
+    assert_eq!(
+        states,
+        ["Suspend0 { name, log, greeting }", "Suspend1 { greeting, farewell }", "Returned"]
+    );
+    assert_eq!(executor.now() - start, Duration::from_millis(300));
+    let message = poll_after_completion(Pin::new(&mut desugared));
+    assert_eq!(poll_after_completion(compiled.as_mut()), message);
+    println!("greet_twice: 完成后再 poll: {message}");
+
+    // 2. if
+    for (ms, expected) in [(20, &["Suspend0 { log, waited }", "Returned"][..]), (0, &["Returned"][..])] {
+        let (compiled_log, desugared_log) = (Log::default(), Log::default());
+        let mut desugared = wait_if(ms, desugared_log.clone());
+        let (compiled_output, desugared_output, states) =
+            trace(&executor, wait_if_compiled(ms, compiled_log.clone()), &mut desugared, wait_if_state);
+        println!(
+            "wait_if({ms}): 输出 {} / {}，状态 {:?}，日志 {:?}",
+            compiled_output,
+            desugared_output,
+            states,
+            desugared_log.borrow()
+        );
+        assert_eq!((compiled_output, desugared_output), (ms, ms));
+        assert_eq!(compiled_log, desugared_log);
+        assert_eq!(states, expected);
+    }
+
+    // 3. 循环
+    let (compiled_log, desugared_log) = (Log::default(), Log::default());
+    let mut desugared = countdown(4, desugared_log.clone());
+    let start = executor.now();
+    let (compiled_output, desugared_output, mut states) =
+        trace(&executor, countdown_compiled(4, compiled_log.clone()), &mut desugared, countdown_state);
+    println!(
+        "countdown: 输出 {} / {}，poll {} 次，用时 {:?}，日志 {:?}",
+        compiled_output,
+        desugared_output,
+        states.len(),
+        executor.now() - start,
+        desugared_log.borrow()
+    );
+    assert_eq!((compiled_output, desugared_output), (60, 60));
+    assert_eq!(compiled_log, desugared_log);
+    assert_eq!(*desugared_log.borrow(), ["3: 10ms", "跳过 2", "1: 20ms"]);
+    assert_eq!(executor.now() - start, Duration::from_millis(60));
+    // while 里 3 次 await，loop 里 6 次
+    assert_eq!(states.len(), 10);
+    states.dedup();
+    assert_eq!(states, ["Suspend0 { log, n, total }", "Suspend1 { total }", "Returned"]);
+
+    // 4. panic
+    let mut cx = Context::from_waker(Waker::noop());
+    let mut compiled = pin!(fail_after_compiled(10));
+    let mut desugared = fail_after(10);
+    let compiled_panic = panic_message(|| {
+        let _ = compiled.as_mut().poll(&mut cx);
+    });
+    let desugared_panic = panic_message(|| {
+        let _ = Pin::new(&mut desugared).poll(&mut cx);
+    });
+    assert!(compiled_panic.is_some());
+    assert_eq!(compiled_panic, desugared_panic);
+    assert!(matches!(desugared, FailAfterCoroutine::Panicked));
+    let message = poll_after_completion(Pin::new(&mut desugared));
+    assert_eq!(poll_after_completion(compiled.as_mut()), message);
+    println!("fail_after: panic 之后再 poll: {message}");
+
+    println!("\n关键点：");
+    println!("- 状态机的形状和 SimpleCoroutine 一样：Unresumed、每个 await 一个 Suspend、Returned、Panicked");
+    println!("- 每个 Suspend 状态只保存之后还会用到的局部变量和正在 await 的子 future");
+    println!("- if 和循环里的 await 也只是一个状态，恢复时从暂停点接着执行剩下的代码");
+}
@@ -0,0 +1,116 @@
+use std::future::Future;
+use std::pin::{Pin, pin};
+use std::task::{Context, Poll, Waker};
+use std::time::Duration;
+
+use super::combinator::{Either, select_biased};
+use super::pin_project::pin_project;
+use super::simple_coroutine::SimpleCoroutine;
+use super::simple_executor::SimpleExecutor;
+use super::stream::{StreamExt, interval};
+use super::timer::sleep;
+
+/// 可以查询是否已经结束的 future
+///
+/// `Future` 的约定是返回 `Ready` 之后不能再 poll，但没有规定再 poll 会发生什么：
+/// `SimpleCoroutine` 会 panic，`MyFuture`、`HelloFuture`、`Sleep` 会再返回一次结果。
+/// 实现了 `FusedFuture` 的 future 可以告诉调用方它是否已经结束，
+/// 在循环里反复 `select` 时就可以跳过已经完成的分支。
+pub trait FusedFuture: Future {
+    /// 返回 `true` 时不应该再 poll 这个 future
+    fn is_terminated(&self) -> bool;
+}
+
+/// 把 future 包装成 `Fuse`：完成之后再 poll 总是返回 `Pending`
+pub fn fuse<F: Future>(future: F) -> Fuse<F> {
+    Fuse {
+        future: Some(future),
+    }
+}
+
+pin_project! {
+    /// `fuse` 返回的 future
+    ///
+    /// 完成时原地 drop 里面的 future，之后的 poll 不再碰它，直接返回 `Pending`，
+    /// 所以不管里面的 future 完成后再 poll 会怎样（panic、再返回一次结果），都不会发生。
+    pub struct Fuse<F> {
+        // 完成后变成 None
+        #[pin]
+        future: Option<F>,
+    }
+}
+
+impl<F: Future> Future for Fuse<F> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<F::Output> {
+        let mut this = self.project();
+        let Some(future) = this.future.as_mut().as_pin_mut() else {
+            return Poll::Pending;
+        };
+        let output = std::task::ready!(future.poll(cx));
+        // 原地 drop，不会移动里面的 future
+        this.future.set(None);
+        Poll::Ready(output)
+    }
+}
+
+impl<F: Future> FusedFuture for Fuse<F> {
+    fn is_terminated(&self) -> bool {
+        self.future.is_none()
+    }
+}
+
+/// 测试 Fuse 和 FusedFuture
+///
+/// 1. `SimpleCoroutine` 完成后再 poll 会 panic；包装成 `Fuse` 后返回 `Pending`
+/// 2. 在循环里 `select`：关闭信号完成一次之后，`Fuse` 保证它不会再次被选中，
+///    循环继续处理其余的 tick
+pub fn test_fuse() {
+    println!("\n=== Fuse 示例：完成之后不再 poll 里面的 future ===");
+
+    // 1. 手动 poll
+    let mut cx = Context::from_waker(Waker::noop());
+    let mut coroutine = SimpleCoroutine::Unresumed;
+    assert!(!coroutine.is_terminated());
+    assert_eq!(Pin::new(&mut coroutine).poll(&mut cx), Poll::Ready(42));
+    assert!(coroutine.is_terminated());
+
+    let mut fused = fuse(SimpleCoroutine::Unresumed);
+    assert_eq!(Pin::new(&mut fused).poll(&mut cx), Poll::Ready(42));
+    assert!(fused.is_terminated());
+    // 直接 poll SimpleCoroutine 会 panic："cannot poll after completion"
+    assert_eq!(Pin::new(&mut fused).poll(&mut cx), Poll::Pending);
+    println!("Fuse<SimpleCoroutine>: 第一次 Ready(42)，之后 Pending，没有 panic");
+
+    // 2. 循环里的 select：25ms 时收到一次关闭信号，每 10ms 一个 tick，处理 5 个 tick 后退出
+    let executor = SimpleExecutor::new_paused();
+    let start = executor.now();
+    let (shutdowns, ticks) = executor.block_on(async {
+        // 裸的 Sleep 完成后再 poll 会再返回一次 Ready，关闭信号就会被处理无数次
+        let mut shutdown = pin!(fuse(sleep(Duration::from_millis(25))));
+        let mut ticker = interval(Duration::from_millis(10));
+        let (mut shutdowns, mut ticks) = (0, 0);
+        while ticks < 5 {
+            match select_biased(shutdown.as_mut(), ticker.next()).await {
+                Either::Left(()) => shutdowns += 1,
+                Either::Right(_) => ticks += 1,
+            }
+        }
+        assert!(shutdown.is_terminated());
+        (shutdowns, ticks)
+    });
+    println!(
+        "select 循环: 关闭信号 {} 次，tick {} 次，用时 {:?}",
+        shutdowns,
+        ticks,
+        executor.now() - start
+    );
+    assert_eq!((shutdowns, ticks), (1, 5));
+    assert_eq!(executor.now() - start, Duration::from_millis(50));
+
+    println!("\n关键点：");
+    println!("- Future 完成后再 poll 的行为没有约定：可能 panic，也可能再返回一次结果");
+    println!("- Fuse 完成后原地 drop 里面的 future，之后的 poll 返回 Pending");
+    println!("- FusedFuture::is_terminated 让调用方知道一个 future 是否还能 poll");
+}
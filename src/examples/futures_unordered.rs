@@ -0,0 +1,316 @@
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Wake, Waker};
+use std::time::Duration;
+
+use super::combinator::join_all;
+use super::simple_executor::SimpleExecutor;
+use super::stream::{Stream, StreamExt};
+use super::timer::{sleep, Sleep};
+
+/// 一组可以动态添加的 future，按完成的顺序产出结果
+///
+/// `join_all` 每次被唤醒都要把所有还没完成的子 future 重新 poll 一遍，
+/// 子 future 很多时，一次唤醒就是成千上万次没有意义的 poll。
+///
+/// 这里每个子 future 有自己的 waker：被唤醒时只把这个子 future 放进就绪队列，
+/// 再唤醒外层任务；`poll_next`（见 `Stream`）只 poll 就绪队列里的子 future。
+/// poll 的次数因此只和唤醒的次数有关，和子 future 的总数无关。
+pub struct FuturesUnordered<F> {
+    // 按下标存放，完成后空出的位置留给之后 push 的 future
+    children: Vec<Option<Child<F>>>,
+    free: Vec<usize>,
+    len: usize,
+    ready: Arc<ReadyList>,
+}
+
+struct Child<F> {
+    future: Pin<Box<F>>,
+    // 创建一次，之后每次 poll 都用同一个 waker
+    waker: Waker,
+    handle: Arc<ChildWaker>,
+}
+
+/// 就绪队列和外层任务的 waker，和 `SharedState` 一样由唤醒方和 poll 方共享
+struct ReadyList {
+    state: Mutex<ReadyState>,
+}
+
+struct ReadyState {
+    queue: VecDeque<Arc<ChildWaker>>,
+    waker: Option<Waker>,
+}
+
+/// 子 future 的 waker：唤醒时把自己放进就绪队列
+struct ChildWaker {
+    index: usize,
+    ready: Arc<ReadyList>,
+    // 已经在就绪队列中，重复唤醒不再入队
+    queued: AtomicBool,
+    // 子 future 已经完成或集合已经被 drop，之后的唤醒直接忽略
+    released: AtomicBool,
+}
+
+impl Wake for ChildWaker {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        if self.released.load(Ordering::Acquire) || self.queued.swap(true, Ordering::AcqRel) {
+            return;
+        }
+        let waker = {
+            let mut state = self.ready.state.lock().unwrap();
+            state.queue.push_back(self.clone());
+            state.waker.take()
+        };
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+impl<F> Default for FuturesUnordered<F> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<F> FuturesUnordered<F> {
+    pub fn new() -> Self {
+        Self {
+            children: Vec::new(),
+            free: Vec::new(),
+            len: 0,
+            ready: Arc::new(ReadyList {
+                state: Mutex::new(ReadyState {
+                    queue: VecDeque::new(),
+                    waker: None,
+                }),
+            }),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// 加入一个 future；它会在下一次 `poll_next` 时被第一次 poll
+    pub fn push(&mut self, future: F) {
+        let index = match self.free.pop() {
+            Some(index) => index,
+            None => {
+                self.children.push(None);
+                self.children.len() - 1
+            }
+        };
+        let handle = Arc::new(ChildWaker {
+            index,
+            ready: self.ready.clone(),
+            queued: AtomicBool::new(true),
+            released: AtomicBool::new(false),
+        });
+        self.ready.state.lock().unwrap().queue.push_back(handle.clone());
+        self.children[index] = Some(Child {
+            future: Box::pin(future),
+            waker: Waker::from(handle.clone()),
+            handle,
+        });
+        self.len += 1;
+    }
+}
+
+impl<F: Future> Stream for FuturesUnordered<F> {
+    type Item = F::Output;
+
+    /// 返回下一个完成的结果；所有子 future 都完成后返回 `Ready(None)`
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<F::Output>> {
+        // 子 future 都在 Box 里，FuturesUnordered 本身是 Unpin 的
+        let this = self.get_mut();
+        if this.len == 0 {
+            return Poll::Ready(None);
+        }
+
+        // 先保存外层 waker，再取就绪队列：之后到来的唤醒一定能看到这个 waker
+        let budget = {
+            let mut state = this.ready.state.lock().unwrap();
+            match &state.waker {
+                Some(waker) if waker.will_wake(cx.waker()) => {}
+                _ => state.waker = Some(cx.waker().clone()),
+            }
+            state.queue.len()
+        };
+
+        // 最多处理这次开始时已经就绪的数量：poll 时立刻唤醒自己的子 future 会排到下一轮，
+        // 不会让这次 poll_next 一直循环下去
+        for _ in 0..budget {
+            let Some(handle) = this.ready.state.lock().unwrap().queue.pop_front() else {
+                break;
+            };
+            handle.queued.store(false, Ordering::Release);
+            if handle.released.load(Ordering::Acquire) {
+                continue;
+            }
+            let index = handle.index;
+            let child = this.children[index].as_mut().expect("就绪的子 future 不存在");
+            if let Poll::Ready(output) = child.future.as_mut().poll(&mut Context::from_waker(&child.waker)) {
+                child.handle.released.store(true, Ordering::Release);
+                this.children[index] = None;
+                this.free.push(index);
+                this.len -= 1;
+                return Poll::Ready(Some(output));
+            }
+        }
+
+        if !this.ready.state.lock().unwrap().queue.is_empty() {
+            // 还有就绪的子 future 没有处理，让出后尽快再来
+            cx.waker().wake_by_ref();
+        }
+        Poll::Pending
+    }
+}
+
+impl<F> Drop for FuturesUnordered<F> {
+    fn drop(&mut self) {
+        for child in self.children.iter().flatten() {
+            child.handle.released.store(true, Ordering::Release);
+        }
+        // 就绪队列里的 ChildWaker 持有 ReadyList，清空以打破引用环
+        self.ready.state.lock().unwrap().queue.clear();
+    }
+}
+
+/// 统计 poll 和唤醒次数的 sleep
+///
+/// 每次 poll 都把外层的 waker 包一层，这样能数出定时器真正调用了多少次 wake。
+struct CountedSleep {
+    sleep: Pin<Box<Sleep>>,
+    stats: Arc<Stats>,
+}
+
+#[derive(Default)]
+struct Stats {
+    polls: AtomicUsize,
+    wakes: AtomicUsize,
+}
+
+struct CountingWake {
+    inner: Waker,
+    stats: Arc<Stats>,
+}
+
+impl Wake for CountingWake {
+    fn wake(self: Arc<Self>) {
+        self.stats.wakes.fetch_add(1, Ordering::Relaxed);
+        self.inner.wake_by_ref();
+    }
+}
+
+impl Future for CountedSleep {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        self.stats.polls.fetch_add(1, Ordering::Relaxed);
+        let waker = Waker::from(Arc::new(CountingWake {
+            inner: cx.waker().clone(),
+            stats: self.stats.clone(),
+        }));
+        self.sleep.as_mut().poll(&mut Context::from_waker(&waker))
+    }
+}
+
+fn counted_sleep(ms: u64, stats: &Arc<Stats>) -> CountedSleep {
+    CountedSleep {
+        sleep: Box::pin(sleep(Duration::from_millis(ms))),
+        stats: stats.clone(),
+    }
+}
+
+/// 等待 `ms` 毫秒后返回 `ms`
+async fn sleep_then(ms: u64) -> u64 {
+    sleep(Duration::from_millis(ms)).await;
+    ms
+}
+
+/// 测试 FuturesUnordered
+///
+/// 一万个定时器，到期时刻分布在 100 个不同的毫秒上（虚拟时钟）：
+/// - `FuturesUnordered`：每个子 future 第一次 poll 一次，被唤醒后再 poll 一次，
+///   poll 次数 = 子 future 数 + 唤醒次数
+/// - `join_all`：每次唤醒都要重新 poll 所有还没完成的子 future，poll 次数是前者的几十倍
+pub fn test_futures_unordered() {
+    println!("\n=== FuturesUnordered 示例：每个子 future 有自己的 waker ===");
+
+    const CHILDREN: usize = 10_000;
+    let executor = SimpleExecutor::new_paused();
+
+    let stats = Arc::new(Stats::default());
+    let start = executor.now();
+    let (completed, in_order) = executor.block_on(async {
+        let mut set = FuturesUnordered::new();
+        for i in 0..CHILDREN {
+            set.push(counted_sleep(1 + (i % 100) as u64, &stats));
+        }
+        assert_eq!(set.len(), CHILDREN);
+        let mut completed = 0;
+        let mut last = executor.now();
+        let mut in_order = true;
+        while set.next().await.is_some() {
+            // 按完成的顺序产出：到期时刻不会倒退
+            in_order &= executor.now() >= last;
+            last = executor.now();
+            completed += 1;
+        }
+        assert!(set.is_empty());
+        (completed, in_order)
+    });
+    let polls = stats.polls.load(Ordering::Relaxed);
+    let wakes = stats.wakes.load(Ordering::Relaxed);
+    println!(
+        "FuturesUnordered: {completed} 个子 future，唤醒 {wakes} 次，poll {polls} 次，用时 {:?}",
+        executor.now() - start
+    );
+    assert_eq!(completed, CHILDREN);
+    assert!(in_order);
+    assert_eq!(wakes, CHILDREN);
+    assert_eq!(polls, CHILDREN + wakes);
+
+    let naive = Arc::new(Stats::default());
+    executor.block_on(async {
+        join_all((0..CHILDREN).map(|i| counted_sleep(1 + (i % 100) as u64, &naive))).await;
+    });
+    let naive_polls = naive.polls.load(Ordering::Relaxed);
+    let naive_wakes = naive.wakes.load(Ordering::Relaxed);
+    println!("join_all: 唤醒 {naive_wakes} 次，poll {naive_polls} 次");
+    assert_eq!(naive_wakes, CHILDREN);
+    assert!(naive_polls > polls * 10);
+
+    // 完成的顺序和 push 的顺序无关；空出的位置可以复用
+    let order = executor.block_on(async {
+        let mut set = FuturesUnordered::new();
+        for ms in [30, 10, 20] {
+            set.push(sleep_then(ms));
+        }
+        let mut order = vec![set.next().await.unwrap()];
+        set.push(sleep_then(1));
+        while let Some(ms) = set.next().await {
+            order.push(ms);
+        }
+        order
+    });
+    assert_eq!(order, vec![10, 1, 20, 30]);
+    println!("完成顺序: {:?}", order);
+
+    println!("\n关键点：");
+    println!("- 每个子 future 的 waker 只把自己放进就绪队列，再唤醒外层任务");
+    println!("- poll_next 只 poll 就绪队列里的子 future，poll 次数和唤醒次数成正比");
+    println!("- join_all 每次唤醒都 poll 所有子 future，子 future 越多越浪费");
+}
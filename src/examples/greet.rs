@@ -1,7 +1,14 @@
 use tokio::time::sleep;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::time::Duration;
 use tokio::join;
 
+use super::combinator::{self, join_all};
+use super::custom_waker::AsyncTimerFuture;
+use super::simple_executor::SimpleExecutor;
+use super::sync::semaphore::Semaphore;
+
 /// 基本的 async 函数示例
 pub async fn greet() {
     println!("Hello!");
@@ -44,8 +51,74 @@ pub async fn test_sequential() {
 }
 
 /// 测试并发执行（即使使用 current_thread，spawn 创建的任务也会并发执行）
+///
+/// 然后用 `Semaphore` 限制并发数：4 个 greet 同时 spawn，但最多 2 个同时运行，
+/// 所以一共需要两轮（约 1 秒）。
 pub async fn test_concurrent() {
     let one = tokio::spawn(greet());
     let two = tokio::spawn(greet());
     let (_, _) = join!(one, two);
+
+    let semaphore = Arc::new(Semaphore::new(2));
+    let running = Arc::new(AtomicUsize::new(0));
+    let tasks: Vec<_> = (0..4)
+        .map(|_| {
+            let semaphore = semaphore.clone();
+            let running = running.clone();
+            tokio::spawn(async move {
+                let _permit = semaphore.acquire().await;
+                let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                println!("同时运行的 greet: {now}");
+                assert!(now <= 2);
+                greet().await;
+                running.fetch_sub(1, Ordering::SeqCst);
+            })
+        })
+        .collect();
+    for task in tasks {
+        task.await.unwrap();
+    }
+}
+
+/// 不依赖 tokio 的 greet：用 `AsyncTimerFuture` 代替 `tokio::time::sleep`
+pub async fn greet_without_tokio() {
+    println!("Hello!");
+    AsyncTimerFuture::new(Duration::from_millis(500)).await;
+    println!("Goodbye!");
+}
+
+/// 和 `test_concurrent` 一样的并发演示，但完全不使用 tokio
+///
+/// `SimpleExecutor` 代替 tokio 运行时，`join!` 代替 `tokio::join!`，
+/// `Semaphore` 加上 `join_all` 代替 `tokio::spawn` 限制并发数。
+/// 使用虚拟时钟，所以可以精确断言用时。
+pub fn test_concurrent_without_tokio() {
+    println!("\n=== 不使用 tokio 的并发 greet：SimpleExecutor + join! + Semaphore ===");
+    let executor = SimpleExecutor::new_paused();
+
+    // 两个 greet 并发执行：一共 500ms，而不是 1s
+    let start = executor.now();
+    executor.block_on(async {
+        combinator::join!(greet_without_tokio(), greet_without_tokio());
+    });
+    assert_eq!(executor.now() - start, Duration::from_millis(500));
+
+    // spawn 出来的任务也可以用 join! 等待
+    let start = executor.now();
+    let one = executor.spawn(greet_without_tokio());
+    let two = executor.spawn(greet_without_tokio());
+    let (one, two) = executor.block_on(async { combinator::join!(one, two) });
+    one.unwrap();
+    two.unwrap();
+    assert_eq!(executor.now() - start, Duration::from_millis(500));
+
+    // 4 个 greet，最多 2 个同时运行：两轮，一共 1s
+    let start = executor.now();
+    let semaphore = Semaphore::new(2);
+    executor.block_on(join_all((0..4).map(|_| async {
+        let _permit = semaphore.acquire().await;
+        greet_without_tokio().await;
+    })));
+    assert_eq!(executor.now() - start, Duration::from_millis(1000));
+    println!("不使用 tokio：并发的 greet 用时 500ms，限制为 2 个并发时 4 个 greet 用时 1s");
 }
@@ -0,0 +1,343 @@
+use std::cell::RefCell;
+use std::future::{Future, poll_fn};
+use std::marker::PhantomPinned;
+use std::mem;
+use std::pin::{Pin, pin};
+use std::ptr;
+use std::task::{Context, Poll, Waker};
+use std::time::Duration;
+
+use super::quiet_panic::panic_message;
+use super::simple_executor::SimpleExecutor;
+use super::timer::{Sleep, sleep};
+
+/// 有两个 `.await` 的 async fn，局部变量跨 await 存活
+///
+/// - `greeting` 在第一个 await 之前创建，两个 await 之后还要用
+/// - `farewell` 在两个 await 之间创建，第二个 await 之后还要用
+pub async fn greet(name: &str, log: &RefCell<Vec<String>>) -> usize {
+    let greeting = format!("Hello, {name}!");
+    log.borrow_mut().push(greeting.clone());
+    sleep(Duration::from_millis(100)).await;
+    let farewell = format!("Goodbye, {name}!");
+    log.borrow_mut().push(farewell.clone());
+    sleep(Duration::from_millis(200)).await;
+    greeting.len() + farewell.len()
+}
+
+/// `greet` 手动展开后的状态机
+///
+/// 和编译器生成的 coroutine 一样，每个状态只保存在这个暂停点还活着的变量：
+/// 参数在 `Unresumed` 中，每个 `.await` 对应一个 `Suspend` 状态，
+/// 里面是跨这个 await 存活的局部变量和正在 await 的子 future。
+///
+/// 没有任何字段借用其他字段，所以可以整个移动：poll 时用 `mem::replace` 取出当前状态，
+/// 执行到下一个暂停点，再写回新的状态。执行期间状态是 `Panicked`，
+/// 函数体 panic 时就停在这个状态（和 `SimpleCoroutine` 一样）。
+pub enum GreetCoroutine<'a> {
+    Unresumed {
+        name: &'a str,
+        log: &'a RefCell<Vec<String>>,
+    },
+    /// 停在第一个 `.await`
+    Suspend0 {
+        name: &'a str,
+        log: &'a RefCell<Vec<String>>,
+        greeting: String,
+        sleep: Sleep,
+    },
+    /// 停在第二个 `.await`；`name` 和 `log` 之后不再使用，不需要保存
+    Suspend1 {
+        greeting: String,
+        farewell: String,
+        sleep: Sleep,
+    },
+    Returned,
+    Panicked,
+}
+
+/// 相当于调用 `greet(name, log)`：只构造状态机，不执行函数体
+pub fn greet_expanded<'a>(name: &'a str, log: &'a RefCell<Vec<String>>) -> GreetCoroutine<'a> {
+    GreetCoroutine::Unresumed { name, log }
+}
+
+impl Future for GreetCoroutine<'_> {
+    type Output = usize;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<usize> {
+        // 所有字段都是 Unpin 的，状态机本身也是 Unpin 的
+        let this = self.get_mut();
+        loop {
+            match mem::replace(this, GreetCoroutine::Panicked) {
+                GreetCoroutine::Unresumed { name, log } => {
+                    let greeting = format!("Hello, {name}!");
+                    log.borrow_mut().push(greeting.clone());
+                    *this = GreetCoroutine::Suspend0 {
+                        name,
+                        log,
+                        greeting,
+                        sleep: sleep(Duration::from_millis(100)),
+                    };
+                }
+                GreetCoroutine::Suspend0 {
+                    name,
+                    log,
+                    greeting,
+                    sleep: mut timer,
+                } => {
+                    if Pin::new(&mut timer).poll(cx).is_pending() {
+                        *this = GreetCoroutine::Suspend0 {
+                            name,
+                            log,
+                            greeting,
+                            sleep: timer,
+                        };
+                        return Poll::Pending;
+                    }
+                    let farewell = format!("Goodbye, {name}!");
+                    log.borrow_mut().push(farewell.clone());
+                    *this = GreetCoroutine::Suspend1 {
+                        greeting,
+                        farewell,
+                        sleep: sleep(Duration::from_millis(200)),
+                    };
+                }
+                GreetCoroutine::Suspend1 {
+                    greeting,
+                    farewell,
+                    sleep: mut timer,
+                } => {
+                    if Pin::new(&mut timer).poll(cx).is_pending() {
+                        *this = GreetCoroutine::Suspend1 {
+                            greeting,
+                            farewell,
+                            sleep: timer,
+                        };
+                        return Poll::Pending;
+                    }
+                    *this = GreetCoroutine::Returned;
+                    return Poll::Ready(greeting.len() + farewell.len());
+                }
+                // 和编译器生成的代码 panic 的消息一样
+                GreetCoroutine::Returned => panic!("`async fn` resumed after completion"),
+                GreetCoroutine::Panicked => panic!("`async fn` resumed after panicking"),
+            }
+        }
+    }
+}
+
+/// 和 `greet` 一样，但跨 await 持有一个指向局部变量的引用
+///
+/// `borrowed` 指向 `greeting`，两者都要保存在 future 里，
+/// 于是 future 的一个字段指向它自己的另一个字段：这就是 async fn 的 future 是 `!Unpin` 的原因。
+pub async fn greet_borrowed(name: &str, log: &RefCell<Vec<String>>) -> usize {
+    let greeting = format!("Hello, {name}!");
+    let borrowed: &String = &greeting;
+    log.borrow_mut().push(borrowed.clone());
+    sleep(Duration::from_millis(100)).await;
+    let farewell = format!("Goodbye, {name}! ({} bytes earlier)", borrowed.len());
+    log.borrow_mut().push(farewell.clone());
+    sleep(Duration::from_millis(200)).await;
+    borrowed.len() + farewell.len()
+}
+
+/// `greet_borrowed` 的暂停点
+enum BorrowedState {
+    Unresumed,
+    Suspend0,
+    Suspend1,
+    Returned,
+    Panicked,
+}
+
+/// `greet_borrowed` 手动展开后的状态机：自引用
+///
+/// `borrowed` 指向本结构体的 `greeting` 字段，结构体被移动后它就悬空了，
+/// 所以不能像 `GreetCoroutine` 那样用 `mem::replace` 把状态取出来再写回去，
+/// 只能通过 `Pin` 原地修改各个字段；`PhantomPinned` 让它成为 `!Unpin`，
+/// 第一次 poll 之后就不能再被移动。
+pub struct GreetBorrowedCoroutine<'a> {
+    state: BorrowedState,
+    name: &'a str,
+    log: &'a RefCell<Vec<String>>,
+    // 跨 await 存活的局部变量，原地存放；还没执行到时是 None
+    greeting: Option<String>,
+    farewell: Option<String>,
+    // `let borrowed = &greeting;`
+    borrowed: *const String,
+    // 正在 await 的子 future
+    sleep: Option<Sleep>,
+    _pinned: PhantomPinned,
+}
+
+/// 相当于调用 `greet_borrowed(name, log)`
+pub fn greet_borrowed_expanded<'a>(
+    name: &'a str,
+    log: &'a RefCell<Vec<String>>,
+) -> GreetBorrowedCoroutine<'a> {
+    GreetBorrowedCoroutine {
+        state: BorrowedState::Unresumed,
+        name,
+        log,
+        greeting: None,
+        farewell: None,
+        borrowed: ptr::null(),
+        sleep: None,
+        _pinned: PhantomPinned,
+    }
+}
+
+impl GreetBorrowedCoroutine<'_> {
+    /// 等待 `sleep` 完成；完成后 drop 它，和 `.await` 结束时 drop 临时值一样
+    fn poll_sleep(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        let sleep = self.sleep.as_mut().expect("没有正在 await 的 sleep");
+        std::task::ready!(Pin::new(sleep).poll(cx));
+        self.sleep = None;
+        Poll::Ready(())
+    }
+
+    fn borrowed(&self) -> &String {
+        // borrowed 指向自己的 greeting 字段；self 被 pin 住，greeting 没有被移动过
+        debug_assert!(ptr::eq(self.borrowed, self.greeting.as_ref().unwrap()));
+        unsafe { &*self.borrowed }
+    }
+}
+
+impl Future for GreetBorrowedCoroutine<'_> {
+    type Output = usize;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<usize> {
+        // 安全性：下面只原地修改字段，从不移动整个结构体或 greeting
+        let this = unsafe { self.get_unchecked_mut() };
+        loop {
+            match mem::replace(&mut this.state, BorrowedState::Panicked) {
+                BorrowedState::Unresumed => {
+                    let greeting = this.greeting.insert(format!("Hello, {}!", this.name));
+                    this.borrowed = greeting;
+                    this.log.borrow_mut().push(this.borrowed().clone());
+                    this.sleep = Some(sleep(Duration::from_millis(100)));
+                    this.state = BorrowedState::Suspend0;
+                }
+                BorrowedState::Suspend0 => {
+                    if this.poll_sleep(cx).is_pending() {
+                        this.state = BorrowedState::Suspend0;
+                        return Poll::Pending;
+                    }
+                    let farewell = format!(
+                        "Goodbye, {}! ({} bytes earlier)",
+                        this.name,
+                        this.borrowed().len()
+                    );
+                    this.log.borrow_mut().push(farewell.clone());
+                    this.farewell = Some(farewell);
+                    this.sleep = Some(sleep(Duration::from_millis(200)));
+                    this.state = BorrowedState::Suspend1;
+                }
+                BorrowedState::Suspend1 => {
+                    if this.poll_sleep(cx).is_pending() {
+                        this.state = BorrowedState::Suspend1;
+                        return Poll::Pending;
+                    }
+                    let output = this.borrowed().len() + this.farewell.as_ref().unwrap().len();
+                    // 函数返回：局部变量按声明的相反顺序 drop，引用先于被借用的值失效
+                    this.farewell = None;
+                    this.borrowed = ptr::null();
+                    this.greeting = None;
+                    this.state = BorrowedState::Returned;
+                    return Poll::Ready(output);
+                }
+                BorrowedState::Returned => panic!("`async fn` resumed after completion"),
+                BorrowedState::Panicked => panic!("`async fn` resumed after panicking"),
+            }
+        }
+    }
+}
+
+/// 用同一个 waker 一起 poll 两个 future：每次 poll 两者的结果（Ready / Pending）都必须相同
+///
+/// 返回两者的输出和 poll 的次数。
+fn lockstep<A: Future, B: Future>(executor: &SimpleExecutor, a: A, b: B) -> (A::Output, B::Output, usize) {
+    executor.block_on(async {
+        let (mut a, mut b) = (pin!(a), pin!(b));
+        let mut polls = 0;
+        poll_fn(|cx| {
+            polls += 1;
+            match (a.as_mut().poll(cx), b.as_mut().poll(cx)) {
+                (Poll::Ready(x), Poll::Ready(y)) => Poll::Ready((x, y, polls)),
+                (Poll::Pending, Poll::Pending) => Poll::Pending,
+                _ => panic!("第 {polls} 次 poll 时两个 future 的结果不同"),
+            }
+        })
+        .await
+    })
+}
+
+/// 完成之后再 poll 一次，返回 panic 的消息
+pub(crate) fn poll_after_completion<F: Future>(future: Pin<&mut F>) -> String {
+    panic_message(|| {
+        let _ = future.poll(&mut Context::from_waker(Waker::noop()));
+    })
+    .expect("完成之后再 poll 应该 panic")
+}
+
+/// 测试手动展开的状态机和编译器生成的 future 行为一致
+///
+/// 对 `greet` 和 `greet_borrowed` 分别：
+/// 1. 在虚拟时钟上用同一个 waker 一起 poll 编译器生成的和手写的 future，
+///    每次 poll 的结果、写入日志的内容、输出和用时都相同
+/// 2. 完成之后再 poll，两者 panic 的消息相同
+pub fn test_greet_coroutine() {
+    println!("\n=== 手动展开的状态机：两个 await 和跨 await 的借用 ===");
+
+    let executor = SimpleExecutor::new_paused();
+
+    // greet：局部变量跨 await 存活
+    let (compiled_log, expanded_log) = (RefCell::new(Vec::new()), RefCell::new(Vec::new()));
+    let mut compiled = pin!(greet("Ferris", &compiled_log));
+    let mut expanded = pin!(greet_expanded("Ferris", &expanded_log));
+    let start = executor.now();
+    let (compiled_output, expanded_output, polls) = lockstep(&executor, compiled.as_mut(), expanded.as_mut());
+    println!(
+        "greet: 输出 {} / {}，poll {} 次，用时 {:?}，日志 {:?}",
+        compiled_output,
+        expanded_output,
+        polls,
+        executor.now() - start,
+        expanded_log.borrow()
+    );
+    assert_eq!(compiled_output, expanded_output);
+    assert_eq!(compiled_log, expanded_log);
+    // 第一次 poll 执行到第一个 await，两次到期各 poll 一次
+    assert_eq!(polls, 3);
+    assert_eq!(executor.now() - start, Duration::from_millis(300));
+    let message = poll_after_completion(expanded.as_mut());
+    assert_eq!(poll_after_completion(compiled.as_mut()), message);
+    println!("greet: 完成后再 poll: {message}");
+
+    // greet_borrowed：自引用
+    let (compiled_log, expanded_log) = (RefCell::new(Vec::new()), RefCell::new(Vec::new()));
+    let mut compiled = pin!(greet_borrowed("Ferris", &compiled_log));
+    let mut expanded = pin!(greet_borrowed_expanded("Ferris", &expanded_log));
+    let start = executor.now();
+    let (compiled_output, expanded_output, polls) = lockstep(&executor, compiled.as_mut(), expanded.as_mut());
+    println!(
+        "greet_borrowed: 输出 {} / {}，poll {} 次，用时 {:?}，日志 {:?}",
+        compiled_output,
+        expanded_output,
+        polls,
+        executor.now() - start,
+        expanded_log.borrow()
+    );
+    assert_eq!(compiled_output, expanded_output);
+    assert_eq!(compiled_log, expanded_log);
+    assert_eq!(polls, 3);
+    assert_eq!(executor.now() - start, Duration::from_millis(300));
+    let message = poll_after_completion(expanded.as_mut());
+    assert_eq!(poll_after_completion(compiled.as_mut()), message);
+    println!("greet_borrowed: 完成后再 poll: {message}");
+
+    println!("\n关键点：");
+    println!("- 每个 .await 是一个暂停点，对应状态机的一个状态");
+    println!("- 跨 await 存活的局部变量保存在对应的状态里，其余的只是 poll 中的临时变量");
+    println!("- 跨 await 的借用让 future 指向自己，必须 pin 住之后才能 poll");
+}
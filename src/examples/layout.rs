@@ -0,0 +1,228 @@
+use std::cell::RefCell;
+use std::future::Future;
+use std::marker::PhantomData;
+use std::mem::{self, MaybeUninit};
+use std::pin::Pin;
+use std::ptr;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use super::custom_waker::AsyncTimerFuture;
+use super::desugared::{GreetTwiceCoroutine, greet_twice_compiled};
+use super::greet::{greet, greet_without_tokio, hello};
+use super::greet_coroutine::{GreetBorrowedCoroutine, GreetCoroutine, greet_borrowed};
+use super::simple_coroutine::SimpleCoroutine;
+use super::simple_executor::SimpleExecutor;
+use super::timer::Sleep;
+
+/// 一个 future 类型的大小和对齐
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FutureLayout {
+    pub name: &'static str,
+    pub size: usize,
+    pub align: usize,
+}
+
+impl FutureLayout {
+    /// 按类型测量：手写的 future 有名字，可以直接用
+    pub fn of<F: Future>(name: &'static str) -> Self {
+        Self {
+            name,
+            size: mem::size_of::<F>(),
+            align: mem::align_of::<F>(),
+        }
+    }
+
+    /// 按值测量：async fn 返回的 future 类型没有名字，只能通过一个实例得到
+    ///
+    /// 调用 async fn 只构造状态机，不执行函数体，所以这里构造出来就直接丢掉。
+    pub fn of_val<F: Future>(name: &'static str, _future: &F) -> Self {
+        Self::of::<F>(name)
+    }
+}
+
+/// 代码库中各个 future 的大小和对齐
+///
+/// 编译器生成的 future 和对应的手写状态机放在一起，方便对比。
+pub fn report() -> Vec<FutureLayout> {
+    let log = RefCell::new(Vec::new());
+    vec![
+        FutureLayout::of_val("greet::hello()", &hello()),
+        FutureLayout::of::<SimpleCoroutine>("SimpleCoroutine"),
+        FutureLayout::of_val("greet::greet()", &greet()),
+        FutureLayout::of_val("greet::greet_without_tokio()", &greet_without_tokio()),
+        FutureLayout::of::<AsyncTimerFuture>("AsyncTimerFuture"),
+        FutureLayout::of::<Sleep>("timer::Sleep"),
+        FutureLayout::of_val("greet_coroutine::greet()", &super::greet_coroutine::greet("", &log)),
+        FutureLayout::of::<GreetCoroutine>("GreetCoroutine"),
+        FutureLayout::of_val("greet_coroutine::greet_borrowed()", &greet_borrowed("", &log)),
+        FutureLayout::of::<GreetBorrowedCoroutine>("GreetBorrowedCoroutine"),
+        FutureLayout::of_val(
+            "desugared::greet_twice_compiled()",
+            &greet_twice_compiled(String::new(), Default::default()),
+        ),
+        FutureLayout::of::<GreetTwiceCoroutine>("GreetTwiceCoroutine (#[desugar])"),
+    ]
+}
+
+/// 打印 `report()`：`cargo run -- layout`
+pub fn print_report() {
+    let layouts = report();
+    let width = layouts.iter().map(|layout| layout.name.len()).max().unwrap_or(0);
+    println!("{:<width$}  {:>6}  {:>5}", "future", "size", "align");
+    for layout in &layouts {
+        println!("{:<width$}  {:>6}  {:>5}", layout.name, layout.size, layout.align);
+    }
+}
+
+// 内联存放 future 的缓冲区，对齐到 16 字节
+#[repr(align(16))]
+struct Buffer<const N: usize>([u8; N]);
+
+/// 大于 `N` 字节的 future 装箱，否则原地存放
+///
+/// async fn 的 future 包含所有子 future，嵌套多层之后可能有几十 KB；
+/// 在栈上构造、移动、`pin!` 它们都会占用这么多栈空间。包装之后的 future
+/// 大小固定是 `N` 加一个指针（向上取整到 16 的倍数），和 `F` 有多大无关：
+/// `F` 放得下时存放在内部的缓冲区里，放不下（或者对齐超过 16）时装箱，只保存指针。
+pub fn boxed_if_larger_than<const N: usize, F: Future>(future: F) -> BoxedIfLarger<F, N> {
+    let mut wrapper = BoxedIfLarger {
+        inline: MaybeUninit::uninit(),
+        boxed: None,
+        _future: PhantomData,
+    };
+    if BoxedIfLarger::<F, N>::INLINE {
+        // 还没有被 pin，之后可以随 wrapper 一起移动
+        unsafe { wrapper.inline.as_mut_ptr().cast::<F>().write(future) };
+    } else {
+        wrapper.boxed = Some(Box::pin(future));
+    }
+    wrapper
+}
+
+/// `boxed_if_larger_than` 返回的 future
+pub struct BoxedIfLarger<F, const N: usize> {
+    // 结构化 pin：`boxed` 是 None 时里面是一个 F
+    inline: MaybeUninit<Buffer<N>>,
+    boxed: Option<Pin<Box<F>>>,
+    // 拥有一个 F：Send、Unpin 和 drop 检查都和 F 一致
+    _future: PhantomData<F>,
+}
+
+impl<F: Future, const N: usize> BoxedIfLarger<F, N> {
+    const INLINE: bool = mem::size_of::<F>() <= N && mem::align_of::<F>() <= mem::align_of::<Buffer<N>>();
+
+    /// future 是否被装箱了
+    pub fn is_boxed(&self) -> bool {
+        self.boxed.is_some()
+    }
+}
+
+impl<F: Future, const N: usize> Future for BoxedIfLarger<F, N> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<F::Output> {
+        let this = unsafe { self.get_unchecked_mut() };
+        match &mut this.boxed {
+            Some(boxed) => boxed.as_mut().poll(cx),
+            // 内联的 future 和 self 一起被 pin 住
+            None => unsafe { Pin::new_unchecked(&mut *this.inline.as_mut_ptr().cast::<F>()) }.poll(cx),
+        }
+    }
+}
+
+impl<F, const N: usize> Drop for BoxedIfLarger<F, N> {
+    fn drop(&mut self) {
+        if self.boxed.is_none() {
+            // 原地 drop，满足 pin 的约定
+            unsafe { ptr::drop_in_place(self.inline.as_mut_ptr().cast::<F>()) };
+        }
+    }
+}
+
+/// 持有一个值直到被 drop
+async fn hold(_value: Rc<()>) {}
+
+/// 测试 future 的大小
+///
+/// 1. 没有 await 的 async fn 只有一个状态标记（`Unresumed`、`Returned`、`Panicked`），1 字节
+/// 2. 有 await 的 async fn 至少要放下它 await 的子 future 和跨 await 存活的局部变量
+/// 3. `boxed_if_larger_than`：大的 future 被装箱，包装之后的大小固定
+pub fn test_layout() {
+    println!("\n=== future 的大小和对齐 ===");
+    print_report();
+    let layouts = report();
+    let find = |name: &str| *layouts.iter().find(|layout| layout.name == name).unwrap();
+
+    // 1. 只有状态标记
+    for name in ["greet::hello()", "SimpleCoroutine"] {
+        let layout = find(name);
+        assert_eq!((layout.size, layout.align), (1, 1), "{name}");
+    }
+
+    // 2. 子 future 和局部变量是状态机的字段
+    // 两个 Arc 和一个 TimerKey(u64)
+    let timer_size = 2 * mem::size_of::<Arc<()>>() + mem::size_of::<u64>();
+    assert_eq!(find("AsyncTimerFuture").size, timer_size);
+    assert_eq!(find("timer::Sleep").size, timer_size);
+    // 只有一个 await，没有跨 await 的局部变量：至少是子 future 加上一个按对齐补齐的状态标记
+    // （tokio::time::Sleep 的布局随 tokio 版本变化，不断言精确的大小）
+    let tokio_sleep = FutureLayout::of::<tokio::time::Sleep>("tokio::time::Sleep");
+    assert!(find("greet::greet()").size >= tokio_sleep.size + tokio_sleep.align);
+    assert_eq!(find("greet::greet_without_tokio()").size, timer_size + mem::align_of::<Sleep>());
+    // greet 的 Suspend0 保存 name、log、greeting 和 Sleep
+    let suspend0 = mem::size_of::<&str>() + mem::size_of::<&RefCell<Vec<String>>>() + mem::size_of::<String>() + timer_size;
+    assert_eq!(find("GreetCoroutine").size, suspend0 + mem::align_of::<String>());
+    assert!(find("greet_coroutine::greet()").size >= suspend0);
+    // 自引用的版本多了一个 farewell、一个指针，而且不能按状态复用空间
+    assert!(find("GreetBorrowedCoroutine").size > find("GreetCoroutine").size);
+    // #[desugar] 的 Suspend0 保存 name、log、greeting 和装箱的子 future
+    let desugared =
+        2 * mem::size_of::<String>() + mem::size_of::<Rc<()>>() + mem::size_of::<Pin<Box<dyn Future<Output = ()>>>>();
+    assert!(find("GreetTwiceCoroutine (#[desugar])").size >= desugared);
+
+    // 3. boxed_if_larger_than
+    let small = boxed_if_larger_than::<16, _>(hello());
+    let large = boxed_if_larger_than::<16, _>(greet_without_tokio());
+    assert!(!small.is_boxed());
+    assert!(large.is_boxed());
+    // 大小和里面的 future 无关
+    assert_eq!(mem::size_of_val(&small), mem::size_of_val(&large));
+    assert!(mem::size_of_val(&small) <= 16 + 16);
+    let huge = boxed_if_larger_than::<16, _>(async {
+        let buffer = [0u8; 16 * 1024];
+        greet_without_tokio().await;
+        buffer.len()
+    });
+    assert!(huge.is_boxed());
+    assert_eq!(mem::size_of_val(&huge), mem::size_of_val(&small));
+    println!(
+        "boxed_if_larger_than::<16>: hello() 内联，greet_without_tokio() 和 16KB 的 future 装箱，包装后都是 {} 字节",
+        mem::size_of_val(&small)
+    );
+
+    let executor = SimpleExecutor::new_paused();
+    let start = executor.now();
+    let (message, len) = executor.block_on(async {
+        large.await;
+        (small.await, huge.await)
+    });
+    assert_eq!((message, len), ("hello, tokio!", 16 * 1024));
+    assert_eq!(executor.now() - start, Duration::from_millis(1000));
+
+    // 没有完成就 drop：内联的和装箱的 future 都被 drop 一次
+    let shared = Rc::new(());
+    let inline = boxed_if_larger_than::<64, _>(hold(shared.clone()));
+    let boxed = boxed_if_larger_than::<0, _>(hold(shared.clone()));
+    assert!(!inline.is_boxed() && boxed.is_boxed());
+    assert_eq!(Rc::strong_count(&shared), 3);
+    drop((inline, boxed));
+    assert_eq!(Rc::strong_count(&shared), 1);
+
+    println!("\n关键点：");
+    println!("- async fn 的 future 是一个 enum：跨 await 存活的局部变量和正在 await 的子 future 是它的字段");
+    println!("- 外层 future 包含内层 future，嵌套越深越大；用 `cargo run -- layout` 查看");
+    println!("- boxed_if_larger_than 把大的 future 放到堆上，包装后的大小固定");
+}
@@ -0,0 +1,191 @@
+use std::collections::HashMap;
+use std::mem::ManuallyDrop;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+use std::time::{Duration, Instant};
+
+use super::custom_waker::AsyncTimerFuture;
+use super::pin_and_poll::HelloFuture;
+use super::simple_executor::SimpleExecutor;
+
+/// 一次 poll 期间的探针：记录 future 有没有用到 `cx.waker()`
+///
+/// 探针 waker 只在 `poll` 期间存在。future 克隆它时得到的是被包装的真正的 waker，
+/// 所以探针本身不会被保存下来，可以放在栈上。
+struct Probe<'a> {
+    inner: &'a Waker,
+    touched: AtomicBool,
+}
+
+unsafe fn clone_probe(ptr: *const ()) -> RawWaker {
+    let probe = unsafe { &*(ptr as *const Probe) };
+    probe.touched.store(true, Ordering::Relaxed);
+    // 把真正的 waker 的所有权交给调用方
+    let waker = ManuallyDrop::new(probe.inner.clone());
+    RawWaker::new(waker.data(), waker.vtable())
+}
+
+unsafe fn wake_by_ref_probe(ptr: *const ()) {
+    let probe = unsafe { &*(ptr as *const Probe) };
+    probe.touched.store(true, Ordering::Relaxed);
+    probe.inner.wake_by_ref();
+}
+
+// 探针 waker 只被 executor 自己持有，不会被 wake 消耗，drop 时也没有资源要释放
+const PROBE_VTABLE: RawWakerVTable =
+    RawWakerVTable::new(clone_probe, wake_by_ref_probe, wake_by_ref_probe, |_| {});
+
+/// 丢失唤醒：future 返回 `Pending` 时既没有保存也没有调用 waker，
+/// 之后超过 `timeout` 仍然没有被唤醒
+#[derive(Debug, Clone)]
+pub struct LostWakeup {
+    pub task: String,
+    pub type_name: &'static str,
+    pub pending_for: Duration,
+}
+
+/// 嫌疑：返回 `Pending` 时没有碰 waker，但还没到超时时间
+struct Suspect {
+    type_name: &'static str,
+    since: Instant,
+}
+
+/// 丢失唤醒检测器（`SimpleExecutor::with_lost_wakeup_detection`）
+///
+/// 手写 future 最常见的错误是返回 `Poll::Pending` 却不注册 waker（比如 `HelloFuture`），
+/// 在真正的 executor 上它会永远挂起。检测分两步：
+///
+/// 1. 每次 poll 都用探针包装 waker，poll 返回 `Pending` 而探针没有被 clone 或 wake，
+///    就把这个 future 记为嫌疑
+/// 2. 嫌疑在 `timeout` 之内被唤醒就撤销（它可能在之前的 poll 中已经保存了 waker）；
+///    超时仍然没有被唤醒，就报告它的类型名
+///
+/// 超时按真实时间计算，executor 空闲时最多等待到下一个嫌疑超时的时刻。
+pub(crate) struct LostWakeupDetector {
+    timeout: Duration,
+    // 主 future 使用编号 0，任务使用任务编号
+    suspects: Mutex<HashMap<usize, Suspect>>,
+    reports: Mutex<Vec<LostWakeup>>,
+}
+
+impl LostWakeupDetector {
+    pub(crate) fn new(timeout: Duration) -> Self {
+        Self {
+            timeout,
+            suspects: Mutex::new(HashMap::new()),
+            reports: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// 用探针包装 `waker` 执行一次 poll
+    pub(crate) fn poll<T>(
+        &self,
+        key: usize,
+        type_name: &'static str,
+        waker: &Waker,
+        poll: impl FnOnce(&mut Context<'_>) -> Poll<T>,
+    ) -> Poll<T> {
+        // 正在被 poll，之前的嫌疑作废
+        self.woken(key);
+
+        let probe = Probe {
+            inner: waker,
+            touched: AtomicBool::new(false),
+        };
+        // 探针 waker 在这个函数返回之前 drop，不会比 probe 活得更久
+        let probe_waker = unsafe {
+            Waker::from_raw(RawWaker::new(
+                &probe as *const Probe as *const (),
+                &PROBE_VTABLE,
+            ))
+        };
+        let result = poll(&mut Context::from_waker(&probe_waker));
+        drop(probe_waker);
+
+        if result.is_pending() && !probe.touched.load(Ordering::Relaxed) {
+            self.suspects.lock().unwrap().insert(
+                key,
+                Suspect {
+                    type_name,
+                    since: Instant::now(),
+                },
+            );
+        }
+        result
+    }
+
+    /// future 被唤醒，撤销嫌疑
+    pub(crate) fn woken(&self, key: usize) {
+        self.suspects.lock().unwrap().remove(&key);
+    }
+
+    /// 报告已经超时的嫌疑，返回距离下一个嫌疑超时还有多久
+    pub(crate) fn check(&self) -> Option<Duration> {
+        let now = Instant::now();
+        let mut suspects = self.suspects.lock().unwrap();
+        let mut next = None::<Duration>;
+        suspects.retain(|&key, suspect| {
+            let pending_for = now - suspect.since;
+            if pending_for < self.timeout {
+                let remaining = self.timeout - pending_for;
+                next = Some(next.map_or(remaining, |next| next.min(remaining)));
+                return true;
+            }
+            let task = if key == 0 {
+                "block_on".to_string()
+            } else {
+                format!("task-{key}")
+            };
+            println!(
+                "[lost-wakeup] {} ({}) 返回 Pending 时没有保存或调用 waker，{:?} 后仍未被唤醒",
+                suspect.type_name, task, pending_for
+            );
+            self.reports.lock().unwrap().push(LostWakeup {
+                task,
+                type_name: suspect.type_name,
+                pending_for,
+            });
+            false
+        });
+        next
+    }
+
+    pub(crate) fn reports(&self) -> Vec<LostWakeup> {
+        self.reports.lock().unwrap().clone()
+    }
+}
+
+/// 测试丢失唤醒检测：`HelloFuture` 返回 `Pending` 时不注册 waker
+///
+/// 1. 被 spawn 的 `HelloFuture` 第一次 poll 返回 `Pending`，之后再也不会被唤醒
+/// 2. 同时运行的 `AsyncTimerFuture` 正确地保存了 waker，不会被报告
+/// 3. 超过 100ms 后，检测器报告 `HelloFuture` 的类型名
+pub fn test_lost_wakeup() {
+    println!("\n=== 丢失唤醒检测示例：找出不注册 waker 的 future ===");
+
+    let executor = SimpleExecutor::new().with_lost_wakeup_detection(Duration::from_millis(100));
+    let _hello = executor.spawn(HelloFuture::new());
+    let timer = executor.spawn(AsyncTimerFuture::new(Duration::from_millis(50)));
+
+    executor.block_on(async {
+        timer.await.unwrap();
+        AsyncTimerFuture::new(Duration::from_millis(300)).await;
+    });
+
+    let reports = executor.lost_wakeups();
+    for report in &reports {
+        println!(
+            "报告: {} ({})，等待了 {:?}",
+            report.type_name, report.task, report.pending_for
+        );
+    }
+    assert_eq!(reports.len(), 1);
+    assert!(reports[0].type_name.ends_with("HelloFuture"));
+    assert!(reports[0].pending_for >= Duration::from_millis(100));
+
+    println!("\n关键点：");
+    println!("- 返回 Pending 的 future 必须保证之后有人调用 waker，否则永远不会被再次 poll");
+    println!("- 检测器用探针 waker 观察 poll 期间有没有 clone 或 wake");
+    println!("- 没碰 waker 且超时未被唤醒的 future 被报告，报告中包含它的类型名");
+}
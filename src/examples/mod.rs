@@ -1,6 +1,30 @@
 pub mod basic_future;
+pub mod channel;
+pub mod clock;
+pub mod combinator;
 pub mod custom_waker;
+pub mod desugared;
+pub mod fuse;
+pub mod futures_unordered;
 pub mod greet;
+pub mod greet_coroutine;
+pub mod layout;
+pub mod lost_wakeup;
+pub mod net;
 pub mod pin_and_poll;
+pub mod pin_project;
+#[cfg(debug_assertions)]
+pub mod poll_after_ready;
+pub(crate) mod quiet_panic;
+pub mod reactor;
+pub mod retry;
 pub mod simple_coroutine;
 pub mod simple_executor;
+pub mod stream;
+pub mod sync;
+pub mod thread_pool_executor;
+pub mod timeout;
+pub mod timer;
+#[cfg(debug_assertions)]
+pub mod tracked_waker;
+pub mod waker;
@@ -0,0 +1,401 @@
+use std::future::Future;
+use std::io::{self, Read, Write};
+use std::mem;
+use std::net::{self, SocketAddr};
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll, ready};
+
+use super::reactor::{Interest, Reactor, cvt};
+use super::simple_executor::SimpleExecutor;
+
+/// 异步读：和 `std::io::Read` 对应，数据没准备好时返回 `Pending` 而不是阻塞
+pub trait AsyncRead {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>>;
+}
+
+/// 异步写：和 `std::io::Write` 对应，缓冲区满时返回 `Pending` 而不是阻塞
+pub trait AsyncWrite {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>>;
+}
+
+/// 为 `AsyncRead` 提供可以 `.await` 的方法
+pub trait AsyncReadExt: AsyncRead {
+    /// 读取一次，返回读到的字节数；返回 0 表示对端关闭了连接
+    fn read<'a>(&'a mut self, buf: &'a mut [u8]) -> ReadFuture<'a, Self>
+    where
+        Self: Unpin,
+    {
+        ReadFuture { reader: self, buf }
+    }
+}
+
+impl<R: AsyncRead + ?Sized> AsyncReadExt for R {}
+
+/// 为 `AsyncWrite` 提供可以 `.await` 的方法
+pub trait AsyncWriteExt: AsyncWrite {
+    /// 写入全部数据
+    fn write_all<'a>(&'a mut self, buf: &'a [u8]) -> WriteAllFuture<'a, Self>
+    where
+        Self: Unpin,
+    {
+        WriteAllFuture { writer: self, buf }
+    }
+}
+
+impl<W: AsyncWrite + ?Sized> AsyncWriteExt for W {}
+
+/// `AsyncReadExt::read` 返回的 future
+pub struct ReadFuture<'a, R: ?Sized> {
+    reader: &'a mut R,
+    buf: &'a mut [u8],
+}
+
+impl<R: AsyncRead + Unpin + ?Sized> Future for ReadFuture<'_, R> {
+    type Output = io::Result<usize>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        Pin::new(&mut *this.reader).poll_read(cx, this.buf)
+    }
+}
+
+/// `AsyncWriteExt::write_all` 返回的 future
+pub struct WriteAllFuture<'a, W: ?Sized> {
+    writer: &'a mut W,
+    buf: &'a [u8],
+}
+
+impl<W: AsyncWrite + Unpin + ?Sized> Future for WriteAllFuture<'_, W> {
+    type Output = io::Result<()>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        while !this.buf.is_empty() {
+            let written = ready!(Pin::new(&mut *this.writer).poll_write(cx, this.buf))?;
+            if written == 0 {
+                return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+            }
+            this.buf = &this.buf[written..];
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// fd 在 reactor 中的登记，drop 时注销
+///
+/// `poll_io` 是所有 I/O 操作的公共模式：
+/// 1. 等待 fd 就绪（没就绪时 reactor 保存 waker，返回 `Pending`）
+/// 2. 执行非阻塞的系统调用
+/// 3. 返回 `WouldBlock` 说明就绪标志过期了，清除后回到第 1 步
+struct Registration {
+    reactor: Arc<Reactor>,
+    fd: RawFd,
+}
+
+impl Registration {
+    fn new(fd: RawFd) -> io::Result<Self> {
+        let reactor = Reactor::current();
+        reactor.register(fd)?;
+        Ok(Self { reactor, fd })
+    }
+
+    fn poll_io<T>(
+        &self,
+        cx: &mut Context<'_>,
+        interest: Interest,
+        mut op: impl FnMut() -> io::Result<T>,
+    ) -> Poll<io::Result<T>> {
+        loop {
+            ready!(self.reactor.poll_ready(self.fd, interest, cx))?;
+            match op() {
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => {
+                    self.reactor.clear_ready(self.fd, interest);
+                }
+                result => return Poll::Ready(result),
+            }
+        }
+    }
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        self.reactor.deregister(self.fd);
+    }
+}
+
+/// 运行在 `SimpleExecutor` reactor 上的 TCP 监听器
+///
+/// 必须在 executor 的上下文中创建（`block_on` 内部，或 `enter()` 的范围内）。
+/// reactor 对每个方向只保存一个 waker，所以同一时刻只应有一个任务在 `accept`。
+pub struct TcpListener {
+    // registration 先于 inner drop：先从 epoll 注销，再关闭 fd
+    registration: Registration,
+    inner: net::TcpListener,
+}
+
+impl TcpListener {
+    pub fn bind(addr: SocketAddr) -> io::Result<Self> {
+        let inner = net::TcpListener::bind(addr)?;
+        inner.set_nonblocking(true)?;
+        let registration = Registration::new(inner.as_raw_fd())?;
+        Ok(Self {
+            registration,
+            inner,
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.inner.local_addr()
+    }
+
+    /// 没有新连接时保存 waker，等待监听 socket 可读
+    pub fn poll_accept(&self, cx: &mut Context<'_>) -> Poll<io::Result<(TcpStream, SocketAddr)>> {
+        let (stream, addr) = ready!(
+            self.registration
+                .poll_io(cx, Interest::Read, || { self.inner.accept() })
+        )?;
+        Poll::Ready(TcpStream::from_std(stream).map(|stream| (stream, addr)))
+    }
+
+    /// 接受一个新连接
+    pub fn accept(&self) -> Accept<'_> {
+        Accept { listener: self }
+    }
+}
+
+/// `TcpListener::accept` 返回的 future
+pub struct Accept<'a> {
+    listener: &'a TcpListener,
+}
+
+impl Future for Accept<'_> {
+    type Output = io::Result<(TcpStream, SocketAddr)>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.listener.poll_accept(cx)
+    }
+}
+
+/// 运行在 `SimpleExecutor` reactor 上的 TCP 连接
+pub struct TcpStream {
+    registration: Registration,
+    inner: net::TcpStream,
+}
+
+impl TcpStream {
+    fn from_std(inner: net::TcpStream) -> io::Result<Self> {
+        inner.set_nonblocking(true)?;
+        let registration = Registration::new(inner.as_raw_fd())?;
+        Ok(Self {
+            registration,
+            inner,
+        })
+    }
+
+    /// 非阻塞地建立连接
+    ///
+    /// `connect` 返回 `EINPROGRESS` 后等待 socket 可写，再通过 `SO_ERROR` 检查连接结果。
+    pub async fn connect(addr: SocketAddr) -> io::Result<TcpStream> {
+        let (raw_addr, len) = socket_addr_to_raw(&addr);
+        let domain = match addr {
+            SocketAddr::V4(_) => libc::AF_INET,
+            SocketAddr::V6(_) => libc::AF_INET6,
+        };
+        let fd = cvt(unsafe {
+            libc::socket(
+                domain,
+                libc::SOCK_STREAM | libc::SOCK_NONBLOCK | libc::SOCK_CLOEXEC,
+                0,
+            )
+        })?;
+        let socket = unsafe { OwnedFd::from_raw_fd(fd) };
+        let result =
+            cvt(unsafe { libc::connect(fd, &raw_addr as *const _ as *const libc::sockaddr, len) });
+        if let Err(err) = result
+            && err.raw_os_error() != Some(libc::EINPROGRESS)
+        {
+            return Err(err);
+        }
+
+        let stream = TcpStream::from_std(net::TcpStream::from(socket))?;
+        std::future::poll_fn(|cx| {
+            stream.registration.poll_io(cx, Interest::Write, || {
+                match stream.inner.take_error()? {
+                    Some(err) => Err(err),
+                    // 可写但 getpeername 失败说明连接还没建立好
+                    None => stream.inner.peer_addr().map(drop).map_err(|err| {
+                        if err.kind() == io::ErrorKind::NotConnected {
+                            io::ErrorKind::WouldBlock.into()
+                        } else {
+                            err
+                        }
+                    }),
+                }
+            })
+        })
+        .await?;
+        Ok(stream)
+    }
+
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.inner.peer_addr()
+    }
+}
+
+impl AsyncRead for TcpStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        this.registration
+            .poll_io(cx, Interest::Read, || (&this.inner).read(buf))
+    }
+}
+
+impl AsyncWrite for TcpStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        this.registration
+            .poll_io(cx, Interest::Write, || (&this.inner).write(buf))
+    }
+}
+
+/// 把 `SocketAddr` 转换成 `connect` 需要的 C 结构体
+fn socket_addr_to_raw(addr: &SocketAddr) -> (libc::sockaddr_storage, libc::socklen_t) {
+    let mut storage: libc::sockaddr_storage = unsafe { mem::zeroed() };
+    let len = match addr {
+        SocketAddr::V4(addr) => {
+            let raw = libc::sockaddr_in {
+                sin_family: libc::AF_INET as libc::sa_family_t,
+                sin_port: addr.port().to_be(),
+                sin_addr: libc::in_addr {
+                    s_addr: u32::from_ne_bytes(addr.ip().octets()),
+                },
+                sin_zero: [0; 8],
+            };
+            unsafe { (&mut storage as *mut _ as *mut libc::sockaddr_in).write(raw) };
+            mem::size_of::<libc::sockaddr_in>()
+        }
+        SocketAddr::V6(addr) => {
+            let raw = libc::sockaddr_in6 {
+                sin6_family: libc::AF_INET6 as libc::sa_family_t,
+                sin6_port: addr.port().to_be(),
+                sin6_flowinfo: addr.flowinfo(),
+                sin6_addr: libc::in6_addr {
+                    s6_addr: addr.ip().octets(),
+                },
+                sin6_scope_id: addr.scope_id(),
+            };
+            unsafe { (&mut storage as *mut _ as *mut libc::sockaddr_in6).write(raw) };
+            mem::size_of::<libc::sockaddr_in6>()
+        }
+    };
+    (storage, len as libc::socklen_t)
+}
+
+/// 回显一个连接上收到的所有数据，直到对端关闭
+async fn echo(mut stream: TcpStream) -> io::Result<usize> {
+    let mut buf = [0u8; 1024];
+    let mut total = 0;
+    loop {
+        let len = stream.read(&mut buf).await?;
+        if len == 0 {
+            return Ok(total);
+        }
+        stream.write_all(&buf[..len]).await?;
+        total += len;
+    }
+}
+
+/// 测试 TcpListener/TcpStream：完全运行在 SimpleExecutor 上的 echo 服务器
+///
+/// 1. 服务器在 127.0.0.1 的随机端口上监听，每个连接 spawn 一个 echo 任务
+/// 2. 三个客户端并发连接，发送消息并读取回显
+/// 3. 全程没有 tokio：等待 I/O 时 executor 阻塞在 epoll_wait 上
+pub fn test_echo_server() {
+    println!("\n=== TcpListener/TcpStream 示例：SimpleExecutor 上的 echo 服务器 ===");
+
+    const CLIENTS: usize = 3;
+    let executor = SimpleExecutor::new();
+
+    let listener = {
+        let _guard = executor.enter();
+        TcpListener::bind("127.0.0.1:0".parse().unwrap()).unwrap()
+    };
+    let addr = listener.local_addr().unwrap();
+    println!("服务器监听: {}", addr);
+
+    let clients: Vec<_> = (0..CLIENTS)
+        .map(|id| {
+            executor.spawn(async move {
+                let mut stream = TcpStream::connect(addr).await.unwrap();
+                assert_eq!(stream.peer_addr().unwrap(), addr);
+                let message = format!("hello from client {id}");
+                stream.write_all(message.as_bytes()).await.unwrap();
+
+                let mut buf = vec![0u8; message.len()];
+                let mut read = 0;
+                while read < buf.len() {
+                    let len = stream.read(&mut buf[read..]).await.unwrap();
+                    assert!(len > 0, "服务器提前关闭了连接");
+                    read += len;
+                }
+                String::from_utf8(buf).unwrap()
+            })
+        })
+        .collect();
+
+    // 主 future 负责 accept，每个连接 spawn 一个 echo 任务
+    // 同一个 fd 的同一方向只保存一个 waker，所以 accept 只在一个任务里进行
+    let (replies, echoed) = executor.block_on(async {
+        let mut servers = Vec::new();
+        for _ in 0..CLIENTS {
+            let (stream, peer) = listener.accept().await.unwrap();
+            println!("[server] 接受连接: {}", peer);
+            servers.push(executor.spawn(async move { echo(stream).await.unwrap() }));
+        }
+
+        let mut replies = Vec::new();
+        for client in clients {
+            replies.push(client.await.unwrap());
+        }
+        // 客户端的 TcpStream 已经 drop，服务器的 echo 任务读到 EOF 后结束
+        let mut echoed = Vec::new();
+        for server in servers {
+            echoed.push(server.await.unwrap());
+        }
+        (replies, echoed)
+    });
+
+    for reply in &replies {
+        println!("[client] 收到回显: {}", reply);
+    }
+    for (id, reply) in replies.iter().enumerate() {
+        assert_eq!(reply, &format!("hello from client {id}"));
+    }
+    assert_eq!(
+        echoed.iter().sum::<usize>(),
+        replies.iter().map(String::len).sum()
+    );
+
+    println!("\n关键点：");
+    println!("- accept/read/write 返回 WouldBlock 时，reactor 保存 waker 并注册 epoll 事件");
+    println!("- fd 就绪后 waker 把任务放回就绪队列，executor 重新 poll");
+    println!("- 整个 echo 服务器和客户端都运行在 SimpleExecutor 上，没有用到 tokio");
+}
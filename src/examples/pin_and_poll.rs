@@ -2,13 +2,18 @@ use std::future::Future;
 use std::pin::Pin;
 use std::task::{Context, Poll, Waker};
 
-/// HelloFuture：使用 get_mut() 修改字段
-/// 
-/// 这个例子展示了在 poll 方法内部使用 get_mut() 的方式
-/// 适用于 Future 实现了 Unpin 的情况
-pub struct HelloFuture {
-    // 可修改的字段：用于演示 get_mut() 如何修改字段
-    count: u32,
+use super::pin_project::pin_project;
+
+pin_project! {
+    /// HelloFuture：使用 project() 修改字段
+    ///
+    /// 这个例子展示了在 poll 方法内部使用 project() 的方式
+    /// HelloFuture 没有 #[pin] 字段，所以是 Unpin 的，也可以直接用 get_mut()；
+    /// project() 对 !Unpin 的 Future 同样适用（见 pin_project 示例中的 PollCounter）
+    pub struct HelloFuture {
+        // 可修改的字段：没有 #[pin]，投影成 &mut u32
+        count: u32,
+    }
 }
 
 impl HelloFuture {
@@ -22,20 +27,22 @@ impl HelloFuture {
 impl Future for HelloFuture {
     type Output = &'static str;
 
-    /// 使用 get_mut() 修改字段
+    /// 使用 project() 修改字段
     /// 
-    /// 适用场景：Future 实现了 Unpin
-    /// 优点：代码最简洁，直接获取 &mut Self，可以修改字段
+    /// 适用场景：任何 Future，不要求 Unpin
+    /// 优点：不需要 unsafe，#[pin] 字段得到 Pin<&mut T>，其余字段得到 &mut T
     fn poll(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Self::Output> {
-        // 使用 get_mut() 获取 &mut Self
-        // 因为 HelloFuture 实现了 Unpin，可以直接使用 get_mut()
-        let this = self.get_mut();
+        // 使用 project() 获取各个字段
+        // 不需要 HelloFuture 实现 Unpin
+        let this = self.project();
         
-        // 修改字段：演示 get_mut() 可以修改字段
-        this.count += 1;
+        // 修改字段：count 没有被 pin，可以直接修改
+        *this.count += 1;
         
         // 当 count 达到 2 时返回 Ready
-        if this.count >= 2 {
+        // 注意：返回 Pending 时没有保存或调用 waker，只有像下面这样手动循环 poll 才能完成；
+        // 交给真正的 executor 会永远挂起（见 lost_wakeup 示例）
+        if *this.count >= 2 {
             Poll::Ready("Hello")
         } else {
             Poll::Pending
@@ -49,10 +56,10 @@ pub fn test_pin_and_poll_unpin() {
     // 这是一个不执行任何操作的 waker，非常适合测试和演示
     // 不需要手动创建 RawWaker 和 RawWakerVTable，也不需要 unsafe
     let waker = Waker::noop();
-    let mut cx = Context::from_waker(&waker);
+    let mut cx = Context::from_waker(waker);
     
-    // 测试 HelloFuture：使用 get_mut()
-    println!("\n=== 测试 HelloFuture：使用 get_mut() ===");
+    // 测试 HelloFuture：使用 project()
+    println!("\n=== 测试 HelloFuture：使用 project() ===");
     let mut future = HelloFuture::new();
     
     // 注意：poll 方法的签名是 fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>)
@@ -92,4 +99,5 @@ pub fn test_pin_and_poll_unpin() {
     println!("\n=== 总结 ===");
     println!("在 poll 方法内部访问和修改 self 的方式：");
     println!("get_mut() - 最简单，要求 Future 实现 Unpin");
+    println!("project() - pin_project! 生成，#[pin] 字段得到 Pin<&mut T>，不要求 Unpin");
 }
@@ -0,0 +1,265 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use super::combinator::{Either, select_biased};
+use super::simple_executor::SimpleExecutor;
+use super::timer::sleep;
+
+/// 结构化 pin 的投影：为结构体生成安全的 `project` 方法
+///
+/// ```ignore
+/// pin_project! {
+///     pub struct Timeout<F> {
+///         #[pin]
+///         future: F,
+///         deadline: Instant,
+///     }
+/// }
+///
+/// fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<...> {
+///     let this = self.project();
+///     this.future.poll(cx);  // Pin<&mut F>
+///     *this.deadline;        // &mut Instant
+/// }
+/// ```
+///
+/// 标记了 `#[pin]` 的字段投影成 `Pin<&mut T>`，其余字段投影成 `&mut T`。
+/// 为了让投影是安全的，宏同时生成：
+///
+/// - `Unpin`：只有 `#[pin]` 字段都是 `Unpin` 时结构体才是 `Unpin`
+/// - 禁止实现 `Drop`（`Drop::drop` 拿到 `&mut Self`，可以把被 pin 的字段移走）；
+///   需要在 drop 时做事情，在宏里写 `impl PinnedDrop`，它拿到的是 `Pin<&mut Self>`：
+///
+/// ```ignore
+/// pin_project! {
+///     pub struct Counted<F> { #[pin] inner: F, polls: usize }
+///     impl<F> PinnedDrop for Counted<F> {
+///         fn drop(this: Pin<&mut Self>) {
+///             println!("poll 了 {} 次", this.project().polls);
+///         }
+///     }
+/// }
+/// ```
+///
+/// 只支持带命名字段的结构体，泛型参数最多一个 bound（`F: Future`），不支持生命周期参数；
+/// where 子句的每一项也最多一个 bound（`where S::Item: Future`）。字段上除了 `#[pin]` 不能有其他属性。
+macro_rules! pin_project {
+    // 字段投影后的类型
+    (@projected [pin] $lt:lifetime $ty:ty) => { ::std::pin::Pin<&$lt mut $ty> };
+    (@projected [] $lt:lifetime $ty:ty) => { &$lt mut $ty };
+    // 投影一个字段（在 unsafe 块中展开）
+    (@project [pin] $field:ident) => { ::std::pin::Pin::new_unchecked($field) };
+    (@project [] $field:ident) => { $field };
+    // 决定结构体是否 Unpin 的字段：只有 #[pin] 的字段算数
+    (@origin [pin] $ty:ty) => { $ty };
+    (@origin [] $ty:ty) => { () };
+    // 没有 PinnedDrop：结构体实现了 Drop 时，两个 impl 冲突，编译不通过
+    (@drop $name:ident [$($generics:tt)*] [$($args:tt)*] [$($where:tt)*]) => {
+        #[allow(dead_code)]
+        trait MustNotImplDrop {}
+        #[allow(drop_bounds)]
+        impl<T: ::std::ops::Drop> MustNotImplDrop for T {}
+        impl<$($generics)*> MustNotImplDrop for $name<$($args)*> where $($where)* {}
+    };
+    (@drop $name:ident [$($generics:tt)*] [$($args:tt)*] [$($where:tt)*] $this:ident $body:block) => {
+        impl<$($generics)*> $name<$($args)*> where $($where)* {
+            fn __pinned_drop($this: ::std::pin::Pin<&mut Self>) $body
+        }
+
+        impl<$($generics)*> ::std::ops::Drop for $name<$($args)*> where $($where)* {
+            fn drop(&mut self) {
+                // 安全性：drop 之后 self 不会再被使用，当作被 pin 住的处理没有问题
+                Self::__pinned_drop(unsafe { ::std::pin::Pin::new_unchecked(self) });
+            }
+        }
+    };
+    (
+        $(#[$attr:meta])*
+        $vis:vis struct $name:ident $(<$($param:ident $(: $bound:path)?),* $(,)?>)?
+        $(where $($where_ty:ty: $where_bound:path),* $(,)?)?
+        {
+            $( $(#[$pin:ident])? $field_vis:vis $field:ident: $ty:ty ),* $(,)?
+        }
+        $(
+            impl $(<$($drop_param:ident),*>)? PinnedDrop for $drop_self:ty {
+                fn drop($this:ident: Pin<&mut Self>) $drop_body:block
+            }
+        )?
+    ) => {
+        $(#[$attr])*
+        $vis struct $name $(<$($param $(: $bound)?),*>)?
+        $(where $($where_ty: $where_bound),*)?
+        {
+            $( $field_vis $field: $ty ),*
+        }
+
+        const _: () = {
+            #[allow(dead_code)]
+            pub(crate) struct Projection<'__pin, $($($param $(: $bound)?),*)?>
+            $(where $($where_ty: $where_bound),*)?
+            {
+                $(
+                    $field_vis $field:
+                        $crate::examples::pin_project::pin_project!(@projected [$($pin)?] '__pin $ty)
+                ),*
+            }
+
+            impl<$($($param $(: $bound)?),*)?> $name<$($($param),*)?>
+            $(where $($where_ty: $where_bound),*)?
+            {
+                /// 把 `Pin<&mut Self>` 投影成各个字段：`#[pin]` 字段是 `Pin<&mut T>`，其余是 `&mut T`
+                pub(crate) fn project<'__pin>(
+                    self: ::std::pin::Pin<&'__pin mut Self>,
+                ) -> Projection<'__pin, $($($param),*)?> {
+                    // 安全性：#[pin] 字段只以 Pin 的形式交出去，不会被移动；
+                    // 结构体不能实现 Drop，也只在 #[pin] 字段都是 Unpin 时才是 Unpin
+                    unsafe {
+                        let Self { $($field),* } = self.get_unchecked_mut();
+                        Projection {
+                            $( $field: $crate::examples::pin_project::pin_project!(@project [$($pin)?] $field) ),*
+                        }
+                    }
+                }
+            }
+
+            #[allow(dead_code)]
+            struct Origin<'__pin, $($($param $(: $bound)?),*)?>
+            $(where $($where_ty: $where_bound),*)?
+            {
+                __pin: ::std::marker::PhantomData<(&'__pin (), fn() -> ($($($param,)*)?))>,
+                $( $field: $crate::examples::pin_project::pin_project!(@origin [$($pin)?] $ty) ),*
+            }
+
+            impl<'__pin, $($($param $(: $bound)?),*)?> ::std::marker::Unpin for $name<$($($param),*)?>
+            where
+                Origin<'__pin, $($($param),*)?>: ::std::marker::Unpin,
+                $($($where_ty: $where_bound),*)?
+            {
+            }
+
+            $crate::examples::pin_project::pin_project!(
+                @drop $name
+                [$($($param $(: $bound)?),*)?]
+                [$($($param),*)?]
+                [$($($where_ty: $where_bound),*)?]
+                $($this $drop_body)?
+            );
+        };
+    };
+}
+pub(crate) use pin_project;
+
+pin_project! {
+    /// 统计 poll 次数的 future：包装另一个 future，drop 时把统计写进 `report`
+    ///
+    /// `inner` 是结构化 pin 的：里面是 `!Unpin` 的 future（比如 async fn 返回的）时，
+    /// `PollCounter` 也是 `!Unpin`，只能 pin 住之后 poll；`polls` 等字段不需要 pin，可以直接修改。
+    pub struct PollCounter<F> {
+        #[pin]
+        inner: F,
+        polls: usize,
+        completed: bool,
+        report: Arc<Mutex<Vec<PollCount>>>,
+    }
+
+    impl<F> PinnedDrop for PollCounter<F> {
+        fn drop(this: Pin<&mut Self>) {
+            let this = this.project();
+            this.report.lock().unwrap().push(PollCount {
+                polls: *this.polls,
+                completed: *this.completed,
+            });
+        }
+    }
+}
+
+/// `PollCounter` drop 时写下的统计
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PollCount {
+    pub polls: usize,
+    /// 被 drop 之前是否已经完成（没有完成就是被取消了）
+    pub completed: bool,
+}
+
+/// 统计 `inner` 被 poll 的次数
+pub fn count_polls<F: Future>(inner: F, report: Arc<Mutex<Vec<PollCount>>>) -> PollCounter<F> {
+    PollCounter {
+        inner,
+        polls: 0,
+        completed: false,
+        report,
+    }
+}
+
+impl<F: Future> Future for PollCounter<F> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<F::Output> {
+        let this = self.project();
+        *this.polls += 1;
+        let output = std::task::ready!(this.inner.poll(cx));
+        *this.completed = true;
+        Poll::Ready(output)
+    }
+}
+
+async fn two_sleeps() -> u32 {
+    sleep(Duration::from_millis(10)).await;
+    sleep(Duration::from_millis(20)).await;
+    7
+}
+
+fn assert_unpin<T: Unpin>(_: &T) {}
+
+/// 测试 pin_project
+///
+/// 1. 包装 `!Unpin` 的 async fn：完成后 drop 时 `PinnedDrop` 记录 poll 了 3 次
+/// 2. 在 `select` 中输掉的分支被取消：drop 时记录没有完成
+/// 3. 里面的 future 是 `Unpin` 的时，包装之后也是 `Unpin`
+pub fn test_pin_project() {
+    println!("\n=== pin_project 示例：安全的结构化 pin 投影和 PinnedDrop ===");
+    let executor = SimpleExecutor::new_paused();
+    let report = Arc::new(Mutex::new(Vec::new()));
+
+    // 1. async fn 的 future 是 !Unpin 的，PollCounter 通过 project 拿到 Pin<&mut F>
+    let output = executor.block_on(count_polls(two_sleeps(), report.clone()));
+    assert_eq!(output, 7);
+
+    // 2. 被取消
+    let start = executor.now();
+    let winner = executor.block_on(async {
+        let slow = count_polls(sleep(Duration::from_millis(100)), report.clone());
+        // 3. Sleep 是 Unpin 的，所以 PollCounter<Sleep> 也是；
+        //    count_polls(two_sleeps(), ..) 传给 assert_unpin 则编译不通过
+        assert_unpin(&slow);
+        select_biased(slow, sleep(Duration::from_millis(10))).await
+    });
+    assert_eq!(winner, Either::Right(()));
+    assert_eq!(executor.now() - start, Duration::from_millis(10));
+
+    let report = report.lock().unwrap().clone();
+    println!("PinnedDrop 记录: {:?}", report);
+    assert_eq!(
+        report,
+        [
+            // 第一次 poll 执行到第一个 sleep，两个 sleep 到期各 poll 一次
+            PollCount {
+                polls: 3,
+                completed: true
+            },
+            // 开始时 poll 一次，另一个分支到期时又 poll 一次，之后被 drop
+            PollCount {
+                polls: 2,
+                completed: false
+            },
+        ]
+    );
+
+    println!("\n关键点：");
+    println!("- #[pin] 字段投影成 Pin<&mut T>，其余字段投影成 &mut T，不需要 unsafe");
+    println!("- 只有 #[pin] 字段都是 Unpin 时，结构体才是 Unpin");
+    println!("- 不能实现 Drop（它拿到 &mut Self）；用 PinnedDrop，拿到的是 Pin<&mut Self>");
+}
@@ -0,0 +1,211 @@
+use std::cell::RefCell;
+use std::future::{Future, poll_fn};
+use std::panic::Location;
+use std::pin::{Pin, pin};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use super::combinator::{Either, select_biased};
+use super::pin_project::pin_project;
+use super::quiet_panic::panic_message;
+use super::simple_coroutine::SimpleCoroutine;
+use super::simple_executor::SimpleExecutor;
+use super::stream::{StreamExt, interval};
+use super::timer::sleep;
+
+thread_local! {
+    // 当前线程上正在运行的 executor 的检测器，由 `SimpleExecutor::enter` 设置
+    static CURRENT: RefCell<Option<Arc<PollAfterReadyDetector>>> = const { RefCell::new(None) };
+}
+
+static NEXT_ID: AtomicUsize = AtomicUsize::new(1);
+
+/// 返回 `Ready` 之后又被 poll 的 future
+#[derive(Debug, Clone)]
+pub struct PollAfterReady {
+    pub type_name: &'static str,
+    /// 调用 `checked` 的位置
+    pub location: &'static Location<'static>,
+    /// 返回 `Ready` 之后又被 poll 的次数
+    pub polls: usize,
+}
+
+/// 完成后再 poll 的检测器（`SimpleExecutor::with_poll_after_ready_detection`，只在 debug 构建中）
+///
+/// 检测是按 future 选择加入的：executor 自己的任务完成后就被丢弃，不会再被 poll；
+/// 但它看不到任务内部的 future 是怎么被 poll 的，所以需要被检查的 future 用 `checked` 包装，
+/// 没有包装的 future 不会被检查。包装后的 future 完成后再被 poll 时，报告给当前 executor 的检测器。
+/// 每个 future 只有一条报告，记录它被多 poll 了几次。
+pub(crate) struct PollAfterReadyDetector {
+    // (future 的编号, 报告)，按第一次发现的顺序
+    reports: Mutex<Vec<(usize, PollAfterReady)>>,
+}
+
+impl PollAfterReadyDetector {
+    pub(crate) fn new() -> Self {
+        Self {
+            reports: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// 设置为当前线程的检测器，guard 被 drop 时恢复之前的
+    pub(crate) fn enter(self: &Arc<Self>) -> EnterGuard {
+        EnterGuard {
+            previous: CURRENT.replace(Some(self.clone())),
+        }
+    }
+
+    fn record(&self, id: usize, type_name: &'static str, location: &'static Location<'static>) {
+        let mut reports = self.reports.lock().unwrap();
+        if let Some((_, report)) = reports.iter_mut().find(|(key, _)| *key == id) {
+            report.polls += 1;
+            return;
+        }
+        println!("[poll-after-ready] {} ({}) 返回 Ready 之后又被 poll", type_name, location);
+        reports.push((
+            id,
+            PollAfterReady {
+                type_name,
+                location,
+                polls: 1,
+            },
+        ));
+    }
+
+    pub(crate) fn reports(&self) -> Vec<PollAfterReady> {
+        self.reports
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(_, report)| report.clone())
+            .collect()
+    }
+}
+
+/// 离开 `SimpleExecutor::enter` 时恢复之前的检测器
+pub(crate) struct EnterGuard {
+    previous: Option<Arc<PollAfterReadyDetector>>,
+}
+
+impl Drop for EnterGuard {
+    fn drop(&mut self) {
+        CURRENT.set(self.previous.take());
+    }
+}
+
+/// 包装 future，检查它是否在返回 `Ready` 之后又被 poll
+///
+/// 记录调用 `checked` 的位置和 future 的类型名。完成之后再被 poll 时，
+/// 报告给当前 executor 的检测器（没有开启检测时不记录）。只报告，不改变行为：
+/// 这次 poll 照常交给里面的 future，它该 panic 还是 panic，该返回什么还是返回什么。
+#[track_caller]
+pub fn checked<F: Future>(future: F) -> Checked<F> {
+    Checked {
+        future,
+        done: false,
+        id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+        type_name: std::any::type_name::<F>(),
+        location: Location::caller(),
+    }
+}
+
+pin_project! {
+    /// `checked` 返回的 future
+    pub struct Checked<F> {
+        #[pin]
+        future: F,
+        // 里面的 future 已经返回过 Ready
+        done: bool,
+        id: usize,
+        type_name: &'static str,
+        location: &'static Location<'static>,
+    }
+}
+
+impl<F: Future> Future for Checked<F> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<F::Output> {
+        let this = self.project();
+        if *this.done {
+            CURRENT.with_borrow(|detector| {
+                if let Some(detector) = detector {
+                    detector.record(*this.id, this.type_name, this.location);
+                }
+            });
+        }
+        let poll = this.future.poll(cx);
+        if poll.is_ready() {
+            *this.done = true;
+        }
+        poll
+    }
+}
+
+/// 测试完成后再 poll 的检测
+///
+/// 1. 循环里 `select` 一个没有 fuse 的关闭信号：它完成之后每一轮都会被再 poll 一次
+/// 2. 手动 poll 两次 `SimpleCoroutine`：第二次照常交给它，它 panic 了
+/// 3. 正常 `.await` 的 future 不会被报告
+///
+/// 报告包含类型名和调用 `checked` 的位置；检测不改变被检查的 future 的行为。
+pub fn test_poll_after_ready() {
+    println!("\n=== 完成后再 poll 检测示例：找出返回 Ready 之后又被 poll 的 future ===");
+
+    let executor = SimpleExecutor::new_paused().with_poll_after_ready_detection();
+    let (ticks, shutdowns, message) = executor.block_on(async {
+        // 1. 忘了 fuse 的关闭信号：Sleep 完成后再 poll 仍然返回 Ready，
+        //    select_biased 每一轮都选中它，ticker 再也轮不到
+        let mut shutdown = pin!(checked(sleep(Duration::from_millis(25))));
+        let mut ticker = interval(Duration::from_millis(10));
+        let (mut ticks, mut shutdowns) = (0, 0);
+        while ticks < 5 && shutdowns < 3 {
+            match select_biased(shutdown.as_mut(), ticker.next()).await {
+                Either::Left(()) => shutdowns += 1,
+                Either::Right(_) => ticks += 1,
+            }
+        }
+
+        // 2. 手动 poll 两次：第二次照常交给 SimpleCoroutine
+        let mut coroutine = pin!(checked(SimpleCoroutine::Unresumed));
+        let first = poll_fn(|cx| Poll::Ready(coroutine.as_mut().poll(cx))).await;
+        assert_eq!(first, Poll::Ready(42));
+        let message = poll_fn(|cx| {
+            Poll::Ready(panic_message(|| {
+                let _ = coroutine.as_mut().poll(cx);
+            }))
+        })
+        .await;
+
+        // 3. 正常使用
+        checked(sleep(Duration::from_millis(5))).await;
+        (ticks, shutdowns, message)
+    });
+    println!("关闭信号之前 tick {ticks} 次，之后关闭信号连续胜出 {shutdowns} 次");
+    assert_eq!((ticks, shutdowns), (2, 3));
+    assert_eq!(message.as_deref(), Some("cannot poll after completion"));
+
+    let reports = executor.polled_after_ready();
+    for report in &reports {
+        println!(
+            "报告: {} ({})，多 poll 了 {} 次",
+            report.type_name, report.location, report.polls
+        );
+    }
+    assert_eq!(reports.len(), 2);
+    assert!(reports[0].type_name.ends_with("timer::Sleep"));
+    assert_eq!(reports[0].polls, 2);
+    assert!(reports[1].type_name.ends_with("SimpleCoroutine"));
+    assert_eq!(reports[1].polls, 1);
+    for report in &reports {
+        assert!(report.location.file().ends_with("poll_after_ready.rs"));
+    }
+
+    println!("\n关键点：");
+    println!("- executor 只负责不再 poll 完成的任务，任务内部的 future 由调用方负责");
+    println!("- checked 记录类型名和调用位置，完成后再被 poll 时报告给当前 executor，但不改变行为");
+    println!("- 检测按 future 选择加入，而且只在 debug 构建中存在");
+    println!("- 修复方法：用 fuse 包装，或者在完成后不再 poll 它");
+}
@@ -0,0 +1,53 @@
+use std::cell::Cell;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Once;
+
+thread_local! {
+    // 当前线程上还活着的 QuietPanics 数量
+    static QUIET: Cell<usize> = const { Cell::new(0) };
+}
+
+static INSTALL: Once = Once::new();
+
+/// `quiet_panics` 返回的 guard，drop 时恢复打印 panic 信息
+pub(crate) struct QuietPanics {
+    _private: (),
+}
+
+/// guard 存活期间，当前线程的 panic 不打印默认的 panic 信息
+///
+/// 示例里故意触发 panic 时使用。不在 drop 时 `set_hook`（正在 panic 的线程调用它会再次 panic），
+/// 而是第一次调用时装上一个 hook，其他时候交给原来的 hook：断言失败或者意外的 panic
+/// 展开时 guard 照常被 drop，之后的示例仍然能看到 panic 信息；其他线程也不受影响。
+pub(crate) fn quiet_panics() -> QuietPanics {
+    INSTALL.call_once(|| {
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            if QUIET.get() == 0 {
+                previous(info);
+            }
+        }));
+    });
+    QUIET.set(QUIET.get() + 1);
+    QuietPanics { _private: () }
+}
+
+impl Drop for QuietPanics {
+    fn drop(&mut self) {
+        QUIET.set(QUIET.get() - 1);
+    }
+}
+
+/// 运行 `f`，返回它 panic 时的消息；不打印默认的 panic 信息
+pub(crate) fn panic_message(f: impl FnOnce()) -> Option<String> {
+    let _quiet = quiet_panics();
+    panic::catch_unwind(AssertUnwindSafe(f))
+        .err()
+        .map(|payload| match payload.downcast::<String>() {
+            Ok(message) => *message,
+            Err(payload) => payload
+                .downcast_ref::<&str>()
+                .map(|s| s.to_string())
+                .unwrap_or_default(),
+        })
+}
@@ -0,0 +1,359 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::future::poll_fn;
+use std::io;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::time::Duration;
+
+use super::custom_waker::AsyncTimerFuture;
+use super::simple_executor::SimpleExecutor;
+
+// eventfd 在 epoll 中的标识，普通 fd 用 fd 本身作为标识
+const WAKEUP_TOKEN: u64 = u64::MAX;
+
+thread_local! {
+    // 当前线程正在使用的 reactor，由 executor 的 enter() 设置
+    static CURRENT: RefCell<Option<Arc<Reactor>>> = const { RefCell::new(None) };
+}
+
+/// 关心的事件：可读或可写
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Interest {
+    Read,
+    Write,
+}
+
+/// 一个 fd 的就绪状态和等待它的 waker
+///
+/// 和 `AsyncTimerFuture` 的 `SharedState` 是同一个模式：
+/// 事件到来时设置就绪标志并调用保存的 waker，future 在 poll 时注入 waker。
+struct ScheduledIo {
+    readable: bool,
+    writable: bool,
+    read_waker: Option<Waker>,
+    write_waker: Option<Waker>,
+}
+
+impl ScheduledIo {
+    /// 还有 waker 在等待的事件，用于重新注册 epoll
+    fn interests(&self) -> u32 {
+        let mut events = 0;
+        if self.read_waker.is_some() {
+            events |= libc::EPOLLIN;
+        }
+        if self.write_waker.is_some() {
+            events |= libc::EPOLLOUT;
+        }
+        events as u32
+    }
+}
+
+/// 基于 epoll 的 I/O reactor（仅 Linux）
+///
+/// # 工作方式
+///
+/// 1. fd 注册到 epoll 时不关心任何事件
+/// 2. future 发现 fd 没有就绪（`WouldBlock`）时，保存 waker，
+///    并用 `EPOLLONESHOT` 重新注册它关心的事件
+/// 3. executor 没有任务可以执行时，在 `epoll_wait` 上等待（`park`）
+/// 4. 事件到来后，设置就绪标志并调用对应的 waker
+///
+/// 使用水平触发加 `EPOLLONESHOT`：每次只通知一次，需要时再重新注册。
+/// 重新注册时如果 fd 已经就绪，epoll 会立刻再通知一次，所以不会丢事件。
+///
+/// # 跨线程唤醒
+///
+/// 其他线程（比如定时器驱动线程）调用 waker 时，executor 可能正阻塞在 `epoll_wait` 上。
+/// 为此注册了一个 eventfd：`unpark` 向它写入，`epoll_wait` 就会返回。
+pub(crate) struct Reactor {
+    epoll: OwnedFd,
+    wakeup: OwnedFd,
+    sources: Mutex<HashMap<RawFd, ScheduledIo>>,
+}
+
+/// 把 libc 的返回值转换成 `io::Result`
+pub(crate) fn cvt(result: libc::c_int) -> io::Result<libc::c_int> {
+    if result < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(result)
+    }
+}
+
+impl Reactor {
+    pub(crate) fn new() -> io::Result<Self> {
+        let epoll = unsafe { OwnedFd::from_raw_fd(cvt(libc::epoll_create1(libc::EPOLL_CLOEXEC))?) };
+        let wakeup = unsafe {
+            OwnedFd::from_raw_fd(cvt(libc::eventfd(0, libc::EFD_NONBLOCK | libc::EFD_CLOEXEC))?)
+        };
+        let reactor = Self {
+            epoll,
+            wakeup,
+            sources: Mutex::new(HashMap::new()),
+        };
+        reactor.ctl(
+            libc::EPOLL_CTL_ADD,
+            reactor.wakeup.as_raw_fd(),
+            libc::EPOLLIN as u32,
+            WAKEUP_TOKEN,
+        )?;
+        Ok(reactor)
+    }
+
+    /// 当前线程的 reactor，必须在 `SimpleExecutor` 的上下文中调用
+    pub(crate) fn current() -> Arc<Reactor> {
+        CURRENT
+            .with_borrow(|current| current.clone())
+            .expect("I/O 对象必须在 SimpleExecutor 的上下文中创建")
+    }
+
+    /// 让当前线程使用这个 reactor，直到返回的 guard 被 drop
+    pub(crate) fn enter(self: &Arc<Self>) -> EnterGuard {
+        let previous = CURRENT.replace(Some(self.clone()));
+        EnterGuard { previous }
+    }
+
+    fn ctl(&self, op: libc::c_int, fd: RawFd, events: u32, token: u64) -> io::Result<()> {
+        let mut event = libc::epoll_event { events, u64: token };
+        cvt(unsafe { libc::epoll_ctl(self.epoll.as_raw_fd(), op, fd, &mut event) })?;
+        Ok(())
+    }
+
+    /// 注册 fd，此时不关心任何事件
+    ///
+    /// 新注册的 fd 先假定可读可写：直接尝试读写，返回 `WouldBlock` 再等待事件。
+    pub(crate) fn register(&self, fd: RawFd) -> io::Result<()> {
+        self.ctl(libc::EPOLL_CTL_ADD, fd, 0, fd as u64)?;
+        let io = ScheduledIo {
+            readable: true,
+            writable: true,
+            read_waker: None,
+            write_waker: None,
+        };
+        self.sources.lock().unwrap().insert(fd, io);
+        Ok(())
+    }
+
+    /// 注销 fd，丢弃保存的 waker；必须在关闭 fd 之前调用
+    pub(crate) fn deregister(&self, fd: RawFd) {
+        self.sources.lock().unwrap().remove(&fd);
+        let _ = self.ctl(libc::EPOLL_CTL_DEL, fd, 0, 0);
+    }
+
+    /// fd 是否就绪；没有就绪时保存 waker，并向 epoll 注册关心的事件
+    pub(crate) fn poll_ready(
+        &self,
+        fd: RawFd,
+        interest: Interest,
+        cx: &mut Context<'_>,
+    ) -> Poll<io::Result<()>> {
+        let mut sources = self.sources.lock().unwrap();
+        let io = sources.get_mut(&fd).expect("fd 没有注册到 reactor");
+        let (ready, waker) = match interest {
+            Interest::Read => (io.readable, &mut io.read_waker),
+            Interest::Write => (io.writable, &mut io.write_waker),
+        };
+        if ready {
+            return Poll::Ready(Ok(()));
+        }
+        *waker = Some(cx.waker().clone());
+        let events = io.interests() | libc::EPOLLONESHOT as u32;
+        match self.ctl(libc::EPOLL_CTL_MOD, fd, events, fd as u64) {
+            Ok(()) => Poll::Pending,
+            Err(err) => Poll::Ready(Err(err)),
+        }
+    }
+
+    /// 读写返回 `WouldBlock` 后调用：清除就绪标志，下次 poll_ready 会重新等待事件
+    pub(crate) fn clear_ready(&self, fd: RawFd, interest: Interest) {
+        if let Some(io) = self.sources.lock().unwrap().get_mut(&fd) {
+            match interest {
+                Interest::Read => io.readable = false,
+                Interest::Write => io.writable = false,
+            }
+        }
+    }
+
+    /// 从其他线程叫醒阻塞在 `park` 中的 executor
+    pub(crate) fn unpark(&self) {
+        let one: u64 = 1;
+        let buf = &one as *const u64 as *const libc::c_void;
+        // eventfd 的计数器溢出（EAGAIN）说明已经有未处理的唤醒，可以忽略
+        unsafe { libc::write(self.wakeup.as_raw_fd(), buf, 8) };
+    }
+
+    /// 在 `epoll_wait` 上等待 I/O 事件或 `unpark`，然后唤醒就绪的 future
+    pub(crate) fn park(&self, timeout: Option<Duration>) -> io::Result<()> {
+        let timeout_ms = match timeout {
+            // 向上取整，否则不足 1ms 的等待会变成 0，退化成忙等
+            Some(timeout) => timeout
+                .as_nanos()
+                .div_ceil(1_000_000)
+                .min(libc::c_int::MAX as u128) as libc::c_int,
+            None => -1,
+        };
+        let mut events = [libc::epoll_event { events: 0, u64: 0 }; 64];
+        let count = loop {
+            let result = unsafe {
+                libc::epoll_wait(
+                    self.epoll.as_raw_fd(),
+                    events.as_mut_ptr(),
+                    events.len() as libc::c_int,
+                    timeout_ms,
+                )
+            };
+            match cvt(result) {
+                Ok(count) => break count as usize,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err),
+            }
+        };
+
+        let mut wakers = Vec::new();
+        // 重新注册失败时不能提前返回：已经取出的 waker 对应的就绪标志已经设置，
+        // 丢掉它们的话这些任务再也不会被唤醒。先记下第一个错误，唤醒之后再返回
+        let mut result = Ok(());
+        {
+            let mut sources = self.sources.lock().unwrap();
+            for event in &events[..count] {
+                let (token, flags) = (event.u64, event.events as libc::c_int);
+                if token == WAKEUP_TOKEN {
+                    // 读出计数器，清除 eventfd 的可读状态
+                    let mut buf: u64 = 0;
+                    let ptr = &mut buf as *mut u64 as *mut libc::c_void;
+                    unsafe { libc::read(self.wakeup.as_raw_fd(), ptr, 8) };
+                    continue;
+                }
+                let fd = token as RawFd;
+                let Some(io) = sources.get_mut(&fd) else {
+                    continue;
+                };
+                // 出错或挂断时，读写两边都要唤醒，让它们在读写时拿到错误
+                let closed = flags & (libc::EPOLLERR | libc::EPOLLHUP) != 0;
+                if closed || flags & libc::EPOLLIN != 0 {
+                    io.readable = true;
+                    wakers.extend(io.read_waker.take());
+                }
+                if closed || flags & libc::EPOLLOUT != 0 {
+                    io.writable = true;
+                    wakers.extend(io.write_waker.take());
+                }
+                // EPOLLONESHOT 触发后 fd 被禁用，还有 waker 在等另一种事件时重新注册
+                let remaining = io.interests();
+                if remaining != 0 {
+                    let events = remaining | libc::EPOLLONESHOT as u32;
+                    if let Err(err) = self.ctl(libc::EPOLL_CTL_MOD, fd, events, fd as u64)
+                        && result.is_ok()
+                    {
+                        result = Err(err);
+                    }
+                }
+            }
+        }
+        // 调用 waker 时不持有 sources 的锁
+        wakers.into_iter().for_each(Waker::wake);
+        result
+    }
+}
+
+/// 离开 `Reactor::enter` 时恢复之前的 reactor
+pub(crate) struct EnterGuard {
+    previous: Option<Arc<Reactor>>,
+}
+
+impl Drop for EnterGuard {
+    fn drop(&mut self) {
+        CURRENT.set(self.previous.take());
+    }
+}
+
+/// 创建非阻塞管道，返回 (读端, 写端)
+fn pipe() -> io::Result<(OwnedFd, OwnedFd)> {
+    let mut fds = [0; 2];
+    cvt(unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_NONBLOCK | libc::O_CLOEXEC) })?;
+    Ok(unsafe { (OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1])) })
+}
+
+/// 等待 fd 就绪后执行一次读或写，`WouldBlock` 时清除就绪标志继续等待
+async fn io_op(
+    reactor: &Reactor,
+    fd: RawFd,
+    interest: Interest,
+    mut op: impl FnMut() -> isize,
+) -> io::Result<usize> {
+    loop {
+        poll_fn(|cx| reactor.poll_ready(fd, interest, cx)).await?;
+        let result = op();
+        if result >= 0 {
+            return Ok(result as usize);
+        }
+        let err = io::Error::last_os_error();
+        if err.kind() != io::ErrorKind::WouldBlock {
+            return Err(err);
+        }
+        reactor.clear_ready(fd, interest);
+    }
+}
+
+/// 测试 reactor：SimpleExecutor 在 epoll_wait 上等待
+///
+/// 1. 另一个线程 100ms 后向管道 A 写入数据，任务等待管道 A 可读 → epoll 报告可读
+/// 2. 任务把读到的数据写入管道 B（等待可写），主线程最后从管道 B 读出
+/// 3. 另一个任务等待 AsyncTimerFuture → 定时器驱动线程通过 eventfd 叫醒 executor
+pub fn test_reactor() {
+    println!("\n=== Reactor 示例：SimpleExecutor 在 epoll_wait 上等待 ===");
+
+    let executor = SimpleExecutor::new();
+    let (a_read, a_write) = pipe().unwrap();
+    let (b_read, b_write) = pipe().unwrap();
+
+    let relay = executor.spawn(async move {
+        let reactor = Reactor::current();
+        let (input, output) = (a_read.as_raw_fd(), b_write.as_raw_fd());
+        reactor.register(input).unwrap();
+        reactor.register(output).unwrap();
+
+        let mut buf = [0u8; 64];
+        let len = io_op(&reactor, input, Interest::Read, || unsafe {
+            libc::read(input, buf.as_mut_ptr() as *mut libc::c_void, buf.len())
+        })
+        .await
+        .unwrap();
+        io_op(&reactor, output, Interest::Write, || unsafe {
+            libc::write(output, buf.as_ptr() as *const libc::c_void, len)
+        })
+        .await
+        .unwrap();
+
+        reactor.deregister(input);
+        reactor.deregister(output);
+        len
+    });
+    let timer = executor.spawn(AsyncTimerFuture::new(Duration::from_millis(50)));
+
+    let writer = std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(100));
+        let msg = b"hello from another thread";
+        let written = unsafe {
+            libc::write(a_write.as_raw_fd(), msg.as_ptr() as *const libc::c_void, msg.len())
+        };
+        assert_eq!(written, msg.len() as isize);
+    });
+
+    let (len, timer_result) = executor.block_on(async move { (relay.await.unwrap(), timer.await.unwrap()) });
+    writer.join().unwrap();
+
+    let mut buf = [0u8; 64];
+    let read = unsafe { libc::read(b_read.as_raw_fd(), buf.as_mut_ptr() as *mut libc::c_void, len) };
+    let message = String::from_utf8_lossy(&buf[..read as usize]);
+    println!("\n经过 executor 转发的数据: {}", message);
+    println!("定时器: {}", timer_result);
+    assert_eq!(message, "hello from another thread");
+
+    println!("\n关键点：");
+    println!("- executor 空闲时阻塞在 epoll_wait 上，而不是 Condvar::wait");
+    println!("- fd 就绪时，reactor 调用保存的 waker，把任务放回就绪队列");
+    println!("- 其他线程的唤醒通过写 eventfd 让 epoll_wait 返回");
+}
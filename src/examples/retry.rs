@@ -0,0 +1,307 @@
+use std::cell::RefCell;
+use std::collections::hash_map::RandomState;
+use std::future::Future;
+use std::hash::{BuildHasher, Hasher};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use super::simple_executor::SimpleExecutor;
+use super::timer::{Sleep, now, sleep};
+
+/// 重试策略：指数退避、抖动和最大尝试次数
+///
+/// 第 n 次重试之前等待 `initial_delay * multiplier^(n-1)`，不超过 `max_delay`；
+/// 设置了抖动时，再随机缩短最多 `jitter` 的比例，避免大量客户端在同一时刻一起重试。
+///
+/// 和 `SimpleExecutor` 的选项一样用 `with_*` 方法设置。
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    initial_delay: Duration,
+    multiplier: f64,
+    max_delay: Duration,
+    max_attempts: usize,
+    jitter: f64,
+    seed: u64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RetryPolicy {
+    /// 默认：100ms 起步，每次翻倍，最多等 10s，一共尝试 5 次，没有抖动
+    pub fn new() -> Self {
+        Self {
+            initial_delay: Duration::from_millis(100),
+            multiplier: 2.0,
+            max_delay: Duration::from_secs(10),
+            max_attempts: 5,
+            jitter: 0.0,
+            // 没有指定种子时每个策略的抖动都不同
+            seed: RandomState::new().build_hasher().finish(),
+        }
+    }
+
+    pub fn with_initial_delay(mut self, delay: Duration) -> Self {
+        self.initial_delay = delay;
+        self
+    }
+
+    pub fn with_multiplier(mut self, multiplier: f64) -> Self {
+        assert!(multiplier >= 1.0, "multiplier 不能小于 1");
+        self.multiplier = multiplier;
+        self
+    }
+
+    pub fn with_max_delay(mut self, delay: Duration) -> Self {
+        self.max_delay = delay;
+        self
+    }
+
+    /// 一共最多尝试几次（包括第一次）
+    pub fn with_max_attempts(mut self, attempts: usize) -> Self {
+        assert!(attempts > 0, "max_attempts 必须大于 0");
+        self.max_attempts = attempts;
+        self
+    }
+
+    /// 每次等待随机缩短 `[0, jitter)` 的比例，`jitter` 在 0 到 1 之间
+    pub fn with_jitter(mut self, jitter: f64) -> Self {
+        assert!((0.0..=1.0).contains(&jitter), "jitter 必须在 0 到 1 之间");
+        self.jitter = jitter;
+        self
+    }
+
+    /// 固定抖动的随机种子，同样的种子得到同样的等待时间，测试时可以精确断言
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// 每次重试之前的等待时间，一共 `max_attempts - 1` 个
+    pub fn backoff(&self) -> Backoff {
+        Backoff {
+            next: self.initial_delay,
+            remaining: self.max_attempts - 1,
+            rng: self.seed,
+            policy: self.clone(),
+        }
+    }
+}
+
+/// `RetryPolicy::backoff` 返回的迭代器
+pub struct Backoff {
+    policy: RetryPolicy,
+    next: Duration,
+    remaining: usize,
+    rng: u64,
+}
+
+impl Backoff {
+    /// splitmix64：足够均匀，不需要额外的依赖；返回 [0, 1) 之间的数
+    fn next_unit(&mut self) -> f64 {
+        self.rng = self.rng.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.rng;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+        (z >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+impl Iterator for Backoff {
+    type Item = Duration;
+
+    fn next(&mut self) -> Option<Duration> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+        let base = self.next.min(self.policy.max_delay);
+        self.next = base.mul_f64(self.policy.multiplier);
+        if self.policy.jitter == 0.0 {
+            return Some(base);
+        }
+        let unit = self.next_unit();
+        // 时间轮的精度是 1ms，到期时刻向上取整；这里也取整，实际等待的时间就和返回的一致
+        let jittered = base.mul_f64(1.0 - self.policy.jitter * unit);
+        Some(Duration::from_millis(jittered.as_nanos().div_ceil(1_000_000) as u64))
+    }
+}
+
+/// 按策略重试一个异步操作
+///
+/// `op` 每次被调用都创建一个新的 future（一次尝试）。返回 `Ok` 时结束；
+/// 返回 `Err` 时按退避时间等待后重试，直到尝试次数用完，返回最后一次的错误。
+/// 默认所有错误都重试，用 `retry_if` 只重试暂时性的错误。
+///
+/// 等待使用时间轮上的 `Sleep`，所以在 `SimpleExecutor::new_paused()` 中按虚拟时间推进。
+pub fn retry<Op, Fut, T, E>(policy: RetryPolicy, op: Op) -> Retry<Op, Fut, fn(&E) -> bool>
+where
+    Op: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+{
+    Retry {
+        backoff: policy.backoff(),
+        op,
+        classify: |_| true,
+        state: RetryState::Idle,
+    }
+}
+
+pub struct Retry<Op, Fut, C> {
+    backoff: Backoff,
+    op: Op,
+    classify: C,
+    // 结构化 pin：正在进行的尝试
+    state: RetryState<Fut>,
+}
+
+enum RetryState<Fut> {
+    // 下一次 poll 时开始新的尝试
+    Idle,
+    Running(Fut),
+    Sleeping(Sleep),
+}
+
+impl<Op, Fut, C> Retry<Op, Fut, C> {
+    /// 只有 `classify` 返回 `true` 的错误才重试，其余的错误立刻返回
+    pub fn retry_if<E, C2>(self, classify: C2) -> Retry<Op, Fut, C2>
+    where
+        C2: FnMut(&E) -> bool,
+    {
+        Retry {
+            backoff: self.backoff,
+            op: self.op,
+            classify,
+            state: self.state,
+        }
+    }
+}
+
+impl<Op, Fut, C, T, E> Future for Retry<Op, Fut, C>
+where
+    Op: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+    C: FnMut(&E) -> bool,
+{
+    type Output = Result<T, E>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // 安全性：宏不能投影枚举的变体，所以手写；只有 state 是结构化 pin 的，Retry 没有实现 Drop
+        let this = unsafe { self.get_unchecked_mut() };
+        // 状态切换都通过 Pin::set 原地替换，正在运行的尝试不会被移动
+        let mut state = unsafe { Pin::new_unchecked(&mut this.state) };
+        loop {
+            let next = match unsafe { state.as_mut().get_unchecked_mut() } {
+                RetryState::Idle => RetryState::Running((this.op)()),
+                RetryState::Running(attempt) => {
+                    let error = match unsafe { Pin::new_unchecked(attempt) }.poll(cx) {
+                        Poll::Pending => return Poll::Pending,
+                        Poll::Ready(Ok(value)) => return Poll::Ready(Ok(value)),
+                        Poll::Ready(Err(error)) => error,
+                    };
+                    if !(this.classify)(&error) {
+                        return Poll::Ready(Err(error));
+                    }
+                    match this.backoff.next() {
+                        Some(delay) => RetryState::Sleeping(sleep(delay)),
+                        None => return Poll::Ready(Err(error)),
+                    }
+                }
+                RetryState::Sleeping(timer) => {
+                    std::task::ready!(Pin::new(timer).poll(cx));
+                    RetryState::Idle
+                }
+            };
+            state.set(next);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FetchError {
+    // 暂时性的错误，比如超时、连接被重置
+    Unavailable,
+    // 重试也没用的错误，比如参数错误
+    BadRequest,
+}
+
+/// 第 `failures` 次之前都返回 `error`，之后成功；记录每次尝试的时刻
+async fn flaky(log: &RefCell<Vec<Instant>>, failures: usize, error: FetchError) -> Result<&'static str, FetchError> {
+    log.borrow_mut().push(now());
+    if log.borrow().len() <= failures {
+        Err(error)
+    } else {
+        Ok("响应")
+    }
+}
+
+/// 相邻两次尝试之间的间隔
+fn gaps(log: &RefCell<Vec<Instant>>) -> Vec<Duration> {
+    log.borrow().windows(2).map(|pair| pair[1] - pair[0]).collect()
+}
+
+/// 测试 retry
+///
+/// 在虚拟时钟上运行，所以可以精确断言退避的时间表：
+/// 1. 失败 3 次后成功：间隔 10ms、20ms、40ms
+/// 2. 一直失败：间隔被 `max_delay` 限制在 25ms，尝试 5 次后返回最后的错误
+/// 3. `retry_if`：不可重试的错误立刻返回
+/// 4. 抖动：同样的种子得到同样的时间表，每个间隔都在 `[base * (1 - jitter), base]` 之内
+pub fn test_retry() {
+    println!("\n=== retry 示例：指数退避和抖动 ===");
+    let executor = SimpleExecutor::new_paused();
+    let policy = RetryPolicy::new()
+        .with_initial_delay(Duration::from_millis(10))
+        .with_multiplier(2.0)
+        .with_max_attempts(5);
+    let ms = |values: &[u64]| values.iter().map(|&ms| Duration::from_millis(ms)).collect::<Vec<_>>();
+
+    // 1. 失败 3 次后成功
+    let log = RefCell::new(Vec::new());
+    let result = executor.block_on(retry(policy.clone(), || flaky(&log, 3, FetchError::Unavailable)));
+    println!("失败 3 次后成功: {:?}，间隔 {:?}", result, gaps(&log));
+    assert_eq!(result, Ok("响应"));
+    assert_eq!(gaps(&log), ms(&[10, 20, 40]));
+
+    // 2. 一直失败，间隔不超过 max_delay
+    let log = RefCell::new(Vec::new());
+    let capped = policy.clone().with_max_delay(Duration::from_millis(25));
+    let result = executor.block_on(retry(capped, || flaky(&log, usize::MAX, FetchError::Unavailable)));
+    println!("一直失败: {:?}，间隔 {:?}", result, gaps(&log));
+    assert_eq!(result, Err(FetchError::Unavailable));
+    assert_eq!(gaps(&log), ms(&[10, 20, 25, 25]));
+
+    // 3. 不可重试的错误
+    let log = RefCell::new(Vec::new());
+    let result = executor.block_on(
+        retry(policy.clone(), || flaky(&log, usize::MAX, FetchError::BadRequest))
+            .retry_if(|error| *error == FetchError::Unavailable),
+    );
+    println!("不可重试的错误: {:?}，尝试了 {} 次", result, log.borrow().len());
+    assert_eq!(result, Err(FetchError::BadRequest));
+    assert_eq!(log.borrow().len(), 1);
+
+    // 4. 抖动
+    let jittered = policy.clone().with_jitter(0.5).with_seed(42);
+    let log = RefCell::new(Vec::new());
+    let result = executor.block_on(retry(jittered.clone(), || flaky(&log, usize::MAX, FetchError::Unavailable)));
+    assert_eq!(result, Err(FetchError::Unavailable));
+    let schedule: Vec<_> = jittered.backoff().collect();
+    println!("抖动 50%，种子 42: 间隔 {:?}", gaps(&log));
+    // 虚拟时钟直接跳到到期时刻，观察到的间隔和时间表完全一致
+    assert_eq!(gaps(&log), schedule);
+    for (delay, base) in schedule.iter().zip(policy.backoff()) {
+        assert!(*delay > base / 2 && *delay <= base);
+    }
+    assert_ne!(schedule, jittered.with_seed(7).backoff().collect::<Vec<_>>());
+
+    println!("\n关键点：");
+    println!("- 每次失败后等待的时间指数增长，并被 max_delay 限制");
+    println!("- 抖动让大量客户端的重试错开；固定种子让时间表可以复现");
+    println!("- 用分类函数区分暂时性错误和永久性错误，后者立刻返回");
+}
@@ -1,22 +1,29 @@
 use std::future::Future;
 use std::pin::Pin;
-use std::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+use std::task::{Context, Poll, Waker};
+
+use super::fuse::FusedFuture;
 
 /// SimpleCoroutine: 编译器生成的等价代码（无 await 的 async 函数）
 pub enum SimpleCoroutine {
     Unresumed,
     Returned,
+    // 函数体不会 panic，这个状态永远不会出现；保留它是因为每个 async fn 的状态机都有这个状态
     #[allow(dead_code)]
-    Panicked,  // 这个 variant 确实不会被使用，保留 allow(dead_code)
+    Panicked,
 }
 
 impl Future for SimpleCoroutine {
     type Output = i32;
     
     fn poll(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Self::Output> {
-        let this = unsafe { self.get_unchecked_mut() };
+        // 状态里没有字段，SimpleCoroutine 是 Unpin 的，不需要 unsafe
+        let this = self.get_mut();
         match this {
             SimpleCoroutine::Unresumed => {
+                // 函数体只是返回 42，不会 panic，直接进入 Returned。
+                // 函数体可能 panic 时，执行期间状态是 Panicked，panic 时就停在那里
+                // （见 GreetCoroutine 的 mem::replace 和 desugared.rs 的 fail_after）
                 *this = SimpleCoroutine::Returned;
                 Poll::Ready(42)
             }
@@ -26,26 +33,19 @@ impl Future for SimpleCoroutine {
     }
 }
 
+impl FusedFuture for SimpleCoroutine {
+    fn is_terminated(&self) -> bool {
+        !matches!(self, SimpleCoroutine::Unresumed)
+    }
+}
+
 fn simple() -> impl Future<Output = i32> {
     SimpleCoroutine::Unresumed
 }
 
 /// 测试 SimpleCoroutine
 pub fn test_simple_coroutine() {
-    fn create_waker() -> Waker {
-        unsafe fn clone(data: *const ()) -> RawWaker {
-            RawWaker::new(data, &VTABLE)
-        }
-        unsafe fn wake(_data: *const ()) {}
-        unsafe fn wake_by_ref(_data: *const ()) {}
-        unsafe fn drop(_data: *const ()) {}
-        
-        const VTABLE: RawWakerVTable = RawWakerVTable::new(clone, wake, wake_by_ref, drop);
-        unsafe { Waker::from_raw(RawWaker::new(&(), &VTABLE)) }
-    }
-    
-    let waker = create_waker();
-    let mut cx = Context::from_waker(&waker);
+    let mut cx = Context::from_waker(Waker::noop());
     let mut fut = Box::pin(simple());
     
     match fut.as_mut().poll(&mut cx) {
@@ -1,17 +1,211 @@
+use std::any::Any;
+use std::collections::VecDeque;
+use std::fmt;
 use std::future::Future;
-use std::pin::Pin;
-use std::sync::{Arc, Condvar, Mutex};
-use std::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
-use std::time::Duration;
+use std::panic::{self, AssertUnwindSafe};
+use std::pin::{Pin, pin};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, RawWaker, RawWakerVTable, Wake, Waker};
+use std::time::{Duration, Instant};
 
 // 导入 AsyncTimerFuture 用于演示
+use super::clock::{Clock, MockClock, SystemClock};
 use super::custom_waker::AsyncTimerFuture;
+use super::lost_wakeup::{LostWakeup, LostWakeupDetector};
+#[cfg(debug_assertions)]
+use super::poll_after_ready::{EnterGuard as PollAfterReadyEnterGuard, PollAfterReady, PollAfterReadyDetector};
+use super::quiet_panic::quiet_panics;
+use super::reactor::{EnterGuard as ReactorEnterGuard, Reactor};
+use super::timeout::{Elapsed, timeout};
+use super::timer::{EnterGuard as TimerEnterGuard, TimerDriver, sleep};
+#[cfg(debug_assertions)]
+use super::tracked_waker::{RefCount, WakerReport, WakerTracker};
+use super::waker::WakerKind;
+
+/// 被 spawn 的任务在 executor 内部的统一形态：擦除了输出类型的 future
+///
+/// 输出只告诉 executor 任务是怎么结束的，真正的结果已经交给了 `JoinHandle`。
+pub(crate) type BoxFuture = Pin<Box<dyn Future<Output = TaskExit> + Send>>;
+
+/// 任务是怎么结束的
+pub(crate) enum TaskExit {
+    Returned,
+    // poll 时 panic 了，panic 的内容已经作为 `JoinError::Panic` 交给了 JoinHandle
+    Panicked,
+}
+
+/// 就绪队列：所有被唤醒、等待重新 poll 的任务都放在这里
+///
+/// `block_on` 的主 future 不进入队列（它不要求 `Send + 'static`），
+/// 只用 `main_woken` 标志记录它是否被唤醒。
+struct ReadyQueue {
+    state: Mutex<QueueState>,
+    reactor: Arc<Reactor>,
+    waker_kind: WakerKind,
+    // 开启追踪时，所有 waker 都被包装成 TrackedWaker（只在 debug 构建中）
+    #[cfg(debug_assertions)]
+    tracker: Option<WakerTracker>,
+    // 开启丢失唤醒检测时，每次 poll 都检查 future 有没有用到 waker
+    detector: Option<LostWakeupDetector>,
+    // 开启完成后再 poll 检测时，`checked` 包装的 future 向它报告（只在 debug 构建中）
+    #[cfg(debug_assertions)]
+    poll_after_ready: Option<Arc<PollAfterReadyDetector>>,
+    next_task_id: AtomicUsize,
+}
+
+struct QueueState {
+    main_woken: bool,
+    tasks: VecDeque<Arc<Task>>,
+    // executor 是否阻塞在 epoll_wait 上；只有这时唤醒才需要写 eventfd
+    parked: bool,
+}
+
+impl ReadyQueue {
+    fn track(&self, waker: Waker, task: impl FnOnce() -> String) -> Waker {
+        #[cfg(debug_assertions)]
+        if let Some(tracker) = &self.tracker {
+            return tracker.wrap(waker, task());
+        }
+        #[cfg(not(debug_assertions))]
+        let _ = task;
+        waker
+    }
+
+    /// 手写 vtable 的 waker：开启追踪时还检查 vtable 对 `Arc<T>` 引用计数的增减
+    ///
+    /// # Safety
+    ///
+    /// `raw` 的 data 必须是 `Arc::<T>::into_raw` 得到的指针，并且满足 `Waker::from_raw` 的要求。
+    #[cfg_attr(not(debug_assertions), allow(clippy::extra_unused_type_parameters))]
+    unsafe fn track_raw<T>(&self, raw: RawWaker, task: impl FnOnce() -> String) -> Waker {
+        #[cfg(debug_assertions)]
+        if let Some(tracker) = &self.tracker {
+            return unsafe { tracker.wrap_raw(raw, RefCount::arc::<T>(), task()) };
+        }
+        #[cfg(not(debug_assertions))]
+        let _ = task;
+        unsafe { Waker::from_raw(raw) }
+    }
+
+    /// poll 一次；开启丢失唤醒检测时由检测器包装 waker
+    fn poll<T>(
+        &self,
+        key: usize,
+        type_name: &'static str,
+        waker: &Waker,
+        poll: impl FnOnce(&mut Context<'_>) -> Poll<T>,
+    ) -> Poll<T> {
+        match &self.detector {
+            Some(detector) => detector.poll(key, type_name, waker, poll),
+            None => poll(&mut Context::from_waker(waker)),
+        }
+    }
+
+    fn wake_main(&self) {
+        if let Some(detector) = &self.detector {
+            detector.woken(0);
+        }
+        let mut state = self.state.lock().unwrap();
+        state.main_woken = true;
+        self.unpark_if_parked(&state);
+    }
+
+    fn push(&self, task: Arc<Task>) {
+        let mut state = self.state.lock().unwrap();
+        state.tasks.push_back(task);
+        self.unpark_if_parked(&state);
+    }
+
+    fn unpark_if_parked(&self, state: &QueueState) {
+        if state.parked {
+            self.reactor.unpark();
+        }
+    }
+}
+
+// WakerKind::Wake：主 future 的 waker 直接由 Arc<ReadyQueue> 构造
+impl Wake for ReadyQueue {
+    fn wake(self: Arc<Self>) {
+        self.wake_main();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.wake_main();
+    }
+}
+
+/// 一个被 spawn 的任务
+///
+/// 每个任务拥有自己的 waker（见 `task_waker`），唤醒时只把**这个任务**放回就绪队列。
+struct Task {
+    id: usize,
+    // 被 spawn 的 future 的类型名，用于诊断报告
+    type_name: &'static str,
+    state: Mutex<TaskState>,
+    // 是否已经在就绪队列中，避免同一个任务被重复入队
+    queued: AtomicBool,
+    queue: Arc<ReadyQueue>,
+}
+
+/// 任务的状态，和编译器为 async fn 生成的状态机（见 `SimpleCoroutine`）一样：
+/// 正常返回后是 `Returned`，poll 时 panic 后是 `Panicked`，两者都不能再被 poll
+enum TaskState {
+    Running(BoxFuture),
+    Returned,
+    Panicked,
+}
+
+impl Task {
+    fn schedule(self: &Arc<Self>) {
+        if let Some(detector) = &self.queue.detector {
+            detector.woken(self.id);
+        }
+        if !self.queued.swap(true, Ordering::AcqRel) {
+            self.queue.push(self.clone());
+        }
+    }
+
+    fn run(self: Arc<Self>) {
+        // 先清除入队标志：poll 期间发生的唤醒需要让任务重新入队
+        self.queued.store(false, Ordering::Release);
+
+        let mut state = self.state.lock().unwrap();
+        // 任务已经返回或者 panic 了，但仍有旧的 waker 被调用：直接忽略
+        let TaskState::Running(future) = &mut *state else {
+            return;
+        };
+        let waker = task_waker(self.clone());
+        // panic 在 joinable 包装的 future 内部被捕获，不会传到这里，executor 和其他任务照常运行
+        let result = self
+            .queue
+            .poll(self.id, self.type_name, &waker, |cx| future.as_mut().poll(cx));
+        match result {
+            Poll::Ready(TaskExit::Returned) => *state = TaskState::Returned,
+            Poll::Ready(TaskExit::Panicked) => *state = TaskState::Panicked,
+            Poll::Pending => {}
+        }
+    }
+}
+
+// WakerKind::Wake：任务的 waker 由 Arc<Task> 构造，唤醒时重新调度这个任务
+impl Wake for Task {
+    fn wake(self: Arc<Self>) {
+        self.schedule();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.schedule();
+    }
+}
 
 // Waker vtable 的回调函数（模块级别）
-// 这些函数用于 SimpleExecutor，展示了如何手动创建 waker
+// 这些函数用于 WakerKind::RawVTable，展示了如何手动创建 waker
 // 注意：这些函数虽然看起来"未使用"，但实际上被 WAKE_VTABLE 引用
+//
+// WAKE_VTABLE 用于 block_on 的主 future：data 指向 Arc<ReadyQueue>
 unsafe fn clone_waker(ptr: *const ()) -> RawWaker {
-    let arc = unsafe { Arc::from_raw(ptr as *const (Mutex<bool>, Condvar)) };
+    let arc = unsafe { Arc::from_raw(ptr as *const ReadyQueue) };
     let clone = Arc::clone(&arc);
     std::mem::forget(arc);
     RawWaker::new(
@@ -21,34 +215,21 @@ unsafe fn clone_waker(ptr: *const ()) -> RawWaker {
 }
 
 unsafe fn wake_waker(ptr: *const ()) {
-    // 从原始指针恢复 Arc
-    let arc = unsafe { Arc::from_raw(ptr as *const (Mutex<bool>, Condvar)) };
-    let (lock, cvar) = &*arc;
-    
+    // 从原始指针恢复 Arc，wake 会消耗 waker，所以这里让 arc 正常 drop
+    let arc = unsafe { Arc::from_raw(ptr as *const ReadyQueue) };
     // 设置唤醒标志并通知等待的线程
-    {
-        let mut woken = lock.lock().unwrap();
-        *woken = true;
-    }
-    cvar.notify_one();
-    
-    // 不要 drop arc，因为它是从 into_raw 创建的
-    std::mem::forget(arc);
+    arc.wake_main();
 }
 
 unsafe fn wake_by_ref_waker(ptr: *const ()) {
-    let arc = unsafe { Arc::from_raw(ptr as *const (Mutex<bool>, Condvar)) };
-    let (lock, cvar) = &*arc;
-    {
-        let mut woken = lock.lock().unwrap();
-        *woken = true;
-    }
-    cvar.notify_one();
+    let arc = unsafe { Arc::from_raw(ptr as *const ReadyQueue) };
+    arc.wake_main();
+    // 不要 drop arc，因为 wake_by_ref 不消耗 waker
     std::mem::forget(arc);
 }
 
 unsafe fn drop_waker(ptr: *const ()) {
-    drop(unsafe { Arc::from_raw(ptr as *const (Mutex<bool>, Condvar)) });
+    drop(unsafe { Arc::from_raw(ptr as *const ReadyQueue) });
 }
 
 const WAKE_VTABLE: RawWakerVTable = RawWakerVTable::new(
@@ -58,26 +239,220 @@ const WAKE_VTABLE: RawWakerVTable = RawWakerVTable::new(
     drop_waker,
 );
 
+// TASK_VTABLE 用于被 spawn 的任务：data 指向 Arc<Task>
+// 唤醒时只把对应的任务放回就绪队列
+unsafe fn clone_task_waker(ptr: *const ()) -> RawWaker {
+    let arc = unsafe { Arc::from_raw(ptr as *const Task) };
+    let clone = Arc::clone(&arc);
+    std::mem::forget(arc);
+    RawWaker::new(Arc::into_raw(clone) as *const (), &TASK_VTABLE)
+}
+
+unsafe fn wake_task_waker(ptr: *const ()) {
+    let arc = unsafe { Arc::from_raw(ptr as *const Task) };
+    arc.schedule();
+}
+
+unsafe fn wake_by_ref_task_waker(ptr: *const ()) {
+    let arc = unsafe { Arc::from_raw(ptr as *const Task) };
+    arc.schedule();
+    std::mem::forget(arc);
+}
+
+unsafe fn drop_task_waker(ptr: *const ()) {
+    drop(unsafe { Arc::from_raw(ptr as *const Task) });
+}
+
+const TASK_VTABLE: RawWakerVTable = RawWakerVTable::new(
+    clone_task_waker,
+    wake_task_waker,
+    wake_by_ref_task_waker,
+    drop_task_waker,
+);
+
+fn task_waker(task: Arc<Task>) -> Waker {
+    let queue = task.queue.clone();
+    let id = task.id;
+    match queue.waker_kind {
+        WakerKind::Wake => queue.track(Waker::from(task), || format!("task-{id}")),
+        WakerKind::RawVTable => unsafe {
+            let raw = RawWaker::new(Arc::into_raw(task) as *const (), &TASK_VTABLE);
+            queue.track_raw::<Task>(raw, || format!("task-{id}"))
+        },
+    }
+}
+
+/// spawn 返回的句柄，await 它可以拿到任务的输出
+///
+/// 和 `AsyncTimerFuture` 的 `SharedState` 是同一个模式：
+/// 任务完成时写入结果并调用保存的 waker，等待方在 poll 时注入 waker。
+/// 任务 panic 时得到 `Err(JoinError::Panic(..))`。
+pub struct JoinHandle<T> {
+    state: Arc<Mutex<JoinState<T>>>,
+}
+
+struct JoinState<T> {
+    result: Option<Result<T, JoinError>>,
+    waker: Option<Waker>,
+}
+
+impl<T> Future for JoinHandle<T> {
+    type Output = Result<T, JoinError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = self.state.lock().unwrap();
+        match state.result.take() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+/// 任务没有正常完成
+pub enum JoinError {
+    /// 任务在 poll 时 panic 了，里面是 panic 的内容（`panic!` 的参数）
+    Panic(Box<dyn Any + Send + 'static>),
+}
+
+impl JoinError {
+    pub fn is_panic(&self) -> bool {
+        matches!(self, JoinError::Panic(_))
+    }
+
+    /// 取出 panic 的内容，可以用 `std::panic::resume_unwind` 继续传播
+    pub fn into_panic(self) -> Box<dyn Any + Send + 'static> {
+        match self {
+            JoinError::Panic(payload) => payload,
+        }
+    }
+
+    /// panic 的消息；内容不是字符串时为 `None`
+    pub fn panic_message(&self) -> Option<&str> {
+        match self {
+            JoinError::Panic(payload) => payload
+                .downcast_ref::<&str>()
+                .copied()
+                .or_else(|| payload.downcast_ref::<String>().map(String::as_str)),
+        }
+    }
+}
+
+impl fmt::Debug for JoinError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.panic_message() {
+            Some(message) => write!(f, "JoinError::Panic({message:?})"),
+            None => write!(f, "JoinError::Panic(..)"),
+        }
+    }
+}
+
+impl fmt::Display for JoinError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.panic_message() {
+            Some(message) => write!(f, "任务 panic: {message}"),
+            None => write!(f, "任务 panic"),
+        }
+    }
+}
+
+impl std::error::Error for JoinError {}
+
+/// 把 future 包装成"完成时写入 JoinHandle"的任务 future
+///
+/// 任何 executor 都可以复用这个函数：只要把返回的 `BoxFuture` 跑完，
+/// 对应的 `JoinHandle` 就会拿到结果。
+///
+/// 每次 poll 都在 `catch_unwind` 中进行：里面的 future panic 时，
+/// panic 的内容作为 `JoinError::Panic` 交给 `JoinHandle`，任务 future 返回 `TaskExit::Panicked`，
+/// 而不是让 panic 一路传到 executor 的循环，把整个线程连同其他任务一起带走。
+pub(crate) fn joinable<F>(future: F) -> (BoxFuture, JoinHandle<F::Output>)
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    let state = Arc::new(Mutex::new(JoinState {
+        result: None,
+        waker: None,
+    }));
+    let task = JoinTask {
+        future: Box::pin(future),
+        state: state.clone(),
+    };
+    (Box::pin(task), JoinHandle { state })
+}
+
+struct JoinTask<F: Future> {
+    future: Pin<Box<F>>,
+    state: Arc<Mutex<JoinState<F::Output>>>,
+}
+
+impl<F: Future> JoinTask<F> {
+    fn complete(&self, result: Result<F::Output, JoinError>) {
+        let waker = {
+            let mut state = self.state.lock().unwrap();
+            state.result = Some(result);
+            state.waker.take()
+        };
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+impl<F: Future> Future for JoinTask<F> {
+    type Output = TaskExit;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<TaskExit> {
+        // panic 之后任务不会再被 poll，里面的 future 处于什么状态都没关系
+        let polled = panic::catch_unwind(AssertUnwindSafe(|| self.future.as_mut().poll(cx)));
+        match polled {
+            Ok(Poll::Ready(output)) => {
+                self.complete(Ok(output));
+                Poll::Ready(TaskExit::Returned)
+            }
+            Ok(Poll::Pending) => Poll::Pending,
+            Err(payload) => {
+                self.complete(Err(JoinError::Panic(payload)));
+                Poll::Ready(TaskExit::Panicked)
+            }
+        }
+    }
+}
+
 /// 简单的 executor：演示如何使用 waker
 ///
-/// 这是一个极简的 executor，实际运行时（如 tokio）会更复杂
+/// 这是一个极简的单线程 executor，实际运行时（如 tokio）会更复杂
 /// 这个例子展示了如何手动创建 executor 和 waker
 ///
-/// # 重要限制：阻塞问题
+/// # 就绪队列
 ///
-/// **这个实现存在严重的局限性**：
+/// 1. **spawn**：把 future 包装成任务（`Task`）放入就绪队列，返回 `JoinHandle`
+/// 2. **每个任务一个 waker**：任务的 waker 被调用时，只把这个任务重新放回队列
+/// 3. **block_on 负责排空队列**：不断取出就绪的任务 poll，
+///    主 future 被唤醒时也会被 poll，直到主 future 完成
 ///
-/// 1. **单任务阻塞**：当 future 返回 `Poll::Pending` 时，executor 会在 `cvar.wait()` 上阻塞
-///    - 如果这是单线程 executor，整个线程被占用
-///    - 无法执行其他 future，无法并发处理多个任务
+/// 这样多个 `AsyncTimerFuture` 可以在同一个线程上同时等待：
+/// 某个任务返回 `Pending` 后，executor 继续 poll 其他就绪任务，而不是停下来等它。
 ///
-/// 2. **为什么还能工作**：
-///    - `block_on` 只运行一个 future，是单任务场景
-///    - 这是一个教学示例，用于展示 waker 的基本机制
+/// # 虚拟时钟
 ///
-/// # 实际运行时的设计（如 Tokio）
+/// `SimpleExecutor::new_paused()` 使用 `MockClock`（类似 tokio 的 `start_paused`）：
+/// 就绪队列为空、主 future 也没被唤醒时，说明所有任务都在等待，
+/// executor 直接把虚拟时间推进到下一个定时器的到期时刻，不需要真的睡眠。
+/// 测试因此可以瞬间完成，而且每次结果都一样。
+///
+/// # 空闲时在 epoll 上等待
+///
+/// 当队列为空、主 future 也没被唤醒时，executor 在 reactor 的 `epoll_wait` 上阻塞：
+/// - fd 就绪时，reactor 调用注册的 waker，任务回到就绪队列
+/// - 其他线程（如定时器驱动线程）调用 waker 时，写 eventfd 让 `epoll_wait` 返回
 ///
-/// 实际运行时不会这样阻塞：
+/// 因此 reactor 只支持 Linux。
+///
+/// # 实际运行时的设计（如 Tokio）
 ///
 /// 1. **事件驱动架构**：
 ///    - Future 返回 `Pending` 时，不阻塞线程
@@ -93,129 +468,434 @@ const WAKE_VTABLE: RawWakerVTable = RawWakerVTable::new(
 ///    - 可以同时管理数千个 future
 ///    - 通过任务调度器在 ready 的 future 之间切换
 ///    - 不会因为一个 future 等待而阻塞其他 future
-///
-/// # 总结
-///
-/// 这个 `SimpleExecutor` 是教学示例，展示了 waker 如何通知 executor 重新 poll，
-/// 但实际运行时需要非阻塞的事件驱动架构来支持并发执行多个 future。
 pub struct SimpleExecutor {
-    pair: Arc<(Mutex<bool>, Condvar)>,
+    queue: Arc<ReadyQueue>,
+    timer: Arc<TimerDriver>,
+    // with_clock 创建的驱动归这个 executor 所有，drop 时关闭它的后台线程
+    owns_timer: bool,
 }
 
 impl SimpleExecutor {
+    /// 使用真实时钟（全局定时器驱动）
     pub fn new() -> Self {
+        Self::with_timer(TimerDriver::global(), false)
+    }
+
+    /// 使用指定的时钟，executor 会创建自己的定时器驱动
+    ///
+    /// 真实时钟的驱动有一个后台线程，executor drop 时让它退出；
+    /// 之后这个驱动上还没到期的定时器不会再到期。
+    pub fn with_clock(clock: Arc<dyn Clock>) -> Self {
+        Self::with_timer(TimerDriver::new(clock), true)
+    }
+
+    /// 使用虚拟时钟：所有任务都空闲时，时间直接跳到下一个定时器的到期时刻
+    pub fn new_paused() -> Self {
+        Self::with_clock(Arc::new(MockClock::new()))
+    }
+
+    fn with_timer(timer: Arc<TimerDriver>, owns_timer: bool) -> Self {
         Self {
-            pair: Arc::new((Mutex::new(false), Condvar::new())),
+            queue: Arc::new(ReadyQueue {
+                state: Mutex::new(QueueState {
+                    main_woken: false,
+                    tasks: VecDeque::new(),
+                    parked: false,
+                }),
+                reactor: Arc::new(Reactor::new().expect("创建 epoll reactor 失败")),
+                waker_kind: WakerKind::default(),
+                #[cfg(debug_assertions)]
+                tracker: None,
+                detector: None,
+                #[cfg(debug_assertions)]
+                poll_after_ready: None,
+                next_task_id: AtomicUsize::new(1),
+            }),
+            timer,
+            owns_timer,
         }
     }
 
-    /// 创建一个 waker，当被唤醒时会设置标志位并通知条件变量
-    fn create_waker(&self) -> Waker {
-        // 克隆 Arc，然后转换为原始指针
-        // 注意：self.pair 是 Arc<(Mutex<bool>, Condvar)>，clone() 后得到新的 Arc
-        let arc_clone = self.pair.clone();
-        unsafe {
-            Waker::from_raw(RawWaker::new(
-                Arc::into_raw(arc_clone) as *const (),
-                &WAKE_VTABLE,
-            ))
-        }
+    /// 选择构造 waker 的方式，默认是 `WakerKind::Wake`
+    ///
+    /// 需要在 spawn 任务之前调用。
+    pub fn with_waker_kind(mut self, kind: WakerKind) -> Self {
+        Arc::get_mut(&mut self.queue)
+            .expect("with_waker_kind 必须在 spawn 之前调用")
+            .waker_kind = kind;
+        self
     }
 
-    /// 运行 future 直到完成
+    /// 用 `TrackedWaker` 包装所有 waker，统计每个任务的 clone/drop/wake 次数
     ///
-    /// # 工作流程
+    /// 用于调试：`WakerKind::RawVTable` 时检查手写 vtable 的引用计数，
+    /// executor 被 drop 时报告还没有被释放的 waker。只在 debug 构建中可用，需要在 spawn 任务之前调用。
+    #[cfg(debug_assertions)]
+    pub fn with_waker_tracking(mut self) -> Self {
+        Arc::get_mut(&mut self.queue)
+            .expect("with_waker_tracking 必须在 spawn 之前调用")
+            .tracker = Some(WakerTracker::new());
+        self
+    }
+
+    /// 开启丢失唤醒检测：future 返回 `Pending` 时没有保存或调用 waker，
+    /// 并且超过 `timeout` 仍未被唤醒，就报告它的类型名（见 `LostWakeupDetector`）
     ///
-    /// 1. 循环 poll future
-    /// 2. 如果返回 `Ready`，返回结果
-    /// 3. 如果返回 `Pending`，阻塞等待 waker 唤醒
-    /// 4. 被唤醒后，继续循环重新 poll
+    /// 需要在 spawn 任务之前调用。
+    pub fn with_lost_wakeup_detection(mut self, timeout: Duration) -> Self {
+        Arc::get_mut(&mut self.queue)
+            .expect("with_lost_wakeup_detection 必须在 spawn 之前调用")
+            .detector = Some(LostWakeupDetector::new(timeout));
+        self
+    }
+
+    /// 检测到的丢失唤醒；没有开启检测时为空
+    pub fn lost_wakeups(&self) -> Vec<LostWakeup> {
+        self.queue
+            .detector
+            .as_ref()
+            .map(LostWakeupDetector::reports)
+            .unwrap_or_default()
+    }
+
+    /// 开启完成后再 poll 检测：`checked` 包装的 future 在返回 `Ready` 之后又被 poll 时，
+    /// 记录它的类型名和调用 `checked` 的位置（见 `poll_after_ready`）
+    ///
+    /// 只检查用 `checked` 包装过的 future；executor 自己的任务完成后就被丢弃，不会再被 poll。
+    /// 只在 debug 构建中存在，需要在 spawn 任务之前调用。
+    #[cfg(debug_assertions)]
+    pub fn with_poll_after_ready_detection(mut self) -> Self {
+        Arc::get_mut(&mut self.queue)
+            .expect("with_poll_after_ready_detection 必须在 spawn 之前调用")
+            .poll_after_ready = Some(Arc::new(PollAfterReadyDetector::new()));
+        self
+    }
+
+    /// 检测到的完成后再 poll；没有开启检测时为空
+    #[cfg(debug_assertions)]
+    pub fn polled_after_ready(&self) -> Vec<PollAfterReady> {
+        self.queue
+            .poll_after_ready
+            .as_ref()
+            .map(|detector| detector.reports())
+            .unwrap_or_default()
+    }
+
+    /// 开启追踪时，返回当前的 waker 统计
+    #[cfg(debug_assertions)]
+    pub fn waker_report(&self) -> Option<WakerReport> {
+        self.queue.tracker.as_ref().map(WakerTracker::report)
+    }
+
+    /// 进入 executor 的上下文：在 guard 存活期间创建的定时器使用这个 executor 的时钟，
+    /// 创建的 I/O 对象注册到这个 executor 的 reactor
+    ///
+    /// `block_on` 内部会自动进入；在 `block_on` 之外创建定时器时需要手动调用，
+    /// 和 tokio 的 `Runtime::enter()` 一样。
+    pub fn enter(&self) -> EnterGuard {
+        EnterGuard {
+            _timer: self.timer.enter(),
+            _reactor: self.queue.reactor.enter(),
+            #[cfg(debug_assertions)]
+            _poll_after_ready: self.queue.poll_after_ready.as_ref().map(|detector| detector.enter()),
+        }
+    }
+
+    /// executor 时钟的当前时刻（虚拟时钟下是虚拟时间）
+    pub fn now(&self) -> Instant {
+        self.timer.now()
+    }
+
+    /// 创建主 future 的 waker，当被唤醒时会设置 `main_woken` 并叫醒 executor
+    fn create_waker(&self) -> Waker {
+        // 克隆 Arc，然后转换为 waker
+        let arc_clone = self.queue.clone();
+        match self.queue.waker_kind {
+            WakerKind::Wake => self.queue.track(Waker::from(arc_clone), || "block_on".to_string()),
+            WakerKind::RawVTable => unsafe {
+                let raw = RawWaker::new(Arc::into_raw(arc_clone) as *const (), &WAKE_VTABLE);
+                self.queue.track_raw::<ReadyQueue>(raw, || "block_on".to_string())
+            },
+        }
+    }
+
+    /// 创建一个任务并放入就绪队列
     ///
-    /// # 阻塞问题
+    /// 任务不会立刻执行，而是在 `block_on` 排空队列时被 poll。
+    /// 任务要能被其他线程的 waker 唤醒，所以要求 `Send + 'static`。
+    pub fn spawn<F>(&self, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        let type_name = std::any::type_name::<F>();
+        let (future, handle) = joinable(future);
+        let task = Arc::new(Task {
+            id: self.queue.next_task_id.fetch_add(1, Ordering::Relaxed),
+            type_name,
+            state: Mutex::new(TaskState::Running(future)),
+            queued: AtomicBool::new(false),
+            queue: self.queue.clone(),
+        });
+        task.schedule();
+        handle
+    }
+
+    /// 运行 future 直到完成，同时执行被 spawn 的任务
     ///
-    /// **注意**：当 future 返回 `Pending` 时，这里会阻塞整个线程：
+    /// # 工作流程
     ///
-    /// ```rust
-    /// while !*woken {
-    ///     woken = cvar.wait(woken).unwrap();  // 线程在这里阻塞！
-    /// }
-    /// ```
+    /// 1. 如果主 future 被唤醒，poll 它；返回 `Ready` 就返回结果
+    /// 2. 否则从就绪队列取出一个任务 poll
+    /// 3. 两者都没有时，阻塞等待 waker 唤醒
     ///
-    /// 这意味着：
-    /// - 如果这是单线程 executor，无法执行其他 future
-    /// - 实际运行时不会这样设计，而是使用非阻塞的事件循环
+    /// `block_on` 返回时，还没完成的任务留在 executor 中，
+    /// 下一次调用 `block_on` 时会继续执行。
     ///
     /// # Waker 如何触发重新 poll
     ///
     /// 1. Future 在 `poll` 中保存 waker（通过 `cx.waker().clone()`）
     /// 2. 异步操作完成后，调用 `waker.wake()`
-    /// 3. `wake()` 会执行 `wake_waker` 回调：
-    ///    - 设置 `woken = true`
-    ///    - 调用 `cvar.notify_one()` 唤醒等待的线程
-    /// 4. Executor 被唤醒，退出 `while` 循环
-    /// 5. 继续 `loop`，再次调用 `poll`，这次返回 `Ready`
-    pub fn block_on<F: Future>(&self, mut future: F) -> F::Output {
+    /// 3. `wake()` 会执行 `Wake` 实现（或 vtable）中的回调：
+    ///    - 主 future 的 waker：设置 `main_woken = true`
+    ///    - 任务的 waker：把任务放回就绪队列
+    ///    - executor 阻塞在 `epoll_wait` 上时，写 eventfd 叫醒它
+    /// 4. Executor 被唤醒，取出下一个要 poll 的 future
+    pub fn block_on<F: Future>(&self, future: F) -> F::Output {
+        let _guard = self.enter();
         let waker = self.create_waker();
-        let mut cx = Context::from_waker(&waker);
-        let mut future = unsafe { Pin::new_unchecked(&mut future) };
+        let type_name = std::any::type_name::<F>();
+        let mut future = pin!(future);
+
+        // 主 future 一开始就需要被 poll 一次
+        self.queue.state.lock().unwrap().main_woken = true;
 
         loop {
-            match future.as_mut().poll(&mut cx) {
-                Poll::Ready(result) => return result,
-                Poll::Pending => {
-                    // 等待被唤醒
-                    println!("[executor] Future 返回 Pending，等待唤醒...");
-
-                    let (lock, cvar) = &*self.pair;
-                    // 注意：这里会阻塞整个线程，无法执行其他 future
-                    // 实际运行时不会这样设计
-                    let mut woken = lock.lock().unwrap();
-                    // 检查是否已经被唤醒（可能在获取锁之前就已经被唤醒了）
-                    while !*woken {
-                        // wait 会释放锁并等待，被唤醒后会重新获取锁
-                        woken = cvar.wait(woken).unwrap();
+            match self.next_runnable() {
+                Runnable::Main => {
+                    let result = self.queue.poll(0, type_name, &waker, |cx| future.as_mut().poll(cx));
+                    if let Poll::Ready(result) = result {
+                        return result;
                     }
-                    // 重置标志，为下次等待做准备
-                    *woken = false;
+                }
+                Runnable::Task(task) => task.run(),
+            }
+        }
+    }
+
+    /// 和 `block_on` 一样，但最多等待 `duration`
+    ///
+    /// 主 future 永远不被唤醒（比如忘了保存 waker）时，`block_on` 会让线程一直阻塞；
+    /// 这里到期后 drop 主 future 并返回 `Err(Elapsed)`，executor 线程可以继续做别的事。
+    /// 在 `poll` 中阻塞线程的 future 仍然无法被打断。
+    pub fn block_on_timeout<F: Future>(&self, duration: Duration, future: F) -> Result<F::Output, Elapsed> {
+        // 在 block_on 内部创建定时器，使用这个 executor 的时钟
+        self.block_on(async { timeout(duration, future).await })
+    }
 
-                    println!("[executor] 收到唤醒信号，重新 poll future");
+    /// 取出下一个要 poll 的 future，没有时阻塞等待
+    ///
+    /// 使用虚拟时钟时，不阻塞，而是把时间推进到下一个定时器的到期时刻；
+    /// 定时器到期会唤醒对应的任务，于是队列中又有了可以 poll 的 future。
+    fn next_runnable(&self) -> Runnable {
+        loop {
+            let mut state = self.queue.state.lock().unwrap();
+            if state.main_woken {
+                state.main_woken = false;
+                return Runnable::Main;
+            }
+            if let Some(task) = state.tasks.pop_front() {
+                return Runnable::Task(task);
+            }
+            if self.timer.is_paused() {
+                // 定时器到期时会调用 waker，waker 要获取队列的锁，所以先释放
+                drop(state);
+                if self.timer.advance_to_next() {
+                    continue;
+                }
+                state = self.queue.state.lock().unwrap();
+                if state.main_woken || !state.tasks.is_empty() {
+                    continue;
                 }
             }
+            println!("[executor] 没有就绪的 future，等待唤醒...");
+            // 先标记 parked 再释放锁：之后的唤醒都会写 eventfd，epoll_wait 不会错过
+            state.parked = true;
+            drop(state);
+            // 有丢失唤醒的嫌疑时，最多等到下一个嫌疑超时，以便及时报告
+            let timeout = self.queue.detector.as_ref().and_then(LostWakeupDetector::check);
+            self.queue.reactor.park(timeout).expect("epoll_wait 失败");
+            self.queue.state.lock().unwrap().parked = false;
+            println!("[executor] 收到唤醒信号，继续执行");
         }
     }
 }
 
+impl Drop for SimpleExecutor {
+    fn drop(&mut self) {
+        #[cfg(debug_assertions)]
+        if let Some(tracker) = &self.queue.tracker {
+            tracker.report_leaks();
+        }
+        if self.owns_timer {
+            self.timer.shutdown();
+        }
+    }
+}
+
+/// 离开 `SimpleExecutor::enter` 时恢复之前的定时器驱动、reactor 和检测器
+pub struct EnterGuard {
+    _timer: TimerEnterGuard,
+    _reactor: ReactorEnterGuard,
+    #[cfg(debug_assertions)]
+    _poll_after_ready: Option<PollAfterReadyEnterGuard>,
+}
+
+enum Runnable {
+    Main,
+    Task(Arc<Task>),
+}
+
 /// 测试 SimpleExecutor：展示如何手动创建 executor
 ///
 /// 这个例子展示了：
 /// 1. 如何手动创建 executor 和 waker
 /// 2. Waker 如何通知 executor 重新 poll future
-/// 3. 阻塞式 executor 的局限性
+/// 3. 没有就绪 future 时 executor 如何等待
+///
+/// 使用虚拟时钟运行：1 秒的定时器瞬间完成，虚拟时间正好前进 1 秒。
 pub fn test_simple_executor() {
     println!("\n=== SimpleExecutor 示例：手动创建 Executor ===");
     
     println!("\n场景：使用 SimpleExecutor 运行 AsyncTimerFuture");
-    println!("1. 创建 SimpleExecutor");
-    println!("2. 创建 AsyncTimerFuture（在后台线程等待 1 秒）");
+    println!("1. 创建 SimpleExecutor（使用虚拟时钟）");
+    println!("2. 创建 AsyncTimerFuture（在定时器驱动中登记 1 秒后到期）");
     println!("3. 使用 block_on 运行 future");
-    println!("4. Executor 在 Pending 时阻塞等待");
-    println!("5. 后台线程完成后唤醒 executor");
+    println!("4. Future 返回 Pending，就绪队列为空，executor 把虚拟时间推进到到期时刻");
+    println!("5. 定时器到期，唤醒 executor");
     println!("6. Executor 重新 poll，返回 Ready\n");
     
-    let executor = SimpleExecutor::new();
-    let future = AsyncTimerFuture::new(Duration::from_secs(1));
+    let executor = SimpleExecutor::new_paused();
+    // 在 executor 的上下文中创建定时器，让它使用 executor 的虚拟时钟
+    let future = {
+        let _guard = executor.enter();
+        AsyncTimerFuture::new(Duration::from_secs(1))
+    };
     
     let start = std::time::Instant::now();
+    let virtual_start = executor.now();
     let result = executor.block_on(future);
     let elapsed = start.elapsed();
+    let virtual_elapsed = executor.now() - virtual_start;
     
     println!("\n结果: {}", result);
-    println!("总耗时: {:?} (包含等待时间)", elapsed);
+    println!("虚拟时间: {:?}，真实耗时: {:?}", virtual_elapsed, elapsed);
+    assert_eq!(virtual_elapsed, Duration::from_secs(1));
     
     println!("\n关键点：");
     println!("- SimpleExecutor 展示了如何手动创建 executor");
-    println!("- 展示了 waker 如何通过 Condvar 唤醒 executor");
-    println!("- 注意：这个实现会阻塞线程，无法并发执行多个 future");
-    println!("- 实际运行时（如 tokio）使用非阻塞的事件驱动架构");
+    println!("- 展示了 waker 如何叫醒阻塞在 epoll_wait 上的 executor");
+    println!("- 虚拟时钟下，所有任务都空闲时时间直接跳到下一个 deadline");
+    println!("- 被 spawn 的任务可以并发执行，见 test_spawn_many_timers()");
+    println!("- 实际运行时（如 tokio）在空闲时等待 I/O 事件，而不只是等待 waker");
 }
 
+/// 测试就绪队列：在一个线程上同时等待多个 AsyncTimerFuture
+///
+/// 三个定时器分别等待 300ms、500ms、700ms，
+/// 如果是逐个阻塞等待，总耗时接近 1.5 秒；
+/// 放进就绪队列并发执行后，总耗时接近最长的 700ms。
+pub fn test_spawn_many_timers() {
+    println!("\n=== SimpleExecutor 示例：spawn 多个任务 ===");
+
+    let executor = SimpleExecutor::new();
+    let durations = [300, 500, 700];
+
+    let start = std::time::Instant::now();
+    let handles: Vec<_> = durations
+        .iter()
+        .map(|&ms| {
+            executor.spawn(async move {
+                AsyncTimerFuture::new(Duration::from_millis(ms)).await;
+                ms
+            })
+        })
+        .collect();
+
+    let results = executor.block_on(async move {
+        let mut results = Vec::new();
+        for handle in handles {
+            results.push(handle.await.unwrap());
+        }
+        results
+    });
+    let elapsed = start.elapsed();
+
+    println!("\n结果: {:?}", results);
+    println!("总耗时: {:?}", elapsed);
+    assert_eq!(results, durations);
+    assert!(elapsed < Duration::from_millis(durations.iter().sum()));
+
+    // 自己的真实时钟：驱动的后台线程在 executor drop 时退出，不会泄漏
+    let executor = SimpleExecutor::with_clock(Arc::new(SystemClock));
+    let driver = executor.timer.clone();
+    executor.block_on(async { sleep(Duration::from_millis(10)).await });
+    assert!(driver.has_background_thread());
+    drop(executor);
+    assert!(!driver.has_background_thread());
+
+    println!("\n关键点：");
+    println!("- 每个任务有自己的 waker，唤醒时只把这个任务放回就绪队列");
+    println!("- 一个任务返回 Pending 时，executor 继续 poll 其他任务");
+    println!("- 总耗时取决于最长的定时器，而不是所有定时器之和");
+    println!("- with_clock 创建的定时器驱动在 executor drop 时关闭，后台线程随之退出");
+}
+
+/// 等待 `ms` 毫秒，`fail` 时 panic，否则返回 `ms`
+async fn work(ms: u64, fail: bool) -> u64 {
+    sleep(Duration::from_millis(ms)).await;
+    if fail {
+        panic!("任务在 {ms}ms 时出错了");
+    }
+    ms
+}
+
+/// 测试任务的 panic 隔离
+///
+/// 三个任务中有两个 panic：
+/// 1. panic 的任务在 `JoinHandle` 上得到 `Err(JoinError::Panic(..))`，可以拿到 panic 的消息
+/// 2. 没有 panic 的任务照常完成，executor 也可以继续使用
+pub fn test_panic_isolation() {
+    println!("\n=== SimpleExecutor 示例：任务 panic 的隔离 ===");
+
+    let executor = SimpleExecutor::new_paused();
+    let start = executor.now();
+    let first = executor.spawn(work(10, true));
+    let survivor = executor.spawn(work(30, false));
+    let second = executor.spawn(work(20, true));
+    let (first, survivor, second) = {
+        // 不打印默认的 panic 信息；这里出错时 guard 也会恢复
+        let _quiet = quiet_panics();
+        executor.block_on(async { (first.await, survivor.await, second.await) })
+    };
+
+    println!("第一个任务: {:?}", first);
+    println!("第二个任务: {:?}", second);
+    println!("没有 panic 的任务: {:?}", survivor);
+    let first = first.unwrap_err();
+    assert!(first.is_panic());
+    assert_eq!(first.panic_message(), Some("任务在 10ms 时出错了"));
+    assert_eq!(second.unwrap_err().to_string(), "任务 panic: 任务在 20ms 时出错了");
+    assert_eq!(survivor.unwrap(), 30);
+    // panic 的任务没有拖慢其他任务
+    assert_eq!(executor.now() - start, Duration::from_millis(30));
+
+    // executor 还能继续使用
+    let handle = executor.spawn(work(5, false));
+    assert_eq!(executor.block_on(handle).unwrap(), 5);
+
+    println!("\n关键点：");
+    println!("- 每次 poll 任务都在 catch_unwind 中进行，panic 不会传到 executor 的循环");
+    println!("- panic 的任务进入 Panicked 状态，不会再被 poll；panic 的内容交给 JoinHandle");
+    println!("- 其他任务和 executor 照常运行，和 tokio 的 JoinError::Panic 一样");
+}
@@ -0,0 +1,573 @@
+use std::future::Future;
+use std::ops::DerefMut;
+use std::pin::{Pin, pin};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use super::combinator::select_start;
+use super::custom_waker::AsyncTimerFuture;
+use super::futures_unordered::FuturesUnordered;
+use super::pin_project::pin_project;
+use super::simple_executor::SimpleExecutor;
+use super::timer::now;
+
+/// 异步的迭代器：可以产出多个值的 future
+///
+/// `Future::poll` 只会返回一次 `Ready`；`poll_next` 每次返回 `Ready(Some(item))` 产出一个值，
+/// 返回 `Ready(None)` 表示结束。`Pending` 的含义和 future 一样：保存 waker，有新值时唤醒。
+pub trait Stream {
+    type Item;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>>;
+}
+
+impl<S: Stream + Unpin + ?Sized> Stream for &mut S {
+    type Item = S::Item;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<S::Item>> {
+        Pin::new(&mut **self).poll_next(cx)
+    }
+}
+
+/// 和 `Future` 一样：`pin!` 或 `Box::pin` 之后的 stream 是 `Unpin` 的，可以直接调用 `next()`
+impl<P> Stream for Pin<P>
+where
+    P: DerefMut<Target: Stream>,
+{
+    type Item = <P::Target as Stream>::Item;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.as_deref_mut().poll_next(cx)
+    }
+}
+
+/// Stream 的适配器
+///
+/// 和迭代器的适配器一样是惰性的：只是把 stream 包一层，poll 外层时才去 poll 里面的 stream。
+pub trait StreamExt: Stream {
+    /// 等待下一个值
+    fn next(&mut self) -> Next<'_, Self>
+    where
+        Self: Unpin,
+    {
+        Next { stream: self }
+    }
+
+    /// 收集所有的值
+    fn collect<C: Default + Extend<Self::Item>>(self) -> Collect<Self, C>
+    where
+        Self: Sized,
+    {
+        Collect {
+            stream: self,
+            items: C::default(),
+        }
+    }
+
+    fn map<T, F>(self, f: F) -> Map<Self, F>
+    where
+        F: FnMut(Self::Item) -> T,
+        Self: Sized,
+    {
+        Map { stream: self, f }
+    }
+
+    fn filter<P>(self, predicate: P) -> Filter<Self, P>
+    where
+        P: FnMut(&Self::Item) -> bool,
+        Self: Sized,
+    {
+        Filter {
+            stream: self,
+            predicate,
+        }
+    }
+
+    /// 每个值经过一个异步函数，一次只运行一个（按顺序）
+    fn then<Fut, F>(self, f: F) -> Then<Self, Fut, F>
+    where
+        F: FnMut(Self::Item) -> Fut,
+        Fut: Future,
+        Self: Sized,
+    {
+        Then {
+            stream: self,
+            f,
+            pending: None,
+        }
+    }
+
+    /// 只取前 `n` 个值
+    fn take(self, n: usize) -> Take<Self>
+    where
+        Self: Sized,
+    {
+        Take {
+            stream: self,
+            remaining: n,
+        }
+    }
+
+    /// 值本身是 future：最多同时运行 `limit` 个，按完成的顺序产出结果
+    fn buffer_unordered(self, limit: usize) -> BufferUnordered<Self>
+    where
+        Self::Item: Future,
+        Self: Sized,
+    {
+        assert!(limit > 0, "limit 必须大于 0");
+        BufferUnordered {
+            stream: self,
+            in_flight: FuturesUnordered::new(),
+            limit,
+            done: false,
+        }
+    }
+
+    /// 把值攒成批：攒够 `capacity` 个，或者这一批的第一个值等了 `timeout`，就产出一批
+    fn chunks_timeout(self, capacity: usize, timeout: Duration) -> ChunksTimeout<Self>
+    where
+        Self: Sized,
+    {
+        assert!(capacity > 0, "capacity 必须大于 0");
+        ChunksTimeout {
+            stream: self,
+            capacity,
+            timeout,
+            items: Vec::new(),
+            timer: None,
+            done: false,
+        }
+    }
+
+    /// 合并两个 stream：哪个先有值就先产出哪个，两个都结束时才结束
+    fn merge<S>(self, other: S) -> Merge<Self, S>
+    where
+        S: Stream<Item = Self::Item>,
+        Self: Sized,
+    {
+        Merge {
+            a: self,
+            b: other,
+            a_done: false,
+            b_done: false,
+        }
+    }
+}
+
+impl<S: Stream + ?Sized> StreamExt for S {}
+
+/// 从迭代器创建 stream，每次 poll 都立刻产出下一个值
+pub fn iter<I: IntoIterator>(items: I) -> Iter<I::IntoIter> {
+    Iter {
+        items: items.into_iter(),
+    }
+}
+
+pub struct Iter<I> {
+    items: I,
+}
+
+impl<I: Iterator + Unpin> Stream for Iter<I> {
+    type Item = I::Item;
+
+    fn poll_next(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Option<I::Item>> {
+        Poll::Ready(self.items.next())
+    }
+}
+
+pub struct Next<'a, S: ?Sized> {
+    stream: &'a mut S,
+}
+
+impl<S: Stream + Unpin + ?Sized> Future for Next<'_, S> {
+    type Output = Option<S::Item>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut *self.stream).poll_next(cx)
+    }
+}
+
+pin_project! {
+    pub struct Collect<S, C> {
+        #[pin]
+        stream: S,
+        items: C,
+    }
+}
+
+impl<S: Stream, C: Default + Extend<S::Item>> Future for Collect<S, C> {
+    type Output = C;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<C> {
+        // 结构化 pin：stream 不会被移动，items 不需要 pin
+        let mut this = self.project();
+        loop {
+            match this.stream.as_mut().poll_next(cx) {
+                Poll::Ready(Some(item)) => this.items.extend(Some(item)),
+                Poll::Ready(None) => return Poll::Ready(std::mem::take(this.items)),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+pin_project! {
+    pub struct Map<S, F> {
+        #[pin]
+        stream: S,
+        f: F,
+    }
+}
+
+impl<S: Stream, T, F: FnMut(S::Item) -> T> Stream for Map<S, F> {
+    type Item = T;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        let this = self.project();
+        this.stream.poll_next(cx).map(|item| item.map(this.f))
+    }
+}
+
+pin_project! {
+    pub struct Filter<S, P> {
+        #[pin]
+        stream: S,
+        predicate: P,
+    }
+}
+
+impl<S: Stream, P: FnMut(&S::Item) -> bool> Stream for Filter<S, P> {
+    type Item = S::Item;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<S::Item>> {
+        let mut this = self.project();
+        // 被过滤掉的值不能返回 Pending（没有人会唤醒我们），要继续 poll 下一个
+        loop {
+            match this.stream.as_mut().poll_next(cx) {
+                Poll::Ready(Some(item)) if !(this.predicate)(&item) => continue,
+                other => return other,
+            }
+        }
+    }
+}
+
+pin_project! {
+    pub struct Then<S, Fut, F> {
+        #[pin]
+        stream: S,
+        f: F,
+        // 正在运行的 future；和 stream 一样是结构化 pin 的
+        #[pin]
+        pending: Option<Fut>,
+    }
+}
+
+impl<S: Stream, Fut: Future, F: FnMut(S::Item) -> Fut> Stream for Then<S, Fut, F> {
+    type Item = Fut::Output;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Fut::Output>> {
+        let mut this = self.project();
+        loop {
+            if let Some(pending) = this.pending.as_mut().as_pin_mut() {
+                let output = match pending.poll(cx) {
+                    Poll::Ready(output) => output,
+                    Poll::Pending => return Poll::Pending,
+                };
+                // 原地 drop，不会移动
+                this.pending.set(None);
+                return Poll::Ready(Some(output));
+            }
+            match this.stream.as_mut().poll_next(cx) {
+                Poll::Ready(Some(item)) => this.pending.set(Some((this.f)(item))),
+                Poll::Ready(None) => return Poll::Ready(None),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+pin_project! {
+    pub struct Take<S> {
+        #[pin]
+        stream: S,
+        remaining: usize,
+    }
+}
+
+impl<S: Stream> Stream for Take<S> {
+    type Item = S::Item;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<S::Item>> {
+        let this = self.project();
+        if *this.remaining == 0 {
+            return Poll::Ready(None);
+        }
+        let item = std::task::ready!(this.stream.poll_next(cx));
+        if item.is_some() {
+            *this.remaining -= 1;
+        }
+        Poll::Ready(item)
+    }
+}
+
+pin_project! {
+    pub struct BufferUnordered<S: Stream>
+    where
+        S::Item: Future,
+    {
+        #[pin]
+        stream: S,
+        in_flight: FuturesUnordered<S::Item>,
+        limit: usize,
+        // 里面的 stream 已经结束
+        done: bool,
+    }
+}
+
+impl<S: Stream> Stream for BufferUnordered<S>
+where
+    S::Item: Future,
+{
+    type Item = <S::Item as Future>::Output;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut this = self.project();
+        // 先把正在运行的数量补到 limit
+        while !*this.done && this.in_flight.len() < *this.limit {
+            match this.stream.as_mut().poll_next(cx) {
+                Poll::Ready(Some(future)) => this.in_flight.push(future),
+                Poll::Ready(None) => *this.done = true,
+                Poll::Pending => break,
+            }
+        }
+        match Pin::new(this.in_flight).poll_next(cx) {
+            Poll::Ready(Some(output)) => Poll::Ready(Some(output)),
+            // 没有正在运行的 future：里面的 stream 也结束了才算结束
+            Poll::Ready(None) if *this.done => Poll::Ready(None),
+            _ => Poll::Pending,
+        }
+    }
+}
+
+pin_project! {
+    pub struct ChunksTimeout<S: Stream> {
+        #[pin]
+        stream: S,
+        capacity: usize,
+        timeout: Duration,
+        items: Vec<S::Item>,
+        // 这一批的第一个值到达时开始计时
+        timer: Option<AsyncTimerFuture>,
+        done: bool,
+    }
+}
+
+/// 产出攒下的这一批，并停止计时；没有攒下值时表示结束
+fn flush<T>(items: &mut Vec<T>, timer: &mut Option<AsyncTimerFuture>) -> Poll<Option<Vec<T>>> {
+    *timer = None;
+    if items.is_empty() {
+        return Poll::Ready(None);
+    }
+    Poll::Ready(Some(std::mem::take(items)))
+}
+
+impl<S: Stream> Stream for ChunksTimeout<S> {
+    type Item = Vec<S::Item>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Vec<S::Item>>> {
+        let mut this = self.project();
+        if *this.done {
+            return flush(this.items, this.timer);
+        }
+        loop {
+            match this.stream.as_mut().poll_next(cx) {
+                Poll::Ready(Some(item)) => {
+                    if this.items.is_empty() {
+                        *this.timer = Some(AsyncTimerFuture::new(*this.timeout));
+                    }
+                    this.items.push(item);
+                    if this.items.len() == *this.capacity {
+                        return flush(this.items, this.timer);
+                    }
+                }
+                Poll::Ready(None) => {
+                    *this.done = true;
+                    return flush(this.items, this.timer);
+                }
+                Poll::Pending => break,
+            }
+        }
+        // 没有新值了，看看这一批是否已经等够了
+        if let Some(timer) = this.timer.as_mut()
+            && Pin::new(timer).poll(cx).is_ready()
+        {
+            return flush(this.items, this.timer);
+        }
+        Poll::Pending
+    }
+}
+
+pin_project! {
+    pub struct Merge<A, B> {
+        #[pin]
+        a: A,
+        #[pin]
+        b: B,
+        a_done: bool,
+        b_done: bool,
+    }
+}
+
+impl<A: Stream, B: Stream<Item = A::Item>> Stream for Merge<A, B> {
+    type Item = A::Item;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<A::Item>> {
+        let this = self.project();
+        let (mut a, mut b) = (this.a, this.b);
+        // 和 select 一样轮换先 poll 哪一个，一直有值的 stream 不会饿死另一个
+        let start = select_start(2, false);
+        for index in [start, 1 - start] {
+            // 已经结束的 stream 不能再 poll
+            let polled = match index {
+                0 if !*this.a_done => a.as_mut().poll_next(cx),
+                1 if !*this.b_done => b.as_mut().poll_next(cx),
+                _ => continue,
+            };
+            match polled {
+                Poll::Ready(Some(item)) => return Poll::Ready(Some(item)),
+                Poll::Ready(None) if index == 0 => *this.a_done = true,
+                Poll::Ready(None) => *this.b_done = true,
+                Poll::Pending => {}
+            }
+        }
+        if *this.a_done && *this.b_done {
+            Poll::Ready(None)
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+/// 每隔 `period` 产出一次的 stream，产出的值是这一次的预定时刻
+///
+/// 第一次在 `period` 之后。
+pub fn interval(period: Duration) -> Interval {
+    assert!(!period.is_zero(), "period 必须大于 0");
+    Interval {
+        next: now() + period,
+        timer: AsyncTimerFuture::new(period),
+        period,
+    }
+}
+
+/// 定时产出的 stream，复用同一个 `AsyncTimerFuture`
+///
+/// 每次到期后用 `reset` 把定时器移到下一个时刻，而不是创建新的定时器。
+/// 下一个时刻从预定时刻而不是实际被 poll 的时刻算起，所以 poll 得晚也不会累积误差；
+/// 落后了好几个周期时会连续产出，直到追上。
+pub struct Interval {
+    timer: AsyncTimerFuture,
+    next: Instant,
+    period: Duration,
+}
+
+impl Stream for Interval {
+    type Item = Instant;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Instant>> {
+        std::task::ready!(Pin::new(&mut self.timer).poll(cx));
+        let tick = self.next;
+        self.next = tick + self.period;
+        let next = self.next;
+        self.timer.reset(next);
+        Poll::Ready(Some(tick))
+    }
+}
+
+/// 等待 `ms` 毫秒后返回 `ms`
+async fn sleep_then(ms: u64) -> u64 {
+    AsyncTimerFuture::new(Duration::from_millis(ms)).await;
+    ms
+}
+
+/// 测试 Stream 和 StreamExt
+///
+/// 全部由 `SimpleExecutor::block_on` 在虚拟时钟上驱动：
+/// 1. `map` / `filter` / `take`：和迭代器一样
+/// 2. `then`：一个接一个地运行异步函数
+/// 3. `buffer_unordered`：最多同时运行 2 个，按完成的顺序产出
+/// 4. `interval`：按固定周期产出
+/// 5. `chunks_timeout`：攒够数量或者等够时间就产出一批
+/// 6. `merge`：两个 stream 按到达的顺序交错产出
+pub fn test_stream() {
+    println!("\n=== Stream 示例：StreamExt 适配器和 Interval ===");
+    let executor = SimpleExecutor::new_paused();
+
+    // 1. map / filter / take
+    let items: Vec<_> = executor.block_on(
+        iter(1..=10)
+            .map(|x| x * 2)
+            .filter(|x| x % 3 != 0)
+            .take(4)
+            .collect(),
+    );
+    println!("map / filter / take: {:?}", items);
+    assert_eq!(items, vec![2, 4, 8, 10]);
+
+    // 2. then：按顺序运行，总用时是每个的和
+    let start = executor.now();
+    let items: Vec<_> = executor.block_on(async { iter([10, 20, 30]).then(sleep_then).collect().await });
+    assert_eq!(items, vec![10, 20, 30]);
+    assert_eq!(executor.now() - start, Duration::from_millis(60));
+    println!("then: {:?}，用时 {:?}", items, executor.now() - start);
+
+    // 3. buffer_unordered：30 和 10 同时开始，10 完成后开始 25（在 35ms 完成）
+    let start = executor.now();
+    let items: Vec<_> = executor.block_on(async {
+        iter([30, 10, 25]).map(sleep_then).buffer_unordered(2).collect().await
+    });
+    assert_eq!(items, vec![10, 30, 25]);
+    assert_eq!(executor.now() - start, Duration::from_millis(35));
+    println!("buffer_unordered(2): {:?}，用时 {:?}", items, executor.now() - start);
+
+    // 4. interval
+    let start = executor.now();
+    let ticks: Vec<_> = executor.block_on(async { interval(Duration::from_millis(10)).take(5).collect().await });
+    let offsets: Vec<_> = ticks.iter().map(|tick| *tick - start).collect();
+    println!("interval: {:?}", offsets);
+    assert_eq!(offsets, (1..=5).map(|i| Duration::from_millis(10 * i)).collect::<Vec<_>>());
+
+    // 5. chunks_timeout：值在 5、10、15、55、60ms 到达，每批最多 2 个，最多等 20ms
+    let start = executor.now();
+    let chunks: Vec<_> = executor.block_on(async {
+        let mut times = Vec::new();
+        let mut chunks = pin!(iter([5, 5, 5, 40, 5]).then(sleep_then).chunks_timeout(2, Duration::from_millis(20)));
+        while let Some(chunk) = chunks.next().await {
+            times.push(executor.now() - start);
+            println!("chunks_timeout: {:?} 在 {:?} 产出", chunk, executor.now() - start);
+        }
+        times
+    });
+    // 第一批攒满了 2 个；第二批只有 1 个，等了 20ms；第三批又攒满了
+    assert_eq!(chunks, [10, 35, 60].map(Duration::from_millis));
+
+    // 6. merge
+    let items: Vec<_> = executor.block_on(async {
+        let a = iter(["a1", "a2"]).then(|name| async move {
+            AsyncTimerFuture::new(Duration::from_millis(10)).await;
+            name
+        });
+        let b = iter(["b1", "b2"]).then(|name| async move {
+            AsyncTimerFuture::new(Duration::from_millis(15)).await;
+            name
+        });
+        a.merge(b).collect().await
+    });
+    println!("merge: {:?}", items);
+    assert_eq!(items, vec!["a1", "b1", "a2", "b2"]);
+
+    println!("\n关键点：");
+    println!("- Stream 是可以产出多个值的 future：poll_next 返回 Ready(Some) / Ready(None) / Pending");
+    println!("- 适配器只是包一层，poll 外层时才 poll 里面的 stream；跳过值时不能返回 Pending");
+    println!("- Interval 复用一个 AsyncTimerFuture，每次到期后 reset 到下一个时刻");
+}
@@ -0,0 +1,317 @@
+use std::cell::UnsafeCell;
+use std::future::Future;
+use std::pin::Pin;
+use std::ptr::NonNull;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Wake, Waker};
+use std::time::Duration;
+
+use super::super::custom_waker::AsyncTimerFuture;
+use super::super::simple_executor::SimpleExecutor;
+use super::super::waker::CountingWaker;
+use super::intrusive::{WakeList, Waiter, WaiterList};
+
+/// 异步屏障：`parties` 个任务都到达后才一起继续
+///
+/// 最后一个到达的任务唤醒其他所有等待者，并且成为 leader。
+/// 释放之后屏障可以重复使用，开始下一轮。
+///
+/// 等待链表是侵入式的（节点放在 `BarrierWait` future 里），等待不分配内存。
+pub struct Barrier {
+    parties: usize,
+    state: Mutex<BarrierState>,
+}
+
+struct BarrierState {
+    // 这一轮已经到达的数量
+    arrived: usize,
+    // 第几轮；等待者排队时记下当时的值
+    generation: u64,
+    waiters: WaiterList<u64>,
+}
+
+/// `Barrier::wait` 的结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BarrierWaitResult(bool);
+
+impl BarrierWaitResult {
+    /// 每一轮只有最后到达的那个任务是 leader
+    pub fn is_leader(&self) -> bool {
+        self.0
+    }
+}
+
+impl Barrier {
+    pub fn new(parties: usize) -> Self {
+        assert!(parties > 0, "parties 必须大于 0");
+        Self {
+            parties,
+            state: Mutex::new(BarrierState {
+                arrived: 0,
+                generation: 0,
+                waiters: WaiterList::new(),
+            }),
+        }
+    }
+
+    /// 到达屏障并等待其他任务
+    pub fn wait(&self) -> BarrierWait<'_> {
+        BarrierWait {
+            barrier: self,
+            queued: false,
+            waiter: UnsafeCell::new(Waiter::new(0)),
+        }
+    }
+}
+
+/// `Barrier::wait` 返回的 future，`!Unpin`
+pub struct BarrierWait<'a> {
+    barrier: &'a Barrier,
+    queued: bool,
+    waiter: UnsafeCell<Waiter<u64>>,
+}
+
+impl Future for BarrierWait<'_> {
+    type Output = BarrierWaitResult;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<BarrierWaitResult> {
+        let this = unsafe { self.get_unchecked_mut() };
+        let node = unsafe { NonNull::new_unchecked(this.waiter.get()) };
+        let mut state = this.barrier.state.lock().unwrap();
+        if !this.queued {
+            state.arrived += 1;
+            if state.arrived == this.barrier.parties {
+                state.arrived = 0;
+                let generation = state.generation;
+                state.generation += 1;
+                // 和 Notify::notify_waiters 一样分批在锁外唤醒。解锁期间被唤醒的任务可能马上进入
+                // 下一轮，排在这一轮剩下的等待者后面；下一轮满了时，新的 leader 用 <= 比较，
+                // 把排在前面的这一轮的等待者一起放行，这里遇到下一轮的等待者就停下交给它
+                let mut wakers = WakeList::new();
+                loop {
+                    let mut done = true;
+                    while let Some(front) = state.waiters.front_mut()
+                        && front.value <= generation
+                    {
+                        if wakers.is_full() {
+                            done = false;
+                            break;
+                        }
+                        if let Some(waker) = state.waiters.pop_front() {
+                            wakers.push(waker);
+                        }
+                    }
+                    drop(state);
+                    wakers.wake_all();
+                    if done {
+                        return Poll::Ready(BarrierWaitResult(true));
+                    }
+                    state = this.barrier.state.lock().unwrap();
+                }
+            }
+            unsafe {
+                (*node.as_ptr()).value = state.generation;
+                state.waiters.push_back(node, cx.waker());
+            }
+            this.queued = true;
+            return Poll::Pending;
+        }
+        let waiter = unsafe { &mut *node.as_ptr() };
+        if waiter.is_linked() {
+            waiter.set_waker(cx.waker());
+            return Poll::Pending;
+        }
+        this.queued = false;
+        Poll::Ready(BarrierWaitResult(false))
+    }
+}
+
+impl Drop for BarrierWait<'_> {
+    fn drop(&mut self) {
+        if !self.queued {
+            return;
+        }
+        let node = unsafe { NonNull::new_unchecked(self.waiter.get()) };
+        let mut state = self.barrier.state.lock().unwrap();
+        // 还没放行就被取消：撤回这次到达，否则这一轮会少等一个任务。
+        // 已经放行、只是还没被取出的等待者属于上一轮，不影响这一轮的计数
+        if unsafe { state.waiters.remove(node) } && self.waiter.get_mut().value == state.generation {
+            state.arrived -= 1;
+        }
+    }
+}
+
+/// 分两个阶段工作，每个阶段结束时在屏障上等待；返回是 leader 的次数
+async fn phased_worker(barrier: Arc<Barrier>, id: u64, log: Arc<Mutex<Vec<String>>>) -> usize {
+    let mut leader = 0;
+    for phase in 1..=2 {
+        // 完成时间不同：id 越大越慢
+        AsyncTimerFuture::new(Duration::from_millis(5 * (id + 1))).await;
+        log.lock().unwrap().push(format!("phase{phase}-done"));
+        if barrier.wait().await.is_leader() {
+            leader += 1;
+        }
+        log.lock().unwrap().push(format!("phase{phase}-released"));
+    }
+    leader
+}
+
+/// 检查每个阶段都是所有任务完成后才放行
+fn check_phases(log: &[String], parties: usize) {
+    for (index, entry) in log.iter().enumerate() {
+        let expected = match index / parties {
+            0 => "phase1-done",
+            1 => "phase1-released",
+            2 => "phase2-done",
+            _ => "phase2-released",
+        };
+        assert_eq!(entry, expected);
+    }
+    assert_eq!(log.len(), parties * 4);
+}
+
+/// 被唤醒时立刻在同一个屏障上再等待 `again` 次，模拟被唤醒的任务马上进入下一轮
+struct Rewait {
+    barrier: &'static Barrier,
+    again: Mutex<usize>,
+    // 下一轮的等待使用的 waker
+    next: Waker,
+    waits: Arc<Mutex<Vec<Pin<Box<BarrierWait<'static>>>>>>,
+    leaders: Arc<AtomicUsize>,
+}
+
+impl Wake for Rewait {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        let again = std::mem::take(&mut *self.again.lock().unwrap());
+        for _ in 0..again {
+            let mut wait = Box::pin(self.barrier.wait());
+            match wait.as_mut().poll(&mut Context::from_waker(&self.next)) {
+                Poll::Ready(result) => {
+                    assert!(result.is_leader());
+                    self.leaders.fetch_add(1, Ordering::Relaxed);
+                }
+                Poll::Pending => self.waits.lock().unwrap().push(wait),
+            }
+        }
+    }
+}
+
+/// 测试 Barrier
+///
+/// 1. SimpleExecutor 和 tokio：3 个速度不同的任务分两个阶段工作，
+///    每个阶段都要等最慢的任务完成后才一起进入下一阶段，每一轮只有一个 leader
+/// 2. 取消：等待中的任务被取消后撤回到达，这一轮仍然要等齐 `parties` 个任务
+/// 3. 超过一批（32 个）的等待者，被唤醒后立刻进入下一轮：
+///    下一轮在上一轮还没放行完时就满了，两轮的等待者都要被唤醒
+pub fn test_barrier() {
+    println!("\n=== Barrier 示例：等待所有任务到达 ===");
+
+    // 1. SimpleExecutor（虚拟时钟）
+    let executor = SimpleExecutor::new_paused();
+    let barrier = Arc::new(Barrier::new(3));
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let handles: Vec<_> = (0..3)
+        .map(|id| executor.spawn(phased_worker(barrier.clone(), id, log.clone())))
+        .collect();
+    let leaders = executor.block_on(async {
+        let mut leaders = 0;
+        for handle in handles {
+            leaders += handle.await.unwrap();
+        }
+        leaders
+    });
+    println!("SimpleExecutor 上的执行顺序: {:?}", log.lock().unwrap());
+    check_phases(&log.lock().unwrap(), 3);
+    assert_eq!(leaders, 2);
+
+    // tokio
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let leaders = runtime.block_on(async {
+        let barrier = Arc::new(Barrier::new(3));
+        let handles: Vec<_> = (0..3)
+            .map(|id| tokio::spawn(phased_worker(barrier.clone(), id, log.clone())))
+            .collect();
+        let mut leaders = 0;
+        for handle in handles {
+            leaders += handle.await.unwrap();
+        }
+        leaders
+    });
+    check_phases(&log.lock().unwrap(), 3);
+    assert_eq!(leaders, 2);
+    println!("SimpleExecutor 和 tokio 上每个阶段都等齐了 3 个任务，每轮一个 leader");
+
+    // 2. 取消
+    let barrier = Barrier::new(2);
+    let counter = CountingWaker::new();
+    let waker = Waker::from(counter.clone());
+    let mut cx = Context::from_waker(&waker);
+
+    let mut first = Box::pin(barrier.wait());
+    assert!(first.as_mut().poll(&mut cx).is_pending());
+    drop(first);
+    // first 撤回了到达，second 仍然要等
+    let mut second = Box::pin(barrier.wait());
+    assert!(second.as_mut().poll(&mut cx).is_pending());
+    let mut third = Box::pin(barrier.wait());
+    assert_eq!(third.as_mut().poll(&mut cx), Poll::Ready(BarrierWaitResult(true)));
+    assert_eq!(counter.wakes(), 1);
+    assert_eq!(second.as_mut().poll(&mut cx), Poll::Ready(BarrierWaitResult(false)));
+    println!("取消的任务撤回了到达，屏障仍然等齐 2 个任务才放行");
+
+    // 3. 34 个参与者：第 0 轮的 33 个等待者分两批唤醒。第一批的 32 个被唤醒时马上再等待，
+    //    其中一个等待 3 次，下一轮在 leader 解锁唤醒第一批时就满了，
+    //    而第 0 轮还剩一个等待者排在下一轮的前面
+    let barrier: &'static Barrier = Box::leak(Box::new(Barrier::new(34)));
+    let counter = CountingWaker::new();
+    let next = Waker::from(counter.clone());
+    let waits = Arc::new(Mutex::new(Vec::new()));
+    let leaders = Arc::new(AtomicUsize::new(0));
+    let mut first_round: Vec<_> = (0..33)
+        .map(|index| {
+            let again = match index {
+                0..31 => 1,
+                31 => 3,
+                _ => 0,
+            };
+            let waker = Waker::from(Arc::new(Rewait {
+                barrier,
+                again: Mutex::new(again),
+                next: next.clone(),
+                waits: waits.clone(),
+                leaders: leaders.clone(),
+            }));
+            let mut wait = Box::pin(barrier.wait());
+            assert!(wait.as_mut().poll(&mut Context::from_waker(&waker)).is_pending());
+            (wait, waker)
+        })
+        .collect();
+    let mut leader = Box::pin(barrier.wait());
+    assert_eq!(leader.as_mut().poll(&mut cx), Poll::Ready(BarrierWaitResult(true)));
+    for (wait, waker) in &mut first_round {
+        let mut cx = Context::from_waker(waker);
+        assert_eq!(wait.as_mut().poll(&mut cx), Poll::Ready(BarrierWaitResult(false)));
+    }
+    // 第 1 轮：33 个等待者和一个 leader，等待者都被唤醒了
+    assert_eq!(leaders.load(Ordering::Relaxed), 1);
+    assert_eq!(counter.wakes(), 33);
+    let mut waits = std::mem::take(&mut *waits.lock().unwrap());
+    assert_eq!(waits.len(), 33);
+    for wait in &mut waits {
+        assert_eq!(wait.as_mut().poll(&mut cx), Poll::Ready(BarrierWaitResult(false)));
+    }
+    println!("34 个参与者被唤醒后立刻进入下一轮：两轮的等待者都被放行");
+
+    println!("\n关键点：");
+    println!("- 最后一个到达的任务唤醒所有等待者，并成为 leader");
+    println!("- 放行后计数清零，屏障可以重复使用");
+    println!("- 等待中被取消要撤回到达，否则这一轮会提前放行");
+}
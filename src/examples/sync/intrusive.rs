@@ -0,0 +1,174 @@
+use std::marker::PhantomPinned;
+use std::ptr::NonNull;
+use std::task::Waker;
+
+/// 侵入式等待链表的节点，直接放在等待的 future 里面
+///
+/// `WaiterQueue` 每次排队都要把 waker 放进 `VecDeque`；这里节点就是 future 的一个字段，
+/// 链表只保存指向它的指针，所以等待本身不需要分配内存。
+///
+/// 代价是节点在链表中时不能移动：持有节点的 future 必须是 `!Unpin`（`PhantomPinned`），
+/// 并且在 drop 时先把自己从链表中移除。
+pub(crate) struct Waiter<T> {
+    prev: Option<NonNull<Waiter<T>>>,
+    next: Option<NonNull<Waiter<T>>>,
+    linked: bool,
+    waker: Option<Waker>,
+    pub(crate) value: T,
+    _pinned: PhantomPinned,
+}
+
+// 节点里的指针只在持有外层 Mutex 时访问
+unsafe impl<T: Send> Send for Waiter<T> {}
+
+impl<T> Waiter<T> {
+    pub(crate) fn new(value: T) -> Self {
+        Self {
+            prev: None,
+            next: None,
+            linked: false,
+            waker: None,
+            value,
+            _pinned: PhantomPinned,
+        }
+    }
+
+    /// 还在链表中；被唤醒方取出后变为 `false`
+    pub(crate) fn is_linked(&self) -> bool {
+        self.linked
+    }
+
+    /// 和 AsyncTimerFuture 一样：future 可能在不同的任务间移动，需要更新 waker
+    pub(crate) fn set_waker(&mut self, waker: &Waker) {
+        match &self.waker {
+            Some(old) if old.will_wake(waker) => {}
+            _ => self.waker = Some(waker.clone()),
+        }
+    }
+}
+
+/// 先来后到的侵入式双向链表
+///
+/// 只能放在 `Mutex` 里使用：所有操作（包括 future 读写自己的节点）都必须持有同一把锁。
+pub(crate) struct WaiterList<T> {
+    head: Option<NonNull<Waiter<T>>>,
+    tail: Option<NonNull<Waiter<T>>>,
+}
+
+unsafe impl<T: Send> Send for WaiterList<T> {}
+
+impl<T> WaiterList<T> {
+    pub(crate) fn new() -> Self {
+        Self {
+            head: None,
+            tail: None,
+        }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.head.is_none()
+    }
+
+    /// 保存 waker 并排到队尾
+    ///
+    /// # Safety
+    ///
+    /// `node` 必须指向一个已经 pin 住、还不在任何链表中的节点，
+    /// 并且节点被释放之前一定会从链表中移除（`remove` 或 `pop_front`）。
+    pub(crate) unsafe fn push_back(&mut self, mut node: NonNull<Waiter<T>>, waker: &Waker) {
+        let waiter = unsafe { node.as_mut() };
+        debug_assert!(!waiter.linked);
+        waiter.waker = Some(waker.clone());
+        waiter.prev = self.tail;
+        waiter.next = None;
+        waiter.linked = true;
+        match self.tail {
+            Some(mut tail) => unsafe { tail.as_mut().next = Some(node) },
+            None => self.head = Some(node),
+        }
+        self.tail = Some(node);
+    }
+
+    /// 从链表中移除；已经不在链表中（被取出过）时返回 `false`
+    ///
+    /// # Safety
+    ///
+    /// `node` 必须有效，并且如果在链表中，就是在这个链表中。
+    pub(crate) unsafe fn remove(&mut self, mut node: NonNull<Waiter<T>>) -> bool {
+        let waiter = unsafe { node.as_mut() };
+        if !waiter.linked {
+            return false;
+        }
+        match waiter.prev {
+            Some(mut prev) => unsafe { prev.as_mut().next = waiter.next },
+            None => self.head = waiter.next,
+        }
+        match waiter.next {
+            Some(mut next) => unsafe { next.as_mut().prev = waiter.prev },
+            None => self.tail = waiter.prev,
+        }
+        waiter.prev = None;
+        waiter.next = None;
+        waiter.linked = false;
+        true
+    }
+
+    /// 队首的节点；持有锁期间可以读写它的 `value`
+    pub(crate) fn front_mut(&mut self) -> Option<&mut Waiter<T>> {
+        // 链表中的节点都是有效的（push_back 的约定）
+        self.head.map(|mut node| unsafe { node.as_mut() })
+    }
+
+    /// 取出队首并返回它的 waker
+    ///
+    /// 取出后节点的 `is_linked()` 为 `false`，等待者下次被 poll 时据此知道轮到了自己。
+    pub(crate) fn pop_front(&mut self) -> Option<Waker> {
+        let mut node = self.head?;
+        unsafe {
+            self.remove(node);
+            node.as_mut().waker.take()
+        }
+    }
+}
+
+/// 一批最多唤醒多少个 waker
+const WAKE_BATCH: usize = 32;
+
+/// 放在栈上的一批 waker，解锁之后再唤醒
+///
+/// waker 可能来自任意 executor：调用或 drop 它时可能 drop 掉一个任务，
+/// 而那个任务里的 `Notified`/`BarrierWait` 在 Drop 中会去锁同一把（不可重入的）Mutex。
+/// 所以不能在锁内唤醒：持有锁时取出一批，解锁后唤醒，再加锁继续。固定大小，不分配内存。
+pub(crate) struct WakeList {
+    wakers: [Option<Waker>; WAKE_BATCH],
+    len: usize,
+}
+
+impl WakeList {
+    pub(crate) fn new() -> Self {
+        Self {
+            wakers: [const { None }; WAKE_BATCH],
+            len: 0,
+        }
+    }
+
+    pub(crate) fn is_full(&self) -> bool {
+        self.len == WAKE_BATCH
+    }
+
+    pub(crate) fn push(&mut self, waker: Waker) {
+        assert!(!self.is_full(), "WakeList 已满");
+        self.wakers[self.len] = Some(waker);
+        self.len += 1;
+    }
+
+    /// 唤醒并清空；必须在锁外调用
+    pub(crate) fn wake_all(&mut self) {
+        for waker in &mut self.wakers[..self.len] {
+            if let Some(waker) = waker.take() {
+                waker.wake();
+            }
+        }
+        self.len = 0;
+    }
+}
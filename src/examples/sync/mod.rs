@@ -0,0 +1,7 @@
+pub mod barrier;
+pub(crate) mod intrusive;
+pub mod mutex;
+pub mod notify;
+pub mod rwlock;
+pub mod semaphore;
+pub(crate) mod waiters;
@@ -0,0 +1,258 @@
+use std::cell::UnsafeCell;
+use std::future::Future;
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::time::Duration;
+
+use super::super::custom_waker::AsyncTimerFuture;
+use super::super::simple_executor::SimpleExecutor;
+use super::super::waker::CountingWaker;
+use super::waiters::WaiterQueue;
+
+/// 异步互斥锁：拿不到锁时保存 waker 并返回 `Pending`，不阻塞线程
+///
+/// # 公平性
+///
+/// 等待者按先来后到排队。释放锁时，如果有人在等，锁不会被放开，
+/// 而是直接交给队首的等待者（handoff），再调用它的 waker。
+/// 这样后来的 `lock()` 不会插队，队首的等待者也不会在被唤醒后又抢不到锁。
+///
+/// # 取消
+///
+/// `lock()` 返回的 future 被 drop 时：
+/// - 还在排队：从队列中移除
+/// - 锁已经交给了它，但它还没来得及被 poll：把锁转交给下一个等待者，
+///   否则后面的等待者永远不会被唤醒（丢失唤醒）
+///
+/// 只依赖 `Waker`，所以在 tokio 和 `SimpleExecutor` 上都能使用。
+pub struct AsyncMutex<T: ?Sized> {
+    state: Mutex<MutexState>,
+    value: UnsafeCell<T>,
+}
+
+struct MutexState {
+    // 有等待者时一定是 true：释放时锁直接转交，不会出现"没锁但有人在等"
+    locked: bool,
+    waiters: WaiterQueue<()>,
+}
+
+// 和 std::sync::Mutex 一样：同一时刻只有一个持有者能访问 value
+unsafe impl<T: ?Sized + Send> Send for AsyncMutex<T> {}
+unsafe impl<T: ?Sized + Send> Sync for AsyncMutex<T> {}
+
+impl<T> AsyncMutex<T> {
+    pub fn new(value: T) -> Self {
+        Self {
+            state: Mutex::new(MutexState {
+                locked: false,
+                waiters: WaiterQueue::new(),
+            }),
+            value: UnsafeCell::new(value),
+        }
+    }
+}
+
+impl<T: ?Sized> AsyncMutex<T> {
+    /// 获取锁
+    pub fn lock(&self) -> Lock<'_, T> {
+        Lock {
+            mutex: self,
+            waiter: None,
+        }
+    }
+
+    /// 不等待：锁空闲且没有人排队时才能拿到
+    pub fn try_lock(&self) -> Option<AsyncMutexGuard<'_, T>> {
+        let mut state = self.state.lock().unwrap();
+        if state.locked {
+            return None;
+        }
+        state.locked = true;
+        Some(AsyncMutexGuard::new(self))
+    }
+
+    /// 释放锁：有等待者时直接转交给队首
+    fn unlock(&self) {
+        let mut state = self.state.lock().unwrap();
+        match state.waiters.pop_front() {
+            Some(waiter) => {
+                drop(state);
+                waiter.waker.wake();
+            }
+            None => state.locked = false,
+        }
+    }
+}
+
+/// `AsyncMutex::lock` 返回的 future
+pub struct Lock<'a, T: ?Sized> {
+    mutex: &'a AsyncMutex<T>,
+    // 排队后的编号；不在队列中说明锁已经转交给了这个 future
+    waiter: Option<u64>,
+}
+
+impl<'a, T: ?Sized> Future for Lock<'a, T> {
+    type Output = AsyncMutexGuard<'a, T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let mut state = this.mutex.state.lock().unwrap();
+        match this.waiter {
+            None => {
+                if !state.locked {
+                    state.locked = true;
+                    return Poll::Ready(AsyncMutexGuard::new(this.mutex));
+                }
+                this.waiter = Some(state.waiters.push((), cx.waker()));
+                Poll::Pending
+            }
+            Some(id) => {
+                if state.waiters.update(id, cx.waker()) {
+                    return Poll::Pending;
+                }
+                this.waiter = None;
+                Poll::Ready(AsyncMutexGuard::new(this.mutex))
+            }
+        }
+    }
+}
+
+impl<T: ?Sized> Drop for Lock<'_, T> {
+    fn drop(&mut self) {
+        let Some(id) = self.waiter else {
+            return;
+        };
+        let removed = self.mutex.state.lock().unwrap().waiters.remove(id);
+        if !removed {
+            // 锁已经交给了我们，但没有人会用它了：转交给下一个等待者
+            self.mutex.unlock();
+        }
+    }
+}
+
+/// 持有锁期间可以访问数据，drop 时释放锁
+pub struct AsyncMutexGuard<'a, T: ?Sized> {
+    mutex: &'a AsyncMutex<T>,
+    // 和 &mut T 一样：T: Sync 时才能在线程间共享 guard
+    _marker: PhantomData<&'a mut T>,
+}
+
+impl<'a, T: ?Sized> AsyncMutexGuard<'a, T> {
+    fn new(mutex: &'a AsyncMutex<T>) -> Self {
+        Self {
+            mutex,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T: ?Sized> Deref for AsyncMutexGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.mutex.value.get() }
+    }
+}
+
+impl<T: ?Sized> DerefMut for AsyncMutexGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.mutex.value.get() }
+    }
+}
+
+impl<T: ?Sized> Drop for AsyncMutexGuard<'_, T> {
+    fn drop(&mut self) {
+        self.mutex.unlock();
+    }
+}
+
+/// 持有锁跨过 await，记录拿到锁的顺序
+async fn record_in_order(mutex: Arc<AsyncMutex<Vec<usize>>>, id: usize) {
+    let mut order = mutex.lock().await;
+    order.push(id);
+    AsyncTimerFuture::new(Duration::from_millis(10)).await;
+}
+
+/// 测试 AsyncMutex
+///
+/// 1. SimpleExecutor：五个任务持有锁跨过定时器，按排队顺序拿到锁
+/// 2. tokio：同样的代码运行在 tokio 的 current_thread 运行时上
+/// 3. 取消：锁转交给一个 future 后它被 drop，锁继续转交给下一个等待者
+pub fn test_async_mutex() {
+    println!("\n=== AsyncMutex 示例：公平的异步互斥锁 ===");
+
+    // 1. SimpleExecutor（虚拟时钟）
+    let executor = SimpleExecutor::new_paused();
+    let start = executor.now();
+    let mutex = Arc::new(AsyncMutex::new(Vec::new()));
+    let handles: Vec<_> = (0..5)
+        .map(|id| executor.spawn(record_in_order(mutex.clone(), id)))
+        .collect();
+    executor.block_on(async {
+        for handle in handles {
+            handle.await.unwrap();
+        }
+    });
+    let order = mutex.try_lock().unwrap().clone();
+    println!("SimpleExecutor 上拿到锁的顺序: {:?}", order);
+    assert_eq!(order, vec![0, 1, 2, 3, 4]);
+    // 每个任务持有锁 10ms，互斥执行
+    assert_eq!(executor.now() - start, Duration::from_millis(50));
+
+    // 2. tokio
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    let mutex = Arc::new(AsyncMutex::new(Vec::new()));
+    runtime.block_on(async {
+        let handles: Vec<_> = (0..5)
+            .map(|id| tokio::spawn(record_in_order(mutex.clone(), id)))
+            .collect();
+        for handle in handles {
+            handle.await.unwrap();
+        }
+    });
+    let order = mutex.try_lock().unwrap().clone();
+    println!("tokio 上拿到锁的顺序: {:?}", order);
+    assert_eq!(order, vec![0, 1, 2, 3, 4]);
+
+    // 3. 取消
+    let mutex = AsyncMutex::new(0);
+    let (counter_a, counter_b) = (CountingWaker::new(), CountingWaker::new());
+    let waker_a = Waker::from(counter_a.clone());
+    let waker_b = Waker::from(counter_b.clone());
+
+    let guard = mutex.try_lock().unwrap();
+    let mut a = mutex.lock();
+    let mut b = mutex.lock();
+    assert!(Pin::new(&mut a).poll(&mut Context::from_waker(&waker_a)).is_pending());
+    assert!(Pin::new(&mut b).poll(&mut Context::from_waker(&waker_b)).is_pending());
+
+    // 锁转交给 a，然后 a 在被 poll 之前被取消
+    drop(guard);
+    assert_eq!(counter_a.wakes(), 1);
+    drop(a);
+    // 锁继续转交给 b
+    assert_eq!(counter_b.wakes(), 1);
+    let Poll::Ready(mut guard) = Pin::new(&mut b).poll(&mut Context::from_waker(&waker_b)) else {
+        panic!("锁应该已经转交给 b");
+    };
+    *guard += 1;
+
+    // 还在排队时被取消：直接从队列中移除，不影响释放
+    let mut c = mutex.lock();
+    assert!(Pin::new(&mut c).poll(&mut Context::from_waker(&waker_a)).is_pending());
+    drop(c);
+    drop(guard);
+    drop(b);
+    assert_eq!(*mutex.try_lock().unwrap(), 1);
+    println!("取消后锁被正确转交，没有丢失唤醒");
+
+    println!("\n关键点：");
+    println!("- 拿不到锁时保存 waker 排队，释放时直接把锁交给队首并唤醒它");
+    println!("- 被取消的等待者如果已经拿到锁，要把锁继续交给下一个");
+    println!("- 只依赖 Waker，tokio 和 SimpleExecutor 都能运行");
+}
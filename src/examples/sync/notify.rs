@@ -0,0 +1,299 @@
+use std::cell::UnsafeCell;
+use std::future::Future;
+use std::pin::Pin;
+use std::ptr::NonNull;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Wake, Waker};
+use std::time::Duration;
+
+use super::super::custom_waker::AsyncTimerFuture;
+use super::super::simple_executor::SimpleExecutor;
+use super::super::waker::CountingWaker;
+use super::intrusive::{WakeList, Waiter, WaiterList};
+
+/// 没有数据的异步通知：一方等待，另一方通知
+///
+/// - `notify_one`：唤醒最早等待的一个；没有人在等时保存一个许可，下一次 `notified()` 立刻完成，
+///   所以先通知后等待也不会丢失唤醒
+/// - `notify_waiters`：唤醒当前所有等待者，不保存许可
+///
+/// 等待链表是侵入式的（节点放在 `Notified` future 里），等待不分配内存。
+pub struct Notify {
+    state: Mutex<NotifyState>,
+}
+
+struct NotifyState {
+    permit: bool,
+    // 每次 notify_waiters 加一；等待者排队时记下当时的值
+    generation: u64,
+    waiters: WaiterList<NotifyWaiter>,
+}
+
+/// 链表节点里保存的值
+struct NotifyWaiter {
+    notification: Notification,
+    generation: u64,
+}
+
+/// 等待者是被哪种方式唤醒的
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Notification {
+    Waiting,
+    One,
+    All,
+}
+
+impl NotifyState {
+    fn notify_one(&mut self) -> Option<Waker> {
+        match self.waiters.front_mut() {
+            Some(front) => {
+                front.value.notification = Notification::One;
+                self.waiters.pop_front()
+            }
+            None => {
+                self.permit = true;
+                None
+            }
+        }
+    }
+}
+
+impl Default for Notify {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Notify {
+    pub fn new() -> Self {
+        Self {
+            state: Mutex::new(NotifyState {
+                permit: false,
+                generation: 0,
+                waiters: WaiterList::new(),
+            }),
+        }
+    }
+
+    /// 唤醒一个等待者，没有人在等时保存许可
+    pub fn notify_one(&self) {
+        let waker = self.state.lock().unwrap().notify_one();
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+
+    /// 唤醒当前所有等待者
+    pub fn notify_waiters(&self) {
+        let mut wakers = WakeList::new();
+        let mut state = self.state.lock().unwrap();
+        // 分批在锁外唤醒，中途会解锁：之后才开始等待的 future 记下的是新的 generation，
+        // 排在这一批的后面，不会被唤醒
+        state.generation += 1;
+        let generation = state.generation;
+        loop {
+            let mut done = true;
+            while let Some(front) = state.waiters.front_mut()
+                && front.value.generation < generation
+            {
+                if wakers.is_full() {
+                    done = false;
+                    break;
+                }
+                front.value.notification = Notification::All;
+                if let Some(waker) = state.waiters.pop_front() {
+                    wakers.push(waker);
+                }
+            }
+            drop(state);
+            wakers.wake_all();
+            if done {
+                return;
+            }
+            state = self.state.lock().unwrap();
+        }
+    }
+
+    /// 等待通知
+    pub fn notified(&self) -> Notified<'_> {
+        Notified {
+            notify: self,
+            queued: false,
+            waiter: UnsafeCell::new(Waiter::new(NotifyWaiter {
+                notification: Notification::Waiting,
+                generation: 0,
+            })),
+        }
+    }
+}
+
+/// `Notify::notified` 返回的 future，`!Unpin`
+pub struct Notified<'a> {
+    notify: &'a Notify,
+    queued: bool,
+    waiter: UnsafeCell<Waiter<NotifyWaiter>>,
+}
+
+impl Future for Notified<'_> {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let this = unsafe { self.get_unchecked_mut() };
+        let node = unsafe { NonNull::new_unchecked(this.waiter.get()) };
+        let mut state = this.notify.state.lock().unwrap();
+        if !this.queued {
+            if state.permit {
+                state.permit = false;
+                return Poll::Ready(());
+            }
+            unsafe {
+                (*node.as_ptr()).value.generation = state.generation;
+                state.waiters.push_back(node, cx.waker());
+            }
+            this.queued = true;
+            return Poll::Pending;
+        }
+        let waiter = unsafe { &mut *node.as_ptr() };
+        if waiter.is_linked() {
+            waiter.set_waker(cx.waker());
+            return Poll::Pending;
+        }
+        this.queued = false;
+        Poll::Ready(())
+    }
+}
+
+impl Drop for Notified<'_> {
+    fn drop(&mut self) {
+        if !self.queued {
+            return;
+        }
+        let node = unsafe { NonNull::new_unchecked(self.waiter.get()) };
+        let waker = {
+            let mut state = self.notify.state.lock().unwrap();
+            if unsafe { state.waiters.remove(node) } {
+                return;
+            }
+            // notify_one 选中了我们，但我们不会再等了：转交给下一个，否则这次通知就丢了
+            if self.waiter.get_mut().value.notification != Notification::One {
+                return;
+            }
+            state.notify_one()
+        };
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+/// 被唤醒时 drop 掉同一个 `Notify` 上的另一个等待者，就像 executor 唤醒时 drop 了一个任务
+struct DropOnWake(Mutex<Option<Pin<Box<Notified<'static>>>>>);
+
+impl Wake for DropOnWake {
+    fn wake(self: Arc<Self>) {
+        self.0.lock().unwrap().take();
+    }
+}
+
+/// 等待 `count` 次通知，每次通知后记录
+async fn consumer(notify: Arc<Notify>, count: usize) -> usize {
+    let mut received = 0;
+    for _ in 0..count {
+        notify.notified().await;
+        received += 1;
+    }
+    received
+}
+
+/// 测试 Notify
+///
+/// 1. 先通知后等待：许可被保存下来，不会丢失唤醒
+/// 2. SimpleExecutor 和 tokio：生产者每 10ms `notify_one` 一次，消费者收到每一次通知
+/// 3. `notify_waiters` 唤醒所有当前的等待者；被 `notify_one` 选中后取消的等待者把通知转交给下一个
+/// 4. waker 在锁外调用：唤醒时 drop 同一个 `Notify` 上的等待者不会死锁
+pub fn test_notify() {
+    println!("\n=== Notify 示例：没有数据的异步通知 ===");
+
+    // 1. 先通知后等待
+    let notify = Notify::new();
+    notify.notify_one();
+    SimpleExecutor::new().block_on(notify.notified());
+    println!("先 notify_one 后 notified()，立刻完成");
+
+    // 2. SimpleExecutor（虚拟时钟）和 tokio
+    let executor = SimpleExecutor::new_paused();
+    let notify = Arc::new(Notify::new());
+    let received = executor.spawn(consumer(notify.clone(), 3));
+    let received = executor.block_on(async {
+        for _ in 0..3 {
+            AsyncTimerFuture::new(Duration::from_millis(10)).await;
+            notify.notify_one();
+        }
+        received.await.unwrap()
+    });
+    assert_eq!(received, 3);
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    let received = runtime.block_on(async {
+        let notify = Arc::new(Notify::new());
+        let received = tokio::spawn(consumer(notify.clone(), 3));
+        for _ in 0..3 {
+            AsyncTimerFuture::new(Duration::from_millis(10)).await;
+            notify.notify_one();
+        }
+        received.await.unwrap()
+    });
+    assert_eq!(received, 3);
+    println!("SimpleExecutor 和 tokio 上的消费者都收到了 3 次通知");
+
+    // 3. notify_waiters 和取消
+    let notify = Notify::new();
+    let counter = CountingWaker::new();
+    let waker = Waker::from(counter.clone());
+    let mut cx = Context::from_waker(&waker);
+
+    // 比一批（32 个）多：分几次解锁唤醒
+    let mut waiters: Vec<_> = (0..40).map(|_| Box::pin(notify.notified())).collect();
+    for waiter in &mut waiters {
+        assert!(waiter.as_mut().poll(&mut cx).is_pending());
+    }
+    notify.notify_waiters();
+    assert_eq!(counter.wakes(), 40);
+    for waiter in &mut waiters {
+        assert!(waiter.as_mut().poll(&mut cx).is_ready());
+    }
+    // notify_waiters 不保存许可
+    let mut late = Box::pin(notify.notified());
+    assert!(late.as_mut().poll(&mut cx).is_pending());
+
+    let mut next = Box::pin(notify.notified());
+    assert!(next.as_mut().poll(&mut cx).is_pending());
+    notify.notify_one();
+    assert_eq!(counter.wakes(), 41);
+    // late 被选中，但在被 poll 之前取消了：通知转交给 next
+    drop(late);
+    assert_eq!(counter.wakes(), 42);
+    assert!(next.as_mut().poll(&mut cx).is_ready());
+    println!("notify_waiters 唤醒了所有等待者，取消的等待者把通知转交给了下一个");
+
+    // 4. 唤醒 first 时 drop 掉 second，second 的 Drop 要加锁；在锁内唤醒的话这里会死锁。
+    // DropOnWake 要放进 Waker，需要 'static，所以这个 Notify 故意泄漏
+    let notify: &'static Notify = Box::leak(Box::new(Notify::new()));
+    let mut second = Box::pin(notify.notified());
+    assert!(second.as_mut().poll(&mut cx).is_pending());
+    let dropper = Arc::new(DropOnWake(Mutex::new(Some(second))));
+    let mut first = Box::pin(notify.notified());
+    assert!(first.as_mut().poll(&mut Context::from_waker(&Waker::from(dropper.clone()))).is_pending());
+    notify.notify_waiters();
+    assert!(dropper.0.lock().unwrap().is_none());
+    assert!(first.as_mut().poll(&mut cx).is_ready());
+    println!("唤醒时 drop 同一个 Notify 上的等待者，没有死锁");
+
+    println!("\n关键点：");
+    println!("- notify_one 没有等待者时保存许可，先通知后等待不会丢失唤醒");
+    println!("- 等待节点放在 future 里，链表只保存指针，等待不分配内存");
+    println!("- 被选中后取消的等待者要把通知转交出去");
+    println!("- waker 分批在锁外调用：唤醒可能 drop 掉另一个要加同一把锁的等待者");
+}
@@ -0,0 +1,416 @@
+use std::cell::UnsafeCell;
+use std::future::Future;
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker, ready};
+use std::time::Duration;
+
+use super::super::custom_waker::AsyncTimerFuture;
+use super::super::simple_executor::SimpleExecutor;
+use super::super::waker::CountingWaker;
+use super::waiters::WaiterQueue;
+
+/// 等待者要的是读锁还是写锁
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Access {
+    Read,
+    Write,
+}
+
+/// 异步读写锁：多个读者可以同时持有，写者独占
+///
+/// # 公平性
+///
+/// 和 `AsyncMutex` 一样按先来后到排队，释放时直接把锁转交给队首：
+/// - 队首是写者：等所有读者释放后交给它
+/// - 队首是读者：连同后面连续的读者一起放行，遇到写者为止
+///
+/// 有写者在排队时，新来的读者也要排在它后面，所以写者不会被源源不断的读者饿死。
+///
+/// # 取消
+///
+/// 被 drop 的等待者如果已经拿到锁，就把锁释放掉再转交；
+/// 如果还在排队，移除它之后重新检查队首（它可能是挡住后面读者的写者）。
+pub struct AsyncRwLock<T: ?Sized> {
+    state: Mutex<RwState>,
+    value: UnsafeCell<T>,
+}
+
+struct RwState {
+    readers: usize,
+    writer: bool,
+    waiters: WaiterQueue<Access>,
+}
+
+impl RwState {
+    /// 把锁转交给队首可以放行的等待者，返回要唤醒的 waker
+    ///
+    /// waker 在释放状态锁之后再调用。
+    fn grant(&mut self) -> Vec<Waker> {
+        let mut wakers = Vec::new();
+        while let Some(waiter) = self.waiters.front() {
+            match waiter.kind {
+                Access::Write if self.readers == 0 && !self.writer => {
+                    self.writer = true;
+                    wakers.push(self.waiters.pop_front().unwrap().waker);
+                    break;
+                }
+                Access::Read if !self.writer => {
+                    self.readers += 1;
+                    wakers.push(self.waiters.pop_front().unwrap().waker);
+                }
+                _ => break,
+            }
+        }
+        wakers
+    }
+
+    /// 有人排队时不插队
+    fn try_acquire(&mut self, access: Access) -> bool {
+        let available = self.waiters.is_empty()
+            && !self.writer
+            && (access == Access::Read || self.readers == 0);
+        if available {
+            match access {
+                Access::Read => self.readers += 1,
+                Access::Write => self.writer = true,
+            }
+        }
+        available
+    }
+
+    fn release(&mut self, access: Access) -> Vec<Waker> {
+        match access {
+            Access::Read => self.readers -= 1,
+            Access::Write => self.writer = false,
+        }
+        self.grant()
+    }
+}
+
+// 和 std::sync::RwLock 一样：读者之间会共享 &T，所以要求 T: Sync
+unsafe impl<T: ?Sized + Send> Send for AsyncRwLock<T> {}
+unsafe impl<T: ?Sized + Send + Sync> Sync for AsyncRwLock<T> {}
+
+impl<T> AsyncRwLock<T> {
+    pub fn new(value: T) -> Self {
+        Self {
+            state: Mutex::new(RwState {
+                readers: 0,
+                writer: false,
+                waiters: WaiterQueue::new(),
+            }),
+            value: UnsafeCell::new(value),
+        }
+    }
+}
+
+impl<T: ?Sized> AsyncRwLock<T> {
+    /// 获取读锁
+    pub fn read(&self) -> Read<'_, T> {
+        Read {
+            lock: self,
+            waiter: None,
+        }
+    }
+
+    /// 获取写锁
+    pub fn write(&self) -> Write<'_, T> {
+        Write {
+            lock: self,
+            waiter: None,
+        }
+    }
+
+    /// 不等待：没有写者持有、也没有人排队时才能拿到
+    pub fn try_read(&self) -> Option<AsyncRwLockReadGuard<'_, T>> {
+        let acquired = self.state.lock().unwrap().try_acquire(Access::Read);
+        acquired.then(|| AsyncRwLockReadGuard::new(self))
+    }
+
+    /// 不等待：没有任何人持有、也没有人排队时才能拿到
+    pub fn try_write(&self) -> Option<AsyncRwLockWriteGuard<'_, T>> {
+        let acquired = self.state.lock().unwrap().try_acquire(Access::Write);
+        acquired.then(|| AsyncRwLockWriteGuard::new(self))
+    }
+
+    /// `Read` 和 `Write` 共用的 poll 逻辑，`Ready` 表示已经拿到锁
+    fn poll_acquire(
+        &self,
+        access: Access,
+        waiter: &mut Option<u64>,
+        cx: &mut Context<'_>,
+    ) -> Poll<()> {
+        let mut state = self.state.lock().unwrap();
+        match *waiter {
+            None => {
+                if state.try_acquire(access) {
+                    return Poll::Ready(());
+                }
+                *waiter = Some(state.waiters.push(access, cx.waker()));
+                Poll::Pending
+            }
+            Some(id) => {
+                if state.waiters.update(id, cx.waker()) {
+                    return Poll::Pending;
+                }
+                *waiter = None;
+                Poll::Ready(())
+            }
+        }
+    }
+
+    /// 等待中的 `Read`/`Write` 被 drop
+    fn cancel(&self, access: Access, id: u64) {
+        let mut state = self.state.lock().unwrap();
+        let wakers = if state.waiters.remove(id) {
+            // 被移除的可能是挡在队首的写者，后面的读者现在也许可以放行了
+            state.grant()
+        } else {
+            // 锁已经交给了我们：还回去，再转交给后面的等待者
+            state.release(access)
+        };
+        drop(state);
+        for waker in wakers {
+            waker.wake();
+        }
+    }
+
+    fn release(&self, access: Access) {
+        let wakers = self.state.lock().unwrap().release(access);
+        for waker in wakers {
+            waker.wake();
+        }
+    }
+}
+
+/// `AsyncRwLock::read` 返回的 future
+pub struct Read<'a, T: ?Sized> {
+    lock: &'a AsyncRwLock<T>,
+    // 排队后的编号；不在队列中说明锁已经转交给了这个 future
+    waiter: Option<u64>,
+}
+
+impl<'a, T: ?Sized> Future for Read<'a, T> {
+    type Output = AsyncRwLockReadGuard<'a, T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        ready!(this.lock.poll_acquire(Access::Read, &mut this.waiter, cx));
+        Poll::Ready(AsyncRwLockReadGuard::new(this.lock))
+    }
+}
+
+impl<T: ?Sized> Drop for Read<'_, T> {
+    fn drop(&mut self) {
+        if let Some(id) = self.waiter {
+            self.lock.cancel(Access::Read, id);
+        }
+    }
+}
+
+/// `AsyncRwLock::write` 返回的 future
+pub struct Write<'a, T: ?Sized> {
+    lock: &'a AsyncRwLock<T>,
+    waiter: Option<u64>,
+}
+
+impl<'a, T: ?Sized> Future for Write<'a, T> {
+    type Output = AsyncRwLockWriteGuard<'a, T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        ready!(this.lock.poll_acquire(Access::Write, &mut this.waiter, cx));
+        Poll::Ready(AsyncRwLockWriteGuard::new(this.lock))
+    }
+}
+
+impl<T: ?Sized> Drop for Write<'_, T> {
+    fn drop(&mut self) {
+        if let Some(id) = self.waiter {
+            self.lock.cancel(Access::Write, id);
+        }
+    }
+}
+
+/// 读锁的 guard，drop 时释放
+pub struct AsyncRwLockReadGuard<'a, T: ?Sized> {
+    lock: &'a AsyncRwLock<T>,
+    _marker: PhantomData<&'a T>,
+}
+
+impl<'a, T: ?Sized> AsyncRwLockReadGuard<'a, T> {
+    fn new(lock: &'a AsyncRwLock<T>) -> Self {
+        Self {
+            lock,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T: ?Sized> Deref for AsyncRwLockReadGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.value.get() }
+    }
+}
+
+impl<T: ?Sized> Drop for AsyncRwLockReadGuard<'_, T> {
+    fn drop(&mut self) {
+        self.lock.release(Access::Read);
+    }
+}
+
+/// 写锁的 guard，drop 时释放
+pub struct AsyncRwLockWriteGuard<'a, T: ?Sized> {
+    lock: &'a AsyncRwLock<T>,
+    _marker: PhantomData<&'a mut T>,
+}
+
+impl<'a, T: ?Sized> AsyncRwLockWriteGuard<'a, T> {
+    fn new(lock: &'a AsyncRwLock<T>) -> Self {
+        Self {
+            lock,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T: ?Sized> Deref for AsyncRwLockWriteGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.value.get() }
+    }
+}
+
+impl<T: ?Sized> DerefMut for AsyncRwLockWriteGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.lock.value.get() }
+    }
+}
+
+impl<T: ?Sized> Drop for AsyncRwLockWriteGuard<'_, T> {
+    fn drop(&mut self) {
+        self.lock.release(Access::Write);
+    }
+}
+
+type Log = Arc<Mutex<Vec<String>>>;
+
+async fn reader(lock: Arc<AsyncRwLock<u32>>, log: Log, name: String) {
+    let value = lock.read().await;
+    log.lock().unwrap().push(format!("{name} 拿到读锁: {}", *value));
+    AsyncTimerFuture::new(Duration::from_millis(20)).await;
+    log.lock().unwrap().push(format!("{name} 释放读锁"));
+}
+
+async fn writer(lock: Arc<AsyncRwLock<u32>>, log: Log) {
+    let mut value = lock.write().await;
+    *value += 1;
+    log.lock().unwrap().push("writer 拿到写锁".to_string());
+}
+
+/// 三个读者、一个写者、一个晚到的读者，按这个顺序开始等待锁
+const SPAWN_ORDER: [&str; 5] = ["reader-0", "reader-1", "reader-2", "writer", "late-reader"];
+
+fn check_log(log: &[String]) {
+    let position = |prefix: &str| log.iter().position(|line| line.starts_with(prefix)).unwrap();
+    // 读者之间不互斥：三个读者都拿到锁之后才有人释放
+    assert!(position("reader-2 拿到") < position("reader-0 释放"));
+    // 写者等所有读者释放
+    assert!(position("writer") > position("reader-2 释放"));
+    // 晚到的读者排在写者后面，读到写者修改后的值
+    assert!(position("late-reader") > position("writer"));
+    assert!(log[position("late-reader")].ends_with(": 1"));
+}
+
+/// 测试 AsyncRwLock
+///
+/// 1. SimpleExecutor：三个读者同时持有读锁，写者排在它们后面，
+///    写者之后来的读者不插队
+/// 2. tokio：同样的代码运行在 tokio 的 current_thread 运行时上
+/// 3. 取消：挡在队首的写者被取消后，后面的读者被放行
+pub fn test_async_rwlock() {
+    println!("\n=== AsyncRwLock 示例：公平的异步读写锁 ===");
+
+    // 1. SimpleExecutor（虚拟时钟）
+    let executor = SimpleExecutor::new_paused();
+    let start = executor.now();
+    let lock = Arc::new(AsyncRwLock::new(0));
+    let log = Log::default();
+    let handles: Vec<_> = SPAWN_ORDER
+        .into_iter()
+        .map(|name| match name {
+            "writer" => executor.spawn(writer(lock.clone(), log.clone())),
+            _ => executor.spawn(reader(lock.clone(), log.clone(), name.to_string())),
+        })
+        .collect();
+    executor.block_on(async {
+        for handle in handles {
+            handle.await.unwrap();
+        }
+    });
+    let log = log.lock().unwrap().clone();
+    println!("SimpleExecutor:");
+    for line in &log {
+        println!("  {}", line);
+    }
+    check_log(&log);
+    // 前三个读者同时持有 20ms，晚到的读者再持有 20ms
+    assert_eq!(executor.now() - start, Duration::from_millis(40));
+
+    // 2. tokio
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    let lock = Arc::new(AsyncRwLock::new(0));
+    let log = Log::default();
+    runtime.block_on(async {
+        let handles: Vec<_> = SPAWN_ORDER
+            .into_iter()
+            .map(|name| match name {
+                "writer" => tokio::spawn(writer(lock.clone(), log.clone())),
+                _ => tokio::spawn(reader(lock.clone(), log.clone(), name.to_string())),
+            })
+            .collect();
+        for handle in handles {
+            handle.await.unwrap();
+        }
+    });
+    let log = log.lock().unwrap().clone();
+    println!("tokio:");
+    for line in &log {
+        println!("  {}", line);
+    }
+    check_log(&log);
+
+    // 3. 取消排在队首的写者
+    let lock = AsyncRwLock::new(0);
+    let counter = CountingWaker::new();
+    let waker = Waker::from(counter.clone());
+    let mut cx = Context::from_waker(&waker);
+
+    let first = lock.try_read().unwrap();
+    let mut write = lock.write();
+    assert!(Pin::new(&mut write).poll(&mut cx).is_pending());
+    // 写者在排队，新来的读者不插队
+    assert!(lock.try_read().is_none());
+    let mut late = lock.read();
+    assert!(Pin::new(&mut late).poll(&mut cx).is_pending());
+
+    drop(write);
+    assert_eq!(counter.wakes(), 1);
+    assert!(Pin::new(&mut late).poll(&mut cx).is_ready());
+    drop(first);
+    assert!(lock.try_write().is_some());
+    println!("队首的写者被取消后，后面的读者被放行");
+
+    println!("\n关键点：");
+    println!("- 多个读者可以同时持有读锁，写者独占");
+    println!("- 按排队顺序转交，有写者排队时新读者不插队，写者不会饿死");
+    println!("- 取消时重新检查队首，不会丢失唤醒");
+}
@@ -0,0 +1,256 @@
+use std::cell::UnsafeCell;
+use std::future::Future;
+use std::pin::Pin;
+use std::ptr::NonNull;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::time::Duration;
+
+use super::super::custom_waker::AsyncTimerFuture;
+use super::super::simple_executor::SimpleExecutor;
+use super::super::waker::CountingWaker;
+use super::intrusive::{Waiter, WaiterList};
+
+/// 异步信号量：许可不够时保存 waker 排队，归还许可时按顺序唤醒
+///
+/// 和 `AsyncMutex` 一样先来后到：队首还在等时，后来的 `acquire` 即使许可够用也要排队，
+/// 否则一次要很多许可的等待者可能永远等不到。
+///
+/// 等待链表是侵入式的（节点放在 `Acquire` future 里），排队不分配内存。
+pub struct Semaphore {
+    state: Mutex<SemaphoreState>,
+}
+
+struct SemaphoreState {
+    permits: usize,
+    // 节点的 value 是需要的许可数
+    waiters: WaiterList<usize>,
+}
+
+impl Semaphore {
+    pub fn new(permits: usize) -> Self {
+        Self {
+            state: Mutex::new(SemaphoreState {
+                permits,
+                waiters: WaiterList::new(),
+            }),
+        }
+    }
+
+    pub fn available_permits(&self) -> usize {
+        self.state.lock().unwrap().permits
+    }
+
+    /// 获取一个许可
+    pub fn acquire(&self) -> Acquire<'_> {
+        self.acquire_many(1)
+    }
+
+    /// 一次获取 `permits` 个许可
+    pub fn acquire_many(&self, permits: usize) -> Acquire<'_> {
+        Acquire {
+            semaphore: self,
+            permits,
+            queued: false,
+            waiter: UnsafeCell::new(Waiter::new(permits)),
+        }
+    }
+
+    /// 不等待：没有人排队并且许可够用时才能拿到
+    pub fn try_acquire(&self) -> Option<SemaphorePermit<'_>> {
+        let mut state = self.state.lock().unwrap();
+        if !state.waiters.is_empty() || state.permits == 0 {
+            return None;
+        }
+        state.permits -= 1;
+        Some(SemaphorePermit {
+            semaphore: self,
+            permits: 1,
+        })
+    }
+
+    /// 增加许可，并按顺序满足排队的等待者
+    pub fn add_permits(&self, permits: usize) {
+        let mut state = self.state.lock().unwrap();
+        state.permits += permits;
+        loop {
+            let Some(front) = state.waiters.front_mut() else {
+                return;
+            };
+            let needed = front.value;
+            if needed > state.permits {
+                return;
+            }
+            state.permits -= needed;
+            let waker = state.waiters.pop_front();
+            // 和 SharedState 一样在锁外调用 waker；一次唤醒一个，不需要收集到 Vec 里
+            drop(state);
+            if let Some(waker) = waker {
+                waker.wake();
+            }
+            state = self.state.lock().unwrap();
+        }
+    }
+}
+
+/// `Semaphore::acquire` 返回的 future
+///
+/// 排队后节点被链表引用，所以它是 `!Unpin` 的，需要先 pin 住（`.await` 会自动做到）。
+pub struct Acquire<'a> {
+    semaphore: &'a Semaphore,
+    permits: usize,
+    // 已经排队；节点不在链表中说明许可已经分给了这个 future
+    queued: bool,
+    waiter: UnsafeCell<Waiter<usize>>,
+}
+
+impl<'a> Future for Acquire<'a> {
+    type Output = SemaphorePermit<'a>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // 不移动任何字段：节点的地址在 drop 之前保持不变
+        let this = unsafe { self.get_unchecked_mut() };
+        let node = unsafe { NonNull::new_unchecked(this.waiter.get()) };
+        let mut state = this.semaphore.state.lock().unwrap();
+        if !this.queued {
+            if state.waiters.is_empty() && state.permits >= this.permits {
+                state.permits -= this.permits;
+                return Poll::Ready(this.permit());
+            }
+            unsafe { state.waiters.push_back(node, cx.waker()) };
+            this.queued = true;
+            return Poll::Pending;
+        }
+        // 持有锁，可以访问节点
+        let waiter = unsafe { &mut *node.as_ptr() };
+        if waiter.is_linked() {
+            waiter.set_waker(cx.waker());
+            return Poll::Pending;
+        }
+        this.queued = false;
+        Poll::Ready(this.permit())
+    }
+}
+
+impl<'a> Acquire<'a> {
+    fn permit(&self) -> SemaphorePermit<'a> {
+        SemaphorePermit {
+            semaphore: self.semaphore,
+            permits: self.permits,
+        }
+    }
+}
+
+impl Drop for Acquire<'_> {
+    fn drop(&mut self) {
+        if !self.queued {
+            return;
+        }
+        let node = unsafe { NonNull::new_unchecked(self.waiter.get()) };
+        let removed = unsafe { self.semaphore.state.lock().unwrap().waiters.remove(node) };
+        // 还在排队：队首可能变了，后面的等待者也许已经可以满足；
+        // 已经分到许可：没有人会用它了，归还
+        let returned = if removed { 0 } else { self.permits };
+        self.semaphore.add_permits(returned);
+    }
+}
+
+/// 持有期间占用许可，drop 时归还
+pub struct SemaphorePermit<'a> {
+    semaphore: &'a Semaphore,
+    permits: usize,
+}
+
+impl Drop for SemaphorePermit<'_> {
+    fn drop(&mut self) {
+        self.semaphore.add_permits(self.permits);
+    }
+}
+
+/// 持有许可跨过定时器，记录同时运行的最大任务数
+async fn limited_job(semaphore: Arc<Semaphore>, running: Arc<AtomicUsize>, peak: Arc<AtomicUsize>) {
+    let _permit = semaphore.acquire().await;
+    let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+    peak.fetch_max(now, Ordering::SeqCst);
+    AsyncTimerFuture::new(Duration::from_millis(10)).await;
+    running.fetch_sub(1, Ordering::SeqCst);
+}
+
+/// 测试 Semaphore
+///
+/// 1. SimpleExecutor：6 个任务，2 个许可，最多 2 个同时运行，一共用 30ms
+/// 2. tokio：同样的代码运行在 tokio 的 current_thread 运行时上
+/// 3. 公平和取消：要 2 个许可的等待者排在前面时，后来的 acquire 不会插队；
+///    它被取消后，后面的等待者立刻拿到许可
+pub fn test_semaphore() {
+    println!("\n=== Semaphore 示例：限制并发数 ===");
+
+    // 1. SimpleExecutor（虚拟时钟）
+    let executor = SimpleExecutor::new_paused();
+    let start = executor.now();
+    let semaphore = Arc::new(Semaphore::new(2));
+    let (running, peak) = (Arc::new(AtomicUsize::new(0)), Arc::new(AtomicUsize::new(0)));
+    let handles: Vec<_> = (0..6)
+        .map(|_| executor.spawn(limited_job(semaphore.clone(), running.clone(), peak.clone())))
+        .collect();
+    executor.block_on(async {
+        for handle in handles {
+            handle.await.unwrap();
+        }
+    });
+    println!(
+        "SimpleExecutor: 最多 {} 个任务同时运行，用时 {:?}",
+        peak.load(Ordering::SeqCst),
+        executor.now() - start
+    );
+    assert_eq!(peak.load(Ordering::SeqCst), 2);
+    assert_eq!(executor.now() - start, Duration::from_millis(30));
+    assert_eq!(semaphore.available_permits(), 2);
+
+    // 2. tokio
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    let semaphore = Arc::new(Semaphore::new(2));
+    let (running, peak) = (Arc::new(AtomicUsize::new(0)), Arc::new(AtomicUsize::new(0)));
+    runtime.block_on(async {
+        let handles: Vec<_> = (0..6)
+            .map(|_| tokio::spawn(limited_job(semaphore.clone(), running.clone(), peak.clone())))
+            .collect();
+        for handle in handles {
+            handle.await.unwrap();
+        }
+    });
+    println!("tokio: 最多 {} 个任务同时运行", peak.load(Ordering::SeqCst));
+    assert_eq!(peak.load(Ordering::SeqCst), 2);
+
+    // 3. 公平和取消
+    let semaphore = Semaphore::new(2);
+    let counter = CountingWaker::new();
+    let waker = Waker::from(counter.clone());
+    let mut cx = Context::from_waker(&waker);
+
+    let held = semaphore.try_acquire().unwrap();
+    let mut big = Box::pin(semaphore.acquire_many(2));
+    let mut small = Box::pin(semaphore.acquire());
+    assert!(big.as_mut().poll(&mut cx).is_pending());
+    // 还剩 1 个许可，但 big 在排队，small 不能插队
+    assert!(small.as_mut().poll(&mut cx).is_pending());
+    assert!(semaphore.try_acquire().is_none());
+
+    drop(big);
+    assert_eq!(counter.wakes(), 1);
+    let Poll::Ready(permit) = small.as_mut().poll(&mut cx) else {
+        panic!("big 被取消后 small 应该拿到许可");
+    };
+    drop(permit);
+    drop(held);
+    assert_eq!(semaphore.available_permits(), 2);
+    println!("排在前面的等待者被取消后，后面的等待者立刻拿到许可");
+
+    println!("\n关键点：");
+    println!("- 许可不够时把节点挂到侵入式链表上，节点就在 future 里，排队不分配内存");
+    println!("- 先来后到：队首在等时后来者不插队");
+    println!("- 取消时从链表中移除节点；已经分到的许可要归还");
+}
@@ -0,0 +1,75 @@
+use std::collections::VecDeque;
+use std::task::Waker;
+
+/// 按先来后到排队的等待者，和 `SharedState` 一样保存 waker
+///
+/// 等待者只知道自己的编号。轮到它时，释放方把它从队列中取出（所有权已经转交给它），
+/// 然后调用它的 waker；等待者再次被 poll 时发现自己不在队列中，就知道已经拿到了。
+pub(crate) struct WaiterQueue<K> {
+    next_id: u64,
+    waiters: VecDeque<Waiter<K>>,
+}
+
+pub(crate) struct Waiter<K> {
+    id: u64,
+    pub(crate) kind: K,
+    pub(crate) waker: Waker,
+}
+
+impl<K> WaiterQueue<K> {
+    pub(crate) fn new() -> Self {
+        Self {
+            next_id: 0,
+            waiters: VecDeque::new(),
+        }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.waiters.is_empty()
+    }
+
+    /// 排到队尾，返回编号
+    pub(crate) fn push(&mut self, kind: K, waker: &Waker) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.waiters.push_back(Waiter {
+            id,
+            kind,
+            waker: waker.clone(),
+        });
+        id
+    }
+
+    /// 还在排队时更新 waker 并返回 `true`；已经被取出（轮到它了）时返回 `false`
+    pub(crate) fn update(&mut self, id: u64, waker: &Waker) -> bool {
+        match self.waiters.iter_mut().find(|waiter| waiter.id == id) {
+            Some(waiter) => {
+                // 和 AsyncTimerFuture 一样：future 可能在不同的任务间移动，需要更新 waker
+                if !waiter.waker.will_wake(waker) {
+                    waiter.waker = waker.clone();
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// 等待者被取消：还在排队时移除并返回 `true`；已经轮到它时返回 `false`
+    pub(crate) fn remove(&mut self, id: u64) -> bool {
+        match self.waiters.iter().position(|waiter| waiter.id == id) {
+            Some(index) => {
+                self.waiters.remove(index);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub(crate) fn front(&self) -> Option<&Waiter<K>> {
+        self.waiters.front()
+    }
+
+    pub(crate) fn pop_front(&mut self) -> Option<Waiter<K>> {
+        self.waiters.pop_front()
+    }
+}
@@ -0,0 +1,395 @@
+use std::cell::Cell;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::panic;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::task::{Context, Wake, Waker};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::custom_waker::AsyncTimerFuture;
+use super::simple_executor::{BoxFuture, JoinHandle, SimpleExecutor, joinable};
+
+thread_local! {
+    // 当前线程如果是某个线程池的 worker，记录 (线程池地址, worker 编号)
+    // 用来判断唤醒/spawn 发生在哪个 worker 上，从而放进它自己的本地队列
+    static CURRENT_WORKER: Cell<Option<(*const Shared, usize)>> = const { Cell::new(None) };
+}
+
+/// 多线程 work-stealing executor
+///
+/// # 队列结构
+///
+/// - **injector**：全局共享队列，从线程池外部 spawn/唤醒的任务放在这里
+/// - **本地队列**：每个 worker 一个，worker 自己 spawn/唤醒的任务放在这里
+///
+/// # worker 取任务的顺序
+///
+/// 1. 自己本地队列的尾部（LIFO，刚产生的任务数据还在缓存里）
+/// 2. injector 的头部
+/// 3. 从其他 worker 本地队列的头部"偷"走一半任务（work stealing）
+/// 4. 都没有任务时，在条件变量上休眠，等待新任务
+///
+/// 这和 tokio `rt-multi-thread` 的调度思路一致，只是 tokio 的本地队列是无锁的，
+/// 这里为了简单用 `Mutex<VecDeque>` 实现。
+pub struct ThreadPoolExecutor {
+    shared: Arc<Shared>,
+    workers: Vec<thread::JoinHandle<()>>,
+}
+
+struct Shared {
+    injector: Mutex<VecDeque<Arc<Task>>>,
+    locals: Vec<Mutex<VecDeque<Arc<Task>>>>,
+    stats: Vec<WorkerCounters>,
+    // 休眠的 worker 在这个条件变量上等待；push 任务后通知
+    sleep_lock: Mutex<()>,
+    sleep_cvar: Condvar,
+    shutdown: AtomicBool,
+}
+
+#[derive(Default)]
+struct WorkerCounters {
+    polled: AtomicUsize,
+    stolen: AtomicUsize,
+}
+
+/// 单个 worker 的统计数据
+#[derive(Debug, Clone, Copy)]
+pub struct WorkerStats {
+    /// 这个 worker poll 任务的次数
+    pub polled: usize,
+    /// 这个 worker 从其他 worker 偷来的任务数
+    pub stolen: usize,
+}
+
+struct Task {
+    future: Mutex<Option<BoxFuture>>,
+    queued: AtomicBool,
+    shared: Arc<Shared>,
+}
+
+impl Wake for Task {
+    fn wake(self: Arc<Self>) {
+        self.schedule();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.clone().schedule();
+    }
+}
+
+impl Task {
+    fn schedule(self: Arc<Self>) {
+        if !self.queued.swap(true, Ordering::AcqRel) {
+            let shared = self.shared.clone();
+            shared.push(self);
+        }
+    }
+
+    fn run(self: Arc<Self>) {
+        self.queued.store(false, Ordering::Release);
+
+        let mut slot = self.future.lock().unwrap();
+        let Some(future) = slot.as_mut() else {
+            return;
+        };
+        let waker = Waker::from(self.clone());
+        let mut cx = Context::from_waker(&waker);
+        if future.as_mut().poll(&mut cx).is_ready() {
+            *slot = None;
+        }
+    }
+}
+
+impl Shared {
+    /// 把任务放进队列：在本线程池的 worker 上就放本地队列，否则放 injector
+    fn push(self: &Arc<Self>, task: Arc<Task>) {
+        match CURRENT_WORKER.get() {
+            Some((pool, index)) if pool == Arc::as_ptr(self) => {
+                self.locals[index].lock().unwrap().push_back(task);
+            }
+            _ => self.injector.lock().unwrap().push_back(task),
+        }
+        // 先拿 sleep_lock 再通知：worker 在持有 sleep_lock 时检查队列，
+        // 这样不会在"检查完队列、还没开始 wait"的间隙丢失通知
+        let _guard = self.sleep_lock.lock().unwrap();
+        self.sleep_cvar.notify_one();
+    }
+
+    fn has_work(&self) -> bool {
+        !self.injector.lock().unwrap().is_empty()
+            || self.locals.iter().any(|local| !local.lock().unwrap().is_empty())
+    }
+
+    fn find_task(&self, index: usize) -> Option<Arc<Task>> {
+        if let Some(task) = self.locals[index].lock().unwrap().pop_back() {
+            return Some(task);
+        }
+        if let Some(task) = self.injector.lock().unwrap().pop_front() {
+            return Some(task);
+        }
+        self.steal(index)
+    }
+
+    /// 从其他 worker 的本地队列头部偷走一半任务
+    fn steal(&self, index: usize) -> Option<Arc<Task>> {
+        let count = self.locals.len();
+        for offset in 1..count {
+            let victim = (index + offset) % count;
+            let mut stolen: VecDeque<Arc<Task>> = {
+                let mut victim_queue = self.locals[victim].lock().unwrap();
+                let half = victim_queue.len().div_ceil(2);
+                victim_queue.drain(..half).collect()
+            };
+            if let Some(task) = stolen.pop_front() {
+                self.stats[index]
+                    .stolen
+                    .fetch_add(stolen.len() + 1, Ordering::Relaxed);
+                self.locals[index].lock().unwrap().extend(stolen);
+                return Some(task);
+            }
+        }
+        None
+    }
+
+    fn spawn<F>(self: &Arc<Self>, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        let (future, handle) = joinable(future);
+        let task = Arc::new(Task {
+            future: Mutex::new(Some(future)),
+            queued: AtomicBool::new(false),
+            shared: self.clone(),
+        });
+        task.schedule();
+        handle
+    }
+}
+
+fn worker_loop(shared: Arc<Shared>, index: usize) {
+    CURRENT_WORKER.set(Some((Arc::as_ptr(&shared), index)));
+    loop {
+        if let Some(task) = shared.find_task(index) {
+            shared.stats[index].polled.fetch_add(1, Ordering::Relaxed);
+            task.run();
+            continue;
+        }
+
+        let guard = shared.sleep_lock.lock().unwrap();
+        if shared.shutdown.load(Ordering::Acquire) {
+            break;
+        }
+        if !shared.has_work() {
+            drop(shared.sleep_cvar.wait(guard).unwrap());
+        }
+    }
+    CURRENT_WORKER.set(None);
+}
+
+impl ThreadPoolExecutor {
+    /// 创建一个有 `workers` 个 worker 线程的线程池
+    pub fn new(workers: usize) -> Self {
+        assert!(workers > 0, "线程池至少需要一个 worker");
+        let shared = Arc::new(Shared {
+            injector: Mutex::new(VecDeque::new()),
+            locals: (0..workers).map(|_| Mutex::new(VecDeque::new())).collect(),
+            stats: (0..workers).map(|_| WorkerCounters::default()).collect(),
+            sleep_lock: Mutex::new(()),
+            sleep_cvar: Condvar::new(),
+            shutdown: AtomicBool::new(false),
+        });
+        let workers = (0..workers)
+            .map(|index| {
+                let shared = shared.clone();
+                thread::Builder::new()
+                    .name(format!("pool-worker-{index}"))
+                    .spawn(move || worker_loop(shared, index))
+                    .unwrap()
+            })
+            .collect();
+        Self { shared, workers }
+    }
+
+    /// 创建一个任务，由某个 worker 执行
+    ///
+    /// 在 worker 线程内部调用时（见 `spawner()`），任务进入该 worker 的本地队列，
+    /// 其他空闲 worker 可以把它偷走。
+    pub fn spawn<F>(&self, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        self.shared.spawn(future)
+    }
+
+    /// 返回一个可以移动到任务内部的 spawn 句柄
+    pub fn spawner(&self) -> Spawner {
+        Spawner {
+            shared: self.shared.clone(),
+        }
+    }
+
+    /// 在线程池上运行 future，阻塞当前线程直到它完成
+    ///
+    /// 当前线程只负责等待 `JoinHandle`，这里直接复用 `SimpleExecutor::block_on`。
+    /// future panic 时，在当前线程上继续传播这个 panic。
+    pub fn block_on<F>(&self, future: F) -> F::Output
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        let handle = self.spawn(future);
+        SimpleExecutor::new()
+            .block_on(handle)
+            .unwrap_or_else(|err| panic::resume_unwind(err.into_panic()))
+    }
+
+    /// 每个 worker 的 poll 次数和偷取任务数
+    pub fn stats(&self) -> Vec<WorkerStats> {
+        self.shared
+            .stats
+            .iter()
+            .map(|counters| WorkerStats {
+                polled: counters.polled.load(Ordering::Relaxed),
+                stolen: counters.stolen.load(Ordering::Relaxed),
+            })
+            .collect()
+    }
+}
+
+impl Drop for ThreadPoolExecutor {
+    fn drop(&mut self) {
+        {
+            let _guard = self.shared.sleep_lock.lock().unwrap();
+            self.shared.shutdown.store(true, Ordering::Release);
+            self.shared.sleep_cvar.notify_all();
+        }
+        for worker in self.workers.drain(..) {
+            worker.join().unwrap();
+        }
+        // 任务持有 Arc<Shared>，队列又持有任务：清空队列打破引用环
+        self.shared.injector.lock().unwrap().clear();
+        for local in &self.shared.locals {
+            local.lock().unwrap().clear();
+        }
+    }
+}
+
+/// 线程池的 spawn 句柄，可以在任务内部继续 spawn 子任务
+#[derive(Clone)]
+pub struct Spawner {
+    shared: Arc<Shared>,
+}
+
+impl Spawner {
+    pub fn spawn<F>(&self, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        self.shared.spawn(future)
+    }
+}
+
+/// greet 风格的 future：打印、等待定时器、再打印
+async fn greet_on_timer(id: usize) -> usize {
+    println!("[task {id}] Hello! ({:?})", thread::current().name());
+    AsyncTimerFuture::new(Duration::from_millis(200)).await;
+    println!("[task {id}] Goodbye! ({:?})", thread::current().name());
+    id
+}
+
+/// 测试 ThreadPoolExecutor：多线程执行任务并统计 work stealing
+///
+/// 1. 从外部 spawn 若干 greet 风格的任务（进入 injector）
+/// 2. 在一个任务内部 spawn 大量子任务（进入该 worker 的本地队列），然后故意阻塞这个 worker
+///    直到子任务都完成：它们只能被其他 worker 偷走执行
+/// 3. 打印每个 worker 的 poll 次数和偷取次数，断言子任务都是被偷走的
+/// 4. 用 tokio 的 `rt-multi-thread` 跑同样的任务做对比
+pub fn test_thread_pool_executor() {
+    println!("\n=== ThreadPoolExecutor 示例：多线程 work-stealing executor ===");
+
+    let pool = ThreadPoolExecutor::new(4);
+    let start = Instant::now();
+
+    let greets: Vec<_> = (0..4).map(|id| pool.spawn(greet_on_timer(id))).collect();
+
+    let spawner = pool.spawner();
+    let fan_out = pool.spawn(async move {
+        let finished = Arc::new(AtomicUsize::new(0));
+        let children: Vec<_> = (0..64u64)
+            .map(|n| {
+                let finished = finished.clone();
+                spawner.spawn(async move {
+                    // 模拟一小段计算
+                    let sum = (0..10_000u64).fold(n, |acc, x| acc.wrapping_add(x));
+                    finished.fetch_add(1, Ordering::Relaxed);
+                    sum
+                })
+            })
+            .collect();
+        // 故意在 poll 中阻塞这个 worker：子任务都在它的本地队列里，
+        // 它不会再去取，所以子任务只能被其他 worker 偷走执行
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while finished.load(Ordering::Relaxed) < 64 && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(1));
+        }
+        let mut sum = 0u64;
+        for child in children {
+            sum = sum.wrapping_add(child.await.unwrap());
+        }
+        sum
+    });
+
+    let (ids, sum) = pool.block_on(async move {
+        let mut ids = Vec::new();
+        for greet in greets {
+            ids.push(greet.await.unwrap());
+        }
+        (ids, fan_out.await.unwrap())
+    });
+    let elapsed = start.elapsed();
+
+    println!("\n结果: ids = {:?}, sum = {}", ids, sum);
+    println!("ThreadPoolExecutor 总耗时: {:?}", elapsed);
+    assert_eq!(ids, vec![0, 1, 2, 3]);
+
+    let stats = pool.stats();
+    for (index, worker) in stats.iter().enumerate() {
+        println!(
+            "worker {index}: polled = {}, stolen = {}",
+            worker.polled, worker.stolen
+        );
+    }
+    // 4 个 greet、1 个 fan_out、64 个子任务和 block_on 的任务，每个至少 poll 一次
+    let tasks = 4 + 1 + 64 + 1;
+    assert!(stats.iter().map(|w| w.polled).sum::<usize>() >= tasks);
+    // 64 个子任务都是从 fan_out 所在 worker 的本地队列偷走的（被转手偷的会多算）
+    let stolen = stats.iter().map(|w| w.stolen).sum::<usize>();
+    assert!(stolen >= 64, "只偷走了 {stolen} 个任务");
+
+    // 对比：tokio 的多线程运行时
+    let rt = tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(4)
+        .build()
+        .unwrap();
+    let start = Instant::now();
+    let tokio_ids = rt.block_on(async {
+        let greets: Vec<_> = (0..4).map(|id| tokio::spawn(greet_on_timer(id))).collect();
+        let mut ids = Vec::new();
+        for greet in greets {
+            ids.push(greet.await.unwrap());
+        }
+        ids
+    });
+    println!("tokio rt-multi-thread 总耗时: {:?}", start.elapsed());
+    assert_eq!(tokio_ids, ids);
+
+    println!("\n关键点：");
+    println!("- 外部 spawn 的任务进入 injector，worker 内部 spawn 的任务进入本地队列");
+    println!("- 空闲的 worker 从其他 worker 的本地队列偷走一半任务");
+    println!("- tokio 的 rt-multi-thread 使用同样的思路，只是队列实现是无锁的");
+}
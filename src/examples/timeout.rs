@@ -0,0 +1,161 @@
+use std::fmt;
+use std::future::Future;
+use std::pin::{Pin, pin};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use super::pin_project::pin_project;
+use super::simple_executor::SimpleExecutor;
+use super::timer::{Sleep, now, sleep, sleep_until};
+
+/// 超时：future 在时限之前没有完成
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Elapsed;
+
+impl fmt::Display for Elapsed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "超时")
+    }
+}
+
+impl std::error::Error for Elapsed {}
+
+/// 最多等待 `duration`，超时返回 `Err(Elapsed)`
+pub fn timeout<F: Future>(duration: Duration, future: F) -> Timeout<F> {
+    deadline(now() + duration, future)
+}
+
+/// 最多等到 `deadline`，超时返回 `Err(Elapsed)`
+///
+/// 到期时刻按当前线程的定时器驱动的时钟计算，和 `AsyncTimerFuture` 一样，
+/// 在 `SimpleExecutor::new_paused()` 中使用虚拟时钟。
+pub fn deadline<F: Future>(deadline: Instant, future: F) -> Timeout<F> {
+    Timeout {
+        future: Some(future),
+        timer: sleep_until(deadline),
+    }
+}
+
+pin_project! {
+    /// `timeout` / `deadline` 返回的 future
+    ///
+    /// 里面的 future 和一个时间轮上的定时器一起 poll：future 先完成就返回它的结果；
+    /// 定时器先到期，就立刻 drop 里面的 future（取消它），再返回 `Err(Elapsed)`。
+    pub struct Timeout<F> {
+        // 超时后原地 drop，变成 None
+        #[pin]
+        future: Option<F>,
+        timer: Sleep,
+    }
+}
+
+impl<F: Future> Future for Timeout<F> {
+    type Output = Result<F::Output, Elapsed>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut this = self.project();
+        let future = this.future.as_mut().as_pin_mut().expect("超时之后不能再 poll");
+        // 先 poll 里面的 future：刚好在到期时完成的结果不会被丢掉
+        if let Poll::Ready(output) = future.poll(cx) {
+            return Poll::Ready(Ok(output));
+        }
+        match Pin::new(this.timer).poll(cx) {
+            Poll::Ready(()) => {
+                // 不等 Timeout 本身被 drop：到期时立刻释放里面 future 持有的资源
+                this.future.set(None);
+                Poll::Ready(Err(Elapsed))
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+/// drop 时设置标志的 future，用来检查超时后是否被及时 drop
+struct DropFlag<F> {
+    future: Pin<Box<F>>,
+    dropped: Arc<AtomicBool>,
+}
+
+impl<F: Future> Future for DropFlag<F> {
+    type Output = F::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<F::Output> {
+        self.future.as_mut().poll(cx)
+    }
+}
+
+impl<F> Drop for DropFlag<F> {
+    fn drop(&mut self) {
+        self.dropped.store(true, Ordering::SeqCst);
+    }
+}
+
+/// 测试 timeout / deadline / block_on_timeout
+///
+/// 1. 在时限之前完成：返回 `Ok`
+/// 2. 超时：返回 `Err(Elapsed)`，并且里面的 future 在返回时已经被 drop
+/// 3. `deadline`：使用绝对时刻
+/// 4. `block_on_timeout`：永远不会完成的 future 不会让 executor 线程一直阻塞
+pub fn test_timeout() {
+    println!("\n=== 超时示例：timeout / deadline / block_on_timeout ===");
+    let executor = SimpleExecutor::new_paused();
+
+    // 1. 在时限之前完成
+    let start = executor.now();
+    let result = executor.block_on(async {
+        timeout(Duration::from_millis(50), async {
+            sleep(Duration::from_millis(10)).await;
+            "完成"
+        })
+        .await
+    });
+    assert_eq!(result, Ok("完成"));
+    assert_eq!(executor.now() - start, Duration::from_millis(10));
+    println!("10ms 的操作，50ms 时限: {:?}", result);
+
+    // 2. 超时
+    let start = executor.now();
+    let dropped = Arc::new(AtomicBool::new(false));
+    executor.block_on(async {
+        let slow = DropFlag {
+            future: Box::pin(sleep(Duration::from_millis(50))),
+            dropped: dropped.clone(),
+        };
+        let mut limited = pin!(timeout(Duration::from_millis(10), slow));
+        let result = limited.as_mut().await;
+        // Timeout 还活着，但里面的 future 已经被 drop 了
+        assert!(dropped.load(Ordering::SeqCst));
+        assert_eq!(result, Err(Elapsed));
+        println!("50ms 的操作，10ms 时限: {:?}，里面的 future 已经被 drop", result);
+    });
+    assert_eq!(executor.now() - start, Duration::from_millis(10));
+
+    // 3. deadline
+    let start = executor.now();
+    let result = executor.block_on(async {
+        let at = now() + Duration::from_millis(25);
+        let first = deadline(at, sleep(Duration::from_millis(10))).await;
+        let second = deadline(at, sleep(Duration::from_millis(10))).await;
+        let third = deadline(at, sleep(Duration::from_millis(10))).await;
+        [first, second, third]
+    });
+    assert_eq!(result, [Ok(()), Ok(()), Err(Elapsed)]);
+    assert_eq!(executor.now() - start, Duration::from_millis(25));
+    println!("三个 10ms 的操作共用 25ms 的 deadline: {:?}", result);
+
+    // 4. block_on_timeout：虚拟时钟和真实时钟
+    let result = executor.block_on_timeout(Duration::from_secs(3600), std::future::pending::<()>());
+    assert_eq!(result, Err(Elapsed));
+    let started = Instant::now();
+    let result = SimpleExecutor::new().block_on_timeout(Duration::from_millis(50), std::future::pending::<()>());
+    assert_eq!(result, Err(Elapsed));
+    assert!(started.elapsed() >= Duration::from_millis(50));
+    println!("永远不会完成的 future: {:?}，executor 线程没有被卡住", result);
+
+    println!("\n关键点：");
+    println!("- 里面的 future 和时间轮上的定时器一起 poll，哪个先完成听哪个");
+    println!("- 超时时立刻 drop 里面的 future，它持有的资源随之释放");
+    println!("- block_on_timeout 让忘了保存 waker 的 future 也不能永远卡住线程");
+}
@@ -0,0 +1,507 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, OnceLock};
+use std::task::{Context, Poll};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use super::clock::{Clock, SystemClock};
+use super::custom_waker::SharedState;
+use super::simple_executor::SimpleExecutor;
+
+// 分层时间轮的参数：6 层，每层 64 个槽，最低层一个槽代表 1 毫秒
+//
+// 第 0 层覆盖 64ms，第 1 层覆盖 64^2ms（约 4 秒），……
+// 第 5 层覆盖 64^6ms（约 2.2 年），更远的定时器按最大值处理
+const LEVELS: usize = 6;
+const SLOT_BITS: u32 = 6;
+const SLOTS: usize = 1 << SLOT_BITS;
+const SLOT_MASK: u64 = SLOTS as u64 - 1;
+const MAX_TICKS: u64 = (1 << (SLOT_BITS * LEVELS as u32)) - 1;
+
+/// 时间轮中的一个定时器：到期时刻（tick）和要唤醒的共享状态
+struct Entry {
+    when: u64,
+    shared_state: Arc<Mutex<SharedState>>,
+    // 登记时 SharedState 的 generation，到期时不同说明期间被 reset 过
+    generation: u64,
+    // 所在的 (层, 槽)，取消时用来从槽中移除
+    location: (usize, usize),
+}
+
+/// 定时器在驱动中的标识，用于取消和重置
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) struct TimerKey(u64);
+
+/// 分层时间轮
+///
+/// 定时器按"距离现在有多远"放到不同的层：
+/// - 到期时刻和当前时刻只在低 6 位不同 → 第 0 层，槽位精确到 1ms
+/// - 只在低 12 位不同 → 第 1 层，一个槽覆盖 64ms
+/// - ……
+///
+/// 时间推进到高层某个槽的起点时，把这个槽里的定时器"降级"（cascade）
+/// 重新放入更低的层；到达第 0 层的槽时，槽里的定时器全部到期。
+///
+/// 槽里只存 `TimerKey`，定时器本身放在 `entries` 中，
+/// 这样取消时可以直接找到它所在的槽并移除。
+struct Wheel {
+    // 已经处理到的 tick（毫秒）
+    elapsed: u64,
+    levels: Vec<Vec<Vec<TimerKey>>>,
+    entries: HashMap<TimerKey, Entry>,
+    next_key: u64,
+}
+
+impl Wheel {
+    fn new() -> Self {
+        Self {
+            elapsed: 0,
+            levels: (0..LEVELS)
+                .map(|_| (0..SLOTS).map(|_| Vec::new()).collect())
+                .collect(),
+            entries: HashMap::new(),
+            next_key: 0,
+        }
+    }
+
+    fn next_key(&mut self) -> TimerKey {
+        self.next_key += 1;
+        TimerKey(self.next_key)
+    }
+
+    /// 计算到期时刻 `when` 应该放在哪一层
+    fn level_for(&self, when: u64) -> usize {
+        // 找到 elapsed 和 when 最高的不同位，每 6 位一层
+        let masked = (self.elapsed ^ when) | SLOT_MASK;
+        let significant = 63 - masked.leading_zeros();
+        significant as usize / SLOT_BITS as usize
+    }
+
+    /// 放入时间轮；如果已经到期，原样返回给调用方处理
+    fn insert(&mut self, key: TimerKey, mut entry: Entry) -> Option<Entry> {
+        if entry.when <= self.elapsed {
+            return Some(entry);
+        }
+        // 超出时间轮范围的定时器先放在当前轮次的最后一个 tick，到时再重新放入
+        let when = entry.when.min(self.elapsed | MAX_TICKS);
+        let level = self.level_for(when);
+        let slot = ((when >> (level as u32 * SLOT_BITS)) & SLOT_MASK) as usize;
+        self.levels[level][slot].push(key);
+        entry.location = (level, slot);
+        self.entries.insert(key, entry);
+        None
+    }
+
+    /// 从时间轮中移除，返回被移除的定时器（已经到期的返回 `None`）
+    fn remove(&mut self, key: TimerKey) -> Option<Entry> {
+        let entry = self.entries.remove(&key)?;
+        let (level, slot) = entry.location;
+        let keys = &mut self.levels[level][slot];
+        if let Some(index) = keys.iter().position(|&k| k == key) {
+            keys.swap_remove(index);
+        }
+        Some(entry)
+    }
+
+    /// 取出一个槽里的所有定时器，重新放入时间轮，收集已经到期的
+    fn rehash_slot(&mut self, level: usize, slot: usize, expired: &mut Vec<Entry>) {
+        for key in std::mem::take(&mut self.levels[level][slot]) {
+            let entry = self.entries.remove(&key).unwrap();
+            expired.extend(self.insert(key, entry));
+        }
+    }
+
+    /// 下一个需要处理的 tick：第 0 层的到期时刻，或高层槽位的降级时刻
+    fn next_expiration(&self) -> Option<u64> {
+        for level in 0..LEVELS {
+            let shift = level as u32 * SLOT_BITS;
+            let current = ((self.elapsed >> shift) & SLOT_MASK) as usize;
+            // 同一层中，比当前槽位更靠后的第一个非空槽
+            if let Some(slot) = (current + 1..SLOTS).find(|&s| !self.levels[level][s].is_empty()) {
+                let level_start = self.elapsed & !((1u64 << (shift + SLOT_BITS)) - 1);
+                return Some(level_start + ((slot as u64) << shift));
+            }
+        }
+        None
+    }
+
+    /// 把时间推进到 `now`，返回所有到期的定时器
+    fn advance(&mut self, now: u64) -> Vec<Entry> {
+        let mut expired = Vec::new();
+        while self.elapsed < now {
+            // 没有定时器需要处理时，直接跳到 now
+            let next = match self.next_expiration() {
+                Some(next) if next <= now => next,
+                _ => {
+                    self.elapsed = now;
+                    break;
+                }
+            };
+            self.elapsed = next;
+
+            // 先从高层往低层降级，再处理第 0 层到期的槽
+            for level in (1..LEVELS).rev() {
+                let shift = level as u32 * SLOT_BITS;
+                if next & ((1u64 << shift) - 1) == 0 {
+                    let slot = ((next >> shift) & SLOT_MASK) as usize;
+                    self.rehash_slot(level, slot, &mut expired);
+                }
+            }
+            self.rehash_slot(0, (next & SLOT_MASK) as usize, &mut expired);
+        }
+        expired
+    }
+}
+
+thread_local! {
+    // 当前线程正在使用的定时器驱动，由 executor 的 enter() 设置
+    static CURRENT: RefCell<Option<Arc<TimerDriver>>> = const { RefCell::new(None) };
+}
+
+/// 定时器驱动：一个时间轮加一个时钟
+///
+/// - 真实时钟：所有定时器共享一个后台线程。线程每次休眠到时间轮中
+///   下一个需要处理的时刻，醒来后推进时间轮并唤醒到期的定时器。
+///   `shutdown` 之前线程一直运行，全局驱动的线程不会退出。
+/// - 虚拟时钟：没有后台线程，时间只在 `advance`/`advance_to_next` 时前进，
+///   通常由 executor 在所有任务都空闲时调用。
+pub(crate) struct TimerDriver {
+    wheel: Mutex<Wheel>,
+    cvar: Condvar,
+    clock: Arc<dyn Clock>,
+    start: Instant,
+    // 真实时钟的后台线程，shutdown 时取出并等待它退出
+    background: Mutex<Option<JoinHandle<()>>>,
+    shutdown: AtomicBool,
+}
+
+impl TimerDriver {
+    /// 使用指定时钟创建定时器驱动；真实时钟会启动后台线程，直到 `shutdown`
+    pub(crate) fn new(clock: Arc<dyn Clock>) -> Arc<TimerDriver> {
+        let driver = Arc::new(TimerDriver {
+            wheel: Mutex::new(Wheel::new()),
+            cvar: Condvar::new(),
+            start: clock.now(),
+            clock,
+            background: Mutex::new(None),
+            shutdown: AtomicBool::new(false),
+        });
+        if !driver.is_paused() {
+            let background = driver.clone();
+            let handle = thread::Builder::new()
+                .name("timer-driver".into())
+                .spawn(move || background.run())
+                .unwrap();
+            *driver.background.lock().unwrap() = Some(handle);
+        }
+        driver
+    }
+
+    /// 让后台线程退出并等待它结束，之后这个驱动上的定时器不会再到期
+    ///
+    /// 由创建驱动的 executor 在 drop 时调用；否则线程持有驱动，永远不会退出。
+    pub(crate) fn shutdown(&self) {
+        {
+            // 和 run 检查标志在同一把锁内，线程不会在检查之后、wait 之前错过通知
+            let _wheel = self.wheel.lock().unwrap();
+            self.shutdown.store(true, Ordering::Release);
+            self.cvar.notify_all();
+        }
+        let Some(background) = self.background.lock().unwrap().take() else {
+            return;
+        };
+        // executor 可能是在驱动线程调用的 waker 里被 drop 的，不能等待自己
+        if background.thread().id() != thread::current().id() {
+            background.join().unwrap();
+        }
+    }
+
+    /// 后台线程是否还在运行；虚拟时钟没有后台线程
+    pub(crate) fn has_background_thread(&self) -> bool {
+        self.background
+            .lock()
+            .unwrap()
+            .as_ref()
+            .is_some_and(|background| !background.is_finished())
+    }
+
+    /// 全局默认的定时器驱动，使用真实时钟
+    pub(crate) fn global() -> Arc<TimerDriver> {
+        static DRIVER: OnceLock<Arc<TimerDriver>> = OnceLock::new();
+        DRIVER
+            .get_or_init(|| TimerDriver::new(Arc::new(SystemClock)))
+            .clone()
+    }
+
+    /// 当前线程的定时器驱动：进入了某个驱动时返回它，否则返回全局驱动
+    pub(crate) fn current() -> Arc<TimerDriver> {
+        CURRENT
+            .with_borrow(|current| current.clone())
+            .unwrap_or_else(TimerDriver::global)
+    }
+
+    /// 让当前线程使用这个驱动，直到返回的 guard 被 drop
+    pub(crate) fn enter(self: &Arc<Self>) -> EnterGuard {
+        let previous = CURRENT.replace(Some(self.clone()));
+        EnterGuard { previous }
+    }
+
+    pub(crate) fn now(&self) -> Instant {
+        self.clock.now()
+    }
+
+    /// 是否使用虚拟时钟
+    pub(crate) fn is_paused(&self) -> bool {
+        self.clock.as_mock().is_some()
+    }
+
+    /// 虚拟时钟：直接跳到下一个定时器的到期时刻，没有定时器时返回 `false`
+    ///
+    /// 这就是"所有任务都空闲时跳到下一个 deadline"：
+    /// 既然没有任务可以运行，等待真实时间流逝没有意义。
+    pub(crate) fn advance_to_next(&self) -> bool {
+        let clock = self.clock.as_mock().expect("只有虚拟时钟可以手动推进");
+        loop {
+            let Some(next) = self.wheel.lock().unwrap().next_expiration() else {
+                return false;
+            };
+            clock.advance_to(self.start + Duration::from_millis(next));
+            // 高层槽位的起点只是降级时刻，不一定有定时器到期，继续往后推进
+            if self.process() > 0 {
+                return true;
+            }
+        }
+    }
+
+    /// 按时钟的当前时刻推进时间轮，唤醒到期的定时器，返回到期的数量
+    fn process(&self) -> usize {
+        let expired = self.wheel.lock().unwrap().advance(self.now_tick());
+        let count = expired.len();
+        expired.into_iter().for_each(fire);
+        count
+    }
+
+    /// 把时刻换算成 tick，向上取整，保证定时器不会提前到期
+    fn tick_for(&self, deadline: Instant) -> u64 {
+        let since_start = deadline.saturating_duration_since(self.start);
+        since_start.as_nanos().div_ceil(1_000_000) as u64
+    }
+
+    fn now_tick(&self) -> u64 {
+        self.now().saturating_duration_since(self.start).as_millis() as u64
+    }
+
+    /// 注册一个定时器：到期时设置 `completed` 并调用保存的 waker
+    pub(crate) fn register(
+        &self,
+        deadline: Instant,
+        shared_state: Arc<Mutex<SharedState>>,
+    ) -> TimerKey {
+        let generation = shared_state.lock().unwrap().generation;
+        let mut wheel = self.wheel.lock().unwrap();
+        let key = wheel.next_key();
+        self.insert(wheel, key, deadline, shared_state, generation);
+        key
+    }
+
+    /// 用新的到期时刻重新登记定时器，无论它是否已经到期
+    ///
+    /// 调用方要先增加 `SharedState::generation`：旧的登记可能已经被取出、正要在锁外唤醒，
+    /// `fire` 靠 generation 认出它并忽略。
+    pub(crate) fn reset(
+        &self,
+        key: TimerKey,
+        deadline: Instant,
+        shared_state: Arc<Mutex<SharedState>>,
+    ) {
+        let generation = shared_state.lock().unwrap().generation;
+        let mut wheel = self.wheel.lock().unwrap();
+        wheel.remove(key);
+        self.insert(wheel, key, deadline, shared_state, generation);
+    }
+
+    /// 取消定时器：从时间轮中移除，之后不会再唤醒它的 waker
+    pub(crate) fn cancel(&self, key: TimerKey) {
+        self.wheel.lock().unwrap().remove(key);
+    }
+
+    /// 时间轮中还没有到期的定时器数量
+    pub(crate) fn pending(&self) -> usize {
+        self.wheel.lock().unwrap().entries.len()
+    }
+
+    fn insert(
+        &self,
+        mut wheel: MutexGuard<'_, Wheel>,
+        key: TimerKey,
+        deadline: Instant,
+        shared_state: Arc<Mutex<SharedState>>,
+        generation: u64,
+    ) {
+        let entry = Entry {
+            when: self.tick_for(deadline),
+            shared_state,
+            generation,
+            location: (0, 0),
+        };
+        let expired = wheel.insert(key, entry);
+        drop(wheel);
+        match expired {
+            Some(entry) => fire(entry),
+            // 新的定时器可能比后台线程正在等待的时刻更早，叫醒它重新计算
+            None => self.cvar.notify_one(),
+        }
+    }
+
+    fn run(&self) {
+        let mut wheel = self.wheel.lock().unwrap();
+        loop {
+            if self.shutdown.load(Ordering::Acquire) {
+                return;
+            }
+            let now = self.now_tick();
+            let expired = wheel.advance(now);
+            if !expired.is_empty() {
+                // 调用 waker 时不持有时间轮的锁
+                drop(wheel);
+                expired.into_iter().for_each(fire);
+                wheel = self.wheel.lock().unwrap();
+                continue;
+            }
+            wheel = match wheel.next_expiration() {
+                Some(next) => {
+                    let timeout = Duration::from_millis(next - now);
+                    self.cvar.wait_timeout(wheel, timeout).unwrap().0
+                }
+                None => self.cvar.wait(wheel).unwrap(),
+            };
+        }
+    }
+}
+
+/// 离开 `TimerDriver::enter` 时恢复之前的驱动
+pub(crate) struct EnterGuard {
+    previous: Option<Arc<TimerDriver>>,
+}
+
+impl Drop for EnterGuard {
+    fn drop(&mut self) {
+        CURRENT.set(self.previous.take());
+    }
+}
+
+/// 定时器到期：和原来后台线程 sleep 结束后做的事情一样
+///
+/// 持有 `SharedState` 的锁调用 waker：future 在 drop 时也要获取这个锁，
+/// 所以 drop 返回之后，不会再有针对它的唤醒。
+/// 取出之后、调用这里之前被 reset 过的登记已经过时，什么也不做。
+fn fire(entry: Entry) {
+    let mut state = entry.shared_state.lock().unwrap();
+    if state.generation != entry.generation {
+        return;
+    }
+    state.completed = true;
+    if let Some(waker) = state.waker.take() {
+        waker.wake();
+    }
+}
+
+/// 基于时间轮的 sleep future
+///
+/// 和 `AsyncTimerFuture` 一样使用 `SharedState` 保存 waker，
+/// 区别是不会为每个定时器创建线程，只在时间轮的槽里登记一次。
+/// drop 时从时间轮中取消登记。
+pub struct Sleep {
+    shared_state: Arc<Mutex<SharedState>>,
+    driver: Arc<TimerDriver>,
+    key: TimerKey,
+}
+
+/// 当前时刻：使用当前线程的定时器驱动的时钟（可能是虚拟时钟）
+pub fn now() -> Instant {
+    TimerDriver::current().now()
+}
+
+/// 等待一段时间
+pub fn sleep(duration: Duration) -> Sleep {
+    sleep_until(now() + duration)
+}
+
+/// 等待到指定时刻
+pub fn sleep_until(deadline: Instant) -> Sleep {
+    let driver = TimerDriver::current();
+    let shared_state = Arc::new(Mutex::new(SharedState {
+        completed: false,
+        waker: None,
+        generation: 0,
+    }));
+    let key = driver.register(deadline, shared_state.clone());
+    Sleep {
+        shared_state,
+        driver,
+        key,
+    }
+}
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = self.shared_state.lock().unwrap();
+        if state.completed {
+            Poll::Ready(())
+        } else {
+            state.waker = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
+
+impl Drop for Sleep {
+    fn drop(&mut self) {
+        self.driver.cancel(self.key);
+        self.shared_state.lock().unwrap().waker = None;
+    }
+}
+
+/// 测试时间轮：一万个定时器只占用一个后台线程
+pub fn test_timer_wheel() {
+    println!("\n=== 时间轮示例：一万个定时器共享一个驱动线程 ===");
+
+    const TIMERS: u64 = 10_000;
+    let executor = SimpleExecutor::new();
+
+    let start = Instant::now();
+    let handles: Vec<_> = (0..TIMERS)
+        .map(|i| {
+            // 到期时刻分布在 1ms ~ 1000ms 之间
+            let duration = Duration::from_millis(1 + i % 1000);
+            executor.spawn(async move {
+                sleep(duration).await;
+                duration
+            })
+        })
+        .collect();
+
+    let longest = executor.block_on(async move {
+        let mut longest = Duration::ZERO;
+        for handle in handles {
+            longest = longest.max(handle.await.unwrap());
+        }
+        longest
+    });
+    let elapsed = start.elapsed();
+
+    println!("{TIMERS} 个定时器全部到期，最长的定时器: {:?}", longest);
+    println!("总耗时: {:?}", elapsed);
+    assert!(elapsed >= longest);
+
+    println!("\n关键点：");
+    println!("- 每个定时器只是时间轮槽位里的一项，不再对应一个 OS 线程");
+    println!("- 驱动线程只在下一个槽位到期时醒来，一次唤醒这个槽里的所有定时器");
+    println!("- AsyncTimerFuture 也注册在同一个时间轮上");
+}
@@ -0,0 +1,515 @@
+use std::collections::{BTreeMap, HashMap};
+use std::mem::{self, ManuallyDrop};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, LazyLock, Mutex};
+use std::task::{RawWaker, RawWakerVTable, Waker};
+use std::time::Duration;
+
+use super::custom_waker::AsyncTimerFuture;
+use super::quiet_panic::panic_message;
+use super::simple_executor::SimpleExecutor;
+use super::waker::WakerKind;
+
+/// 所有被追踪的 waker 的登记表
+///
+/// `TrackedWaker` 的 data 指针不是真正的指针，而是登记表中的编号。
+/// 编号从不复用，所以 waker 被 drop 之后再使用它时，一定找不到对应的记录，
+/// 可以可靠地报告 use-after-drop，而不是访问已经释放的内存。
+static REGISTRY: LazyLock<Mutex<Registry>> = LazyLock::new(|| {
+    Mutex::new(Registry {
+        next_id: 1,
+        live: HashMap::new(),
+        stats: HashMap::new(),
+    })
+});
+
+struct Registry {
+    next_id: usize,
+    live: HashMap<usize, Entry>,
+    // 每个 tracker 的统计，tracker 被 drop 时移除
+    stats: HashMap<usize, HashMap<String, WakerStats>>,
+}
+
+/// 一个还活着的被追踪的 waker
+struct Entry {
+    tracker: usize,
+    task: String,
+    // 被包装的 waker，每个 TrackedWaker 拥有它的一个引用；放在 Arc 里，这样可以在释放锁之后
+    // 再调用它，被包装的 waker 本身也是 TrackedWaker 时不会重入登记表的锁
+    inner: Arc<Waker>,
+    // 有值时，每次调用被包装的 vtable 都检查它的引用计数
+    refcount: Option<RefCount>,
+}
+
+impl Registry {
+    fn insert(&mut self, tracker: usize, task: String, inner: Waker, refcount: Option<RefCount>) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.live.insert(
+            id,
+            Entry {
+                tracker,
+                task,
+                inner: Arc::new(inner),
+                refcount,
+            },
+        );
+        id
+    }
+
+    /// 记录一次操作；tracker 已经被 drop 时不再统计
+    fn record(&mut self, tracker: usize, task: &str, op: impl FnOnce(&mut WakerStats)) {
+        if let Some(stats) = self.stats.get_mut(&tracker) {
+            op(stats.entry(task.to_string()).or_default());
+        }
+    }
+
+    /// 找不到记录说明 waker 已经被 drop（或者已经被 `wake` 消耗）
+    fn expect_live(&self, id: usize, op: &str) -> &Entry {
+        match self.live.get(&id) {
+            Some(entry) => entry,
+            None => use_after_drop(id, op),
+        }
+    }
+
+    fn remove(&mut self, id: usize, op: &str) -> Entry {
+        match self.live.remove(&id) {
+            Some(entry) => entry,
+            None => use_after_drop(id, op),
+        }
+    }
+}
+
+fn use_after_drop(id: usize, op: &str) -> ! {
+    panic!("TrackedWaker #{id}: drop 之后又调用了 {op}（use-after-drop / double-free）")
+}
+
+fn registry() -> std::sync::MutexGuard<'static, Registry> {
+    // 某个 vtable 回调 panic 时锁可能被毒化，登记表本身仍然是一致的
+    REGISTRY.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+fn id_of(ptr: *const ()) -> usize {
+    ptr as usize
+}
+
+/// 被包装的 waker 的引用计数，用来检查它的 vtable 有没有算错
+///
+/// data 是 `Arc::into_raw` 得到的指针时用 `RefCount::arc::<T>()`。
+#[derive(Clone, Copy)]
+pub struct RefCount {
+    count: unsafe fn(*const ()) -> usize,
+    retain: unsafe fn(*const ()),
+    release: unsafe fn(*const ()),
+}
+
+unsafe fn arc_count<T>(data: *const ()) -> usize {
+    let arc = ManuallyDrop::new(unsafe { Arc::from_raw(data as *const T) });
+    Arc::strong_count(&arc)
+}
+
+unsafe fn arc_retain<T>(data: *const ()) {
+    unsafe { Arc::increment_strong_count(data as *const T) };
+}
+
+unsafe fn arc_release<T>(data: *const ()) {
+    unsafe { Arc::decrement_strong_count(data as *const T) };
+}
+
+impl RefCount {
+    pub fn arc<T>() -> Self {
+        Self {
+            count: arc_count::<T>,
+            retain: arc_retain::<T>,
+            release: arc_release::<T>,
+        }
+    }
+}
+
+/// 调用被包装的 vtable 之前的引用计数
+///
+/// 检查期间持有一个额外的引用：vtable 多减了引用计数时，data 也不会在检查完之前被释放。
+/// 假设检查期间没有其他线程释放同一个 data 的其他引用（`SimpleExecutor` 在同一个线程上 poll 和唤醒）。
+struct Probe {
+    refcount: RefCount,
+    data: *const (),
+    before: usize,
+}
+
+impl Probe {
+    fn start(refcount: Option<RefCount>, inner: &Waker) -> Option<Self> {
+        let refcount = refcount?;
+        let data = inner.data();
+        unsafe { (refcount.retain)(data) };
+        let before = unsafe { (refcount.count)(data) };
+        Some(Self { refcount, data, before })
+    }
+
+    /// 检查引用计数至少变化了 `min_delta`（clone 是 +1，wake_by_ref 是 0，wake 和 drop 是 -1）
+    fn finish(self, id: usize, op: &str, min_delta: isize) {
+        let after = unsafe { (self.refcount.count)(self.data) };
+        let expected = self.before as isize + min_delta;
+        if (after as isize) < expected {
+            // 不释放额外的引用：它正好补上 vtable 少算的那一个，之后不会 use-after-free
+            panic!(
+                "TrackedWaker #{id}: 被包装的 vtable 在 {op} 中把引用计数从 {} 变成了 {after}，至少应该是 {expected}（忘了 mem::forget？）",
+                self.before
+            );
+        }
+        unsafe { (self.refcount.release)(self.data) };
+    }
+}
+
+unsafe fn clone_tracked(ptr: *const ()) -> RawWaker {
+    let (tracker, task, inner, refcount) = {
+        let mut registry = registry();
+        let entry = registry.expect_live(id_of(ptr), "clone");
+        let (tracker, task, inner, refcount) =
+            (entry.tracker, entry.task.clone(), entry.inner.clone(), entry.refcount);
+        registry.record(tracker, &task, |stats| stats.clones += 1);
+        (tracker, task, inner, refcount)
+    };
+    // 在锁外克隆被包装的 waker
+    let probe = Probe::start(refcount, &inner);
+    let clone = Waker::clone(&inner);
+    if let Some(probe) = probe {
+        // 克隆出来的 waker 指向别的 data 时，原来的引用计数不变
+        let min_delta = if clone.data() == inner.data() { 1 } else { 0 };
+        probe.finish(id_of(ptr), "clone", min_delta);
+    }
+    let id = registry().insert(tracker, task, clone, refcount);
+    RawWaker::new(id as *const (), &TRACKED_VTABLE)
+}
+
+unsafe fn wake_tracked(ptr: *const ()) {
+    let entry = {
+        let mut registry = registry();
+        let entry = registry.remove(id_of(ptr), "wake");
+        registry.record(entry.tracker, &entry.task, |stats| stats.wakes += 1);
+        entry
+    };
+    let probe = Probe::start(entry.refcount, &entry.inner);
+    match Arc::try_unwrap(entry.inner) {
+        Ok(inner) => {
+            inner.wake();
+            if let Some(probe) = probe {
+                probe.finish(id_of(ptr), "wake", -1);
+            }
+        }
+        // 另一个线程正在通过同一个记录调用它（只有 vtable 用错时才会发生）
+        Err(inner) => {
+            inner.wake_by_ref();
+            if let Some(probe) = probe {
+                probe.finish(id_of(ptr), "wake", 0);
+            }
+        }
+    }
+}
+
+unsafe fn wake_by_ref_tracked(ptr: *const ()) {
+    let (inner, refcount) = {
+        let mut registry = registry();
+        let entry = registry.expect_live(id_of(ptr), "wake_by_ref");
+        let (tracker, task, inner, refcount) =
+            (entry.tracker, entry.task.clone(), entry.inner.clone(), entry.refcount);
+        registry.record(tracker, &task, |stats| stats.wakes += 1);
+        (inner, refcount)
+    };
+    let probe = Probe::start(refcount, &inner);
+    inner.wake_by_ref();
+    if let Some(probe) = probe {
+        probe.finish(id_of(ptr), "wake_by_ref", 0);
+    }
+}
+
+unsafe fn drop_tracked(ptr: *const ()) {
+    // 已经在 panic 展开中（比如刚刚报告了 use-after-drop），再 panic 会直接 abort
+    if std::thread::panicking() && !registry().live.contains_key(&id_of(ptr)) {
+        return;
+    }
+    let entry = {
+        let mut registry = registry();
+        let entry = registry.remove(id_of(ptr), "drop");
+        registry.record(entry.tracker, &entry.task, |stats| stats.drops += 1);
+        entry
+    };
+    let probe = Probe::start(entry.refcount, &entry.inner);
+    let min_delta = match Arc::try_unwrap(entry.inner) {
+        Ok(inner) => {
+            drop(inner);
+            -1
+        }
+        Err(inner) => {
+            drop(inner);
+            0
+        }
+    };
+    if let Some(probe) = probe {
+        probe.finish(id_of(ptr), "drop", min_delta);
+    }
+}
+
+const TRACKED_VTABLE: RawWakerVTable = RawWakerVTable::new(
+    clone_tracked,
+    wake_tracked,
+    wake_by_ref_tracked,
+    drop_tracked,
+);
+
+/// 一个任务的 waker 统计
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WakerStats {
+    /// 由 executor 创建（`wrap`）的 waker 数量
+    pub created: usize,
+    pub clones: usize,
+    pub drops: usize,
+    /// `wake` 和 `wake_by_ref` 的总次数
+    pub wakes: usize,
+}
+
+/// 没有被 drop 的 waker
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LeakedWaker {
+    pub id: usize,
+    pub task: String,
+}
+
+/// `WakerTracker::report` 的结果
+#[derive(Debug, Clone, Default)]
+pub struct WakerReport {
+    pub tasks: BTreeMap<String, WakerStats>,
+    pub leaked: Vec<LeakedWaker>,
+}
+
+impl WakerReport {
+    pub fn print(&self) {
+        for (task, stats) in &self.tasks {
+            println!(
+                "  {task}: 创建 {}，clone {}，drop {}，wake {}",
+                stats.created, stats.clones, stats.drops, stats.wakes
+            );
+        }
+        for leaked in &self.leaked {
+            println!("  泄漏: TrackedWaker #{} ({})", leaked.id, leaked.task);
+        }
+    }
+}
+
+/// 调试用的 waker 检查器：包装任意 waker，统计每个任务的 clone/drop/wake 次数
+///
+/// - 被包装的 waker 仍然负责真正的唤醒，`TrackedWaker` 的每个回调都委托给被包装的 vtable
+/// - 关闭时（`report`）还活着的 waker 被报告为泄漏
+/// - drop 之后再 clone/wake/drop 同一个 waker 会立刻 panic
+/// - 用 `wrap_raw` 包装时，每次委托前后检查被包装的引用计数：clone 没有加一、
+///   wake_by_ref 减了一（比如 `SimpleExecutor` 手写的 vtable 忘了 `mem::forget`）会立刻 panic
+///
+/// 只在 debug 构建中可用（见 `SimpleExecutor::with_waker_tracking`）。
+pub struct WakerTracker {
+    id: usize,
+}
+
+static NEXT_TRACKER: AtomicUsize = AtomicUsize::new(1);
+
+impl WakerTracker {
+    pub fn new() -> Self {
+        let id = NEXT_TRACKER.fetch_add(1, Ordering::Relaxed);
+        registry().stats.insert(id, HashMap::new());
+        Self { id }
+    }
+
+    /// 包装一个 waker，`task` 是统计时使用的任务名
+    ///
+    /// 不知道它的引用计数怎么存，只检查 clone/drop 是否配对和 use-after-drop。
+    pub fn wrap(&self, waker: Waker, task: impl Into<String>) -> Waker {
+        self.insert(waker, None, task.into())
+    }
+
+    /// 包装一个 `RawWaker`，并在每次调用它的 vtable 时检查 `refcount`
+    ///
+    /// # Safety
+    ///
+    /// `raw` 必须满足 `Waker::from_raw` 的要求，`refcount` 必须和它的 data 指针的类型一致。
+    pub unsafe fn wrap_raw(&self, raw: RawWaker, refcount: RefCount, task: impl Into<String>) -> Waker {
+        let inner = unsafe { Waker::from_raw(raw) };
+        self.insert(inner, Some(refcount), task.into())
+    }
+
+    fn insert(&self, inner: Waker, refcount: Option<RefCount>, task: String) -> Waker {
+        let mut registry = registry();
+        registry.record(self.id, &task, |stats| stats.created += 1);
+        let id = registry.insert(self.id, task, inner, refcount);
+        unsafe { Waker::from_raw(RawWaker::new(id as *const (), &TRACKED_VTABLE)) }
+    }
+
+    /// 当前的统计，以及还没有被 drop 的 waker
+    pub fn report(&self) -> WakerReport {
+        let registry = registry();
+        let tasks = registry.stats[&self.id]
+            .iter()
+            .map(|(task, stats)| (task.clone(), *stats))
+            .collect();
+        let mut leaked: Vec<_> = registry
+            .live
+            .iter()
+            .filter(|(_, entry)| entry.tracker == self.id)
+            .map(|(&id, entry)| LeakedWaker {
+                id,
+                task: entry.task.clone(),
+            })
+            .collect();
+        leaked.sort_by_key(|leaked| leaked.id);
+        WakerReport { tasks, leaked }
+    }
+
+    /// 有泄漏时打印报告，返回泄漏的数量
+    pub fn report_leaks(&self) -> usize {
+        let report = self.report();
+        if !report.leaked.is_empty() {
+            eprintln!("[TrackedWaker] 检测到 {} 个泄漏的 waker:", report.leaked.len());
+            for leaked in &report.leaked {
+                eprintln!("  TrackedWaker #{} ({})", leaked.id, leaked.task);
+            }
+        }
+        report.leaked.len()
+    }
+}
+
+impl Drop for WakerTracker {
+    fn drop(&mut self) {
+        // 还活着的 waker 的记录要留着，之后使用它们时才能检查；统计不再需要了
+        registry().stats.remove(&self.id);
+    }
+}
+
+// 有错误的 vtable，data 指向 Arc<()>：clone 和 wake_by_ref 都忘了 mem::forget，
+// 和 SimpleExecutor 的 clone_waker / wake_by_ref_waker 去掉 mem::forget 之后一样
+unsafe fn clone_forgetful(ptr: *const ()) -> RawWaker {
+    let arc = unsafe { Arc::<()>::from_raw(ptr) };
+    let clone = Arc::clone(&arc);
+    // 错误：这里应该 mem::forget(arc)，否则引用计数又被减了回去
+    RawWaker::new(Arc::into_raw(clone), &FORGETFUL_VTABLE)
+}
+
+unsafe fn wake_forgetful(ptr: *const ()) {
+    drop(unsafe { Arc::<()>::from_raw(ptr) });
+}
+
+unsafe fn wake_by_ref_forgetful(ptr: *const ()) {
+    // 错误：wake_by_ref 不消耗 waker，这个 Arc 应该被 mem::forget
+    drop(unsafe { Arc::<()>::from_raw(ptr) });
+}
+
+unsafe fn drop_forgetful(ptr: *const ()) {
+    drop(unsafe { Arc::<()>::from_raw(ptr) });
+}
+
+const FORGETFUL_VTABLE: RawWakerVTable = RawWakerVTable::new(
+    clone_forgetful,
+    wake_forgetful,
+    wake_by_ref_forgetful,
+    drop_forgetful,
+);
+
+/// 测试 TrackedWaker：检查 executor 的 waker 引用计数
+///
+/// 1. 在 `SimpleExecutor` 的两种 waker 实现上开启追踪，任务结束后没有泄漏
+/// 2. 被 `mem::forget` 的 waker 被报告为泄漏
+/// 3. 错误的 vtable 实现（clone 时忘记增加引用计数）会导致 use-after-drop，立刻 panic
+/// 4. `wrap_raw` 检查被包装的 vtable：clone 或 wake_by_ref 忘了 `mem::forget` 时立刻 panic
+pub fn test_tracked_waker() {
+    println!("\n=== TrackedWaker 示例：检查 waker 的引用计数 ===");
+
+    for kind in [WakerKind::Wake, WakerKind::RawVTable] {
+        let executor = SimpleExecutor::new_paused()
+            .with_waker_kind(kind)
+            .with_waker_tracking();
+        let handles: Vec<_> = (1..=3u64)
+            .map(|i| {
+                executor.spawn(async move {
+                    AsyncTimerFuture::new(Duration::from_millis(100 * i)).await;
+                })
+            })
+            .collect();
+        executor.block_on(async {
+            for handle in handles {
+                handle.await.unwrap();
+            }
+        });
+
+        let report = executor.waker_report().unwrap();
+        println!("\n{:?}:", kind);
+        report.print();
+        assert!(report.leaked.is_empty(), "{:?}: {:?}", kind, report.leaked);
+        for stats in report.tasks.values() {
+            // 每个 waker 最终都被 drop 或者被 wake 消耗
+            assert!(stats.created + stats.clones >= stats.drops);
+        }
+        // 三个定时器各唤醒对应的任务一次
+        let task_wakes: usize = report
+            .tasks
+            .iter()
+            .filter(|(task, _)| task.starts_with("task-"))
+            .map(|(_, stats)| stats.wakes)
+            .sum();
+        assert_eq!(task_wakes, 3);
+    }
+
+    // 泄漏：被 forget 的 waker 永远不会被 drop
+    let tracker = WakerTracker::new();
+    let waker = tracker.wrap(Waker::noop().clone(), "leaky");
+    mem::forget(waker.clone());
+    drop(waker);
+    let report = tracker.report();
+    println!("\nmem::forget 一个 clone:");
+    report.print();
+    assert_eq!(report.leaked.len(), 1);
+    assert_eq!(report.tasks["leaky"].clones, 1);
+    assert_eq!(report.tasks["leaky"].drops, 1);
+
+    // use-after-drop：用同一个 data 指针构造了第二个 waker，
+    // 相当于 vtable 的 clone 没有增加引用计数
+    let tracker = WakerTracker::new();
+    let message = panic_message(|| {
+        let waker = tracker.wrap(Waker::noop().clone(), "buggy");
+        let alias = unsafe { Waker::from_raw(RawWaker::new(waker.data(), waker.vtable())) };
+        drop(waker);
+        alias.wake_by_ref();
+    });
+    println!("\n错误的 clone 实现: {}", message.as_deref().unwrap_or("没有 panic"));
+    assert!(message.unwrap().contains("use-after-drop"));
+
+    // double-free：两个 waker 共用一份引用计数，第二次 drop 时 panic
+    let message = panic_message(|| {
+        let waker = tracker.wrap(Waker::noop().clone(), "buggy");
+        let alias = unsafe { Waker::from_raw(RawWaker::new(waker.data(), waker.vtable())) };
+        drop(waker);
+        drop(alias);
+    });
+    println!("重复 drop: {}", message.as_deref().unwrap_or("没有 panic"));
+    assert!(message.unwrap().contains("drop"));
+    assert!(tracker.report().leaked.is_empty());
+
+    // 被包装的 vtable 算错了引用计数
+    let shared = Arc::new(());
+    for op in ["clone", "wake_by_ref"] {
+        let message = panic_message(|| {
+            let raw = RawWaker::new(Arc::into_raw(shared.clone()), &FORGETFUL_VTABLE);
+            let waker = unsafe { tracker.wrap_raw(raw, RefCount::arc::<()>(), "forgetful") };
+            if op == "clone" {
+                drop(waker.clone());
+            } else {
+                waker.wake_by_ref();
+            }
+        });
+        println!("{op} 忘了 mem::forget: {}", message.as_deref().unwrap_or("没有 panic"));
+        assert!(message.unwrap().contains(&format!("在 {op} 中")));
+        // 检查时多持有的引用补上了少算的那一个：Arc 没有被提前释放，最后只剩 shared
+        assert_eq!(Arc::strong_count(&shared), 1);
+    }
+
+    println!("\n关键点：");
+    println!("- TrackedWaker 包装任意 waker，被包装的 waker 负责真正的唤醒");
+    println!("- data 指针是永不复用的编号，drop 之后再使用一定会被发现");
+    println!("- wrap_raw 在每次委托前后检查被包装的引用计数，手写 vtable 忘了 mem::forget 会被发现");
+    println!("- executor 关闭时还活着的 waker 被报告为泄漏");
+}
@@ -0,0 +1,134 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::task::{Context, Poll, Wake, Waker};
+use std::time::Duration;
+
+use super::custom_waker::AsyncTimerFuture;
+use super::simple_executor::SimpleExecutor;
+
+/// executor 构造 waker 的方式
+///
+/// - `Wake`：为调度对象实现 `std::task::Wake`，用 `Waker::from(Arc<T>)` 构造，
+///   引用计数由标准库管理，不需要 unsafe
+/// - `RawVTable`：手写 `RawWakerVTable` 的四个回调，自己用 `Arc::from_raw`/`forget`
+///   维护引用计数，展示 waker 的底层结构
+///
+/// 两种方式唤醒行为完全相同，默认使用 `Wake`。
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum WakerKind {
+    #[default]
+    Wake,
+    RawVTable,
+}
+
+/// 记录被唤醒次数的 waker
+pub(crate) struct CountingWaker {
+    wakes: AtomicUsize,
+}
+
+impl CountingWaker {
+    pub(crate) fn new() -> Arc<Self> {
+        Arc::new(Self {
+            wakes: AtomicUsize::new(0),
+        })
+    }
+
+    pub(crate) fn wakes(&self) -> usize {
+        self.wakes.load(Ordering::SeqCst)
+    }
+}
+
+impl Wake for CountingWaker {
+    fn wake(self: Arc<Self>) {
+        self.wakes.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+/// 前 `yields` 次被 poll 时返回 `Pending`，并通过 `waker` 重新调度自己
+///
+/// `by_ref` 为 `true` 时调用 `wake_by_ref`，否则克隆一份 waker 交给另一个线程调用 `wake`，
+/// 这样 `clone`/`wake`/`wake_by_ref`/`drop` 四个回调都会被用到。
+struct YieldNow {
+    yields: usize,
+    by_ref: bool,
+    polls: Arc<AtomicUsize>,
+}
+
+impl Future for YieldNow {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        self.polls.fetch_add(1, Ordering::SeqCst);
+        if self.yields == 0 {
+            return Poll::Ready(());
+        }
+        self.yields -= 1;
+        if self.by_ref {
+            cx.waker().wake_by_ref();
+        } else {
+            let waker = cx.waker().clone();
+            std::thread::spawn(move || waker.wake());
+        }
+        Poll::Pending
+    }
+}
+
+/// 在指定的 waker 实现下运行同一组任务，返回每个任务被 poll 的次数和经过的虚拟时间
+fn run_with(kind: WakerKind) -> (Vec<usize>, Duration) {
+    let executor = SimpleExecutor::new_paused().with_waker_kind(kind);
+    let start = executor.now();
+
+    let tasks = [(3, true), (2, false), (0, true)];
+    let counters: Vec<_> = tasks
+        .iter()
+        .map(|_| Arc::new(AtomicUsize::new(0)))
+        .collect();
+    let handles: Vec<_> = tasks
+        .iter()
+        .zip(&counters)
+        .map(|(&(yields, by_ref), polls)| {
+            executor.spawn(YieldNow {
+                yields,
+                by_ref,
+                polls: polls.clone(),
+            })
+        })
+        .collect();
+
+    executor.block_on(async {
+        // 主 future 的 waker 由定时器调用
+        AsyncTimerFuture::new(Duration::from_millis(100)).await;
+        for handle in handles {
+            handle.await.unwrap();
+        }
+    });
+
+    let polls = counters.iter().map(|c| c.load(Ordering::SeqCst)).collect();
+    (polls, executor.now() - start)
+}
+
+/// 测试两种 waker 实现：同一组任务在 `Wake` 和 `RawVTable` 下行为完全相同
+pub fn test_waker_kinds() {
+    println!("\n=== Waker 示例：std::task::Wake 与手写 RawWakerVTable ===");
+
+    let by_wake = run_with(WakerKind::Wake);
+    let by_vtable = run_with(WakerKind::RawVTable);
+    println!("Wake:      poll 次数 {:?}，虚拟时间 {:?}", by_wake.0, by_wake.1);
+    println!("RawVTable: poll 次数 {:?}，虚拟时间 {:?}", by_vtable.0, by_vtable.1);
+
+    // 每个任务被 poll 的次数 = 让出的次数 + 1
+    assert_eq!(by_wake.0, vec![4, 3, 1]);
+    assert_eq!(by_wake, by_vtable);
+    assert_eq!(by_wake.1, Duration::from_millis(100));
+
+    // clone 出来的 waker 唤醒的是同一个任务
+    let waker = Waker::from(CountingWaker::new());
+    assert!(waker.will_wake(&waker.clone()));
+
+    println!("\n关键点：");
+    println!("- 实现 Wake trait 后，Waker::from(Arc<T>) 自动生成 vtable，不需要 unsafe");
+    println!("- 手写 vtable 需要自己保证 clone/wake/drop 时引用计数正确");
+    println!("- 两种方式对 executor 来说没有区别：wake 都是把任务放回就绪队列");
+}
@@ -2,6 +2,18 @@ mod examples;
 
 #[tokio::main(flavor = "current_thread")]
 async fn main() {
+    // 子命令：`cargo run -- layout` 只打印各个 future 的大小和对齐
+    if let Some(command) = std::env::args().nth(1) {
+        match command.as_str() {
+            "layout" => examples::layout::print_report(),
+            other => {
+                eprintln!("未知的子命令: {other}（可用的子命令: layout）");
+                std::process::exit(2);
+            }
+        }
+        return;
+    }
+
     // 示例 1: 基本的 Future 实现
     examples::basic_future::test_basic_future().await;
 
@@ -12,7 +24,7 @@ async fn main() {
     // 示例 3: 顺序执行
     examples::greet::test_sequential().await;
 
-    // 示例 4: 并发执行（即使使用 current_thread，spawn 创建的任务也会并发执行）
+    // 示例 4: 并发执行（即使使用 current_thread，spawn 创建的任务也会并发执行），以及用 Semaphore 限制并发数
     examples::greet::test_concurrent().await;
 
     // 示例 5: SimpleCoroutine（编译器生成的等价代码）
@@ -38,4 +50,196 @@ async fn main() {
         examples::simple_executor::test_simple_executor();
     });
     handle.join().unwrap();
+
+    // 示例 10: SimpleExecutor 就绪队列（spawn 多个任务并发执行）
+    let handle = std::thread::spawn(|| {
+        examples::simple_executor::test_spawn_many_timers();
+    });
+    handle.join().unwrap();
+
+    // 示例 11: ThreadPoolExecutor（多线程 work-stealing executor）
+    let handle = std::thread::spawn(|| {
+        examples::thread_pool_executor::test_thread_pool_executor();
+    });
+    handle.join().unwrap();
+
+    // 示例 12: 时间轮（所有定时器共享一个驱动线程）
+    let handle = std::thread::spawn(|| {
+        examples::timer::test_timer_wheel();
+    });
+    handle.join().unwrap();
+
+    // 示例 13: AsyncTimerFuture 的取消和重置
+    let handle = std::thread::spawn(|| {
+        examples::custom_waker::test_timer_cancellation();
+    });
+    handle.join().unwrap();
+
+    // 示例 14: epoll reactor（SimpleExecutor 空闲时在 epoll_wait 上等待）
+    let handle = std::thread::spawn(|| {
+        examples::reactor::test_reactor();
+    });
+    handle.join().unwrap();
+
+    // 示例 15: 运行在 SimpleExecutor 上的 TCP echo 服务器
+    let handle = std::thread::spawn(|| {
+        examples::net::test_echo_server();
+    });
+    handle.join().unwrap();
+
+    // 示例 16: std::task::Wake 与手写 RawWakerVTable 的 waker 行为一致
+    let handle = std::thread::spawn(|| {
+        examples::waker::test_waker_kinds();
+    });
+    handle.join().unwrap();
+
+    // 示例 17: TrackedWaker 检查 waker 的泄漏、use-after-drop 和 vtable 的引用计数（只在 debug 构建中）
+    #[cfg(debug_assertions)]
+    {
+        let handle = std::thread::spawn(|| {
+            examples::tracked_waker::test_tracked_waker();
+        });
+        handle.join().unwrap();
+    }
+
+    // 示例 18: 丢失唤醒检测（返回 Pending 却不注册 waker 的 future）
+    let handle = std::thread::spawn(|| {
+        examples::lost_wakeup::test_lost_wakeup();
+    });
+    handle.join().unwrap();
+
+    // 示例 19: AsyncMutex（公平、可取消的异步互斥锁）
+    let handle = std::thread::spawn(|| {
+        examples::sync::mutex::test_async_mutex();
+    });
+    handle.join().unwrap();
+
+    // 示例 20: AsyncRwLock（公平、可取消的异步读写锁）
+    let handle = std::thread::spawn(|| {
+        examples::sync::rwlock::test_async_rwlock();
+    });
+    handle.join().unwrap();
+
+    // 示例 21: oneshot channel（普通线程、tokio、SimpleExecutor 之间收发）
+    let handle = std::thread::spawn(|| {
+        examples::channel::oneshot::test_oneshot();
+    });
+    handle.join().unwrap();
+
+    // 示例 22: mpsc channel（有界 channel 的背压、关闭检测）
+    let handle = std::thread::spawn(|| {
+        examples::channel::mpsc::test_mpsc();
+    });
+    handle.join().unwrap();
+
+    // 示例 23: broadcast channel（环形缓冲区，慢的接收方报告落后）
+    let handle = std::thread::spawn(|| {
+        examples::channel::broadcast::test_broadcast();
+    });
+    handle.join().unwrap();
+
+    // 示例 24: watch channel（最新值和变化通知）
+    let handle = std::thread::spawn(|| {
+        examples::channel::watch::test_watch();
+    });
+    handle.join().unwrap();
+
+    // 示例 25: Semaphore（侵入式等待链表，限制并发数）
+    let handle = std::thread::spawn(|| {
+        examples::sync::semaphore::test_semaphore();
+    });
+    handle.join().unwrap();
+
+    // 示例 26: Notify（notify_one / notify_waiters）
+    let handle = std::thread::spawn(|| {
+        examples::sync::notify::test_notify();
+    });
+    handle.join().unwrap();
+
+    // 示例 27: Barrier（等待所有任务到达后一起继续）
+    let handle = std::thread::spawn(|| {
+        examples::sync::barrier::test_barrier();
+    });
+    handle.join().unwrap();
+
+    // 示例 28: 组合子（不依赖 tokio 的 join! / try_join! / select! / join_all / select_all）
+    let handle = std::thread::spawn(|| {
+        examples::combinator::test_combinators();
+    });
+    handle.join().unwrap();
+
+    // 示例 29: 完全不使用 tokio 的并发 greet（SimpleExecutor + join! + Semaphore）
+    let handle = std::thread::spawn(|| {
+        examples::greet::test_concurrent_without_tokio();
+    });
+    handle.join().unwrap();
+
+    // 示例 30: FuturesUnordered（每个子 future 有自己的 waker，poll 次数和唤醒次数成正比）
+    let handle = std::thread::spawn(|| {
+        examples::futures_unordered::test_futures_unordered();
+    });
+    handle.join().unwrap();
+
+    // 示例 31: Stream 和 StreamExt（map / filter / then / take / buffer_unordered / chunks_timeout / merge / Interval）
+    let handle = std::thread::spawn(|| {
+        examples::stream::test_stream();
+    });
+    handle.join().unwrap();
+
+    // 示例 32: 超时（timeout / deadline / block_on_timeout）
+    let handle = std::thread::spawn(|| {
+        examples::timeout::test_timeout();
+    });
+    handle.join().unwrap();
+
+    // 示例 33: 重试（指数退避、抖动、错误分类）
+    let handle = std::thread::spawn(|| {
+        examples::retry::test_retry();
+    });
+    handle.join().unwrap();
+
+    // 示例 34: 任务 panic 的隔离（catch_unwind 和 JoinError::Panic）
+    let handle = std::thread::spawn(|| {
+        examples::simple_executor::test_panic_isolation();
+    });
+    handle.join().unwrap();
+
+    // 示例 35: Fuse 和 FusedFuture
+    let handle = std::thread::spawn(|| {
+        examples::fuse::test_fuse();
+    });
+    handle.join().unwrap();
+
+    // 示例 36: 检测完成后又被 poll 的 future（类型名和调用位置，只在 debug 构建中）
+    #[cfg(debug_assertions)]
+    {
+        let handle = std::thread::spawn(|| {
+            examples::poll_after_ready::test_poll_after_ready();
+        });
+        handle.join().unwrap();
+    }
+
+    // 示例 37: 手动展开 greet 的状态机（两个 await、跨 await 的借用）
+    let handle = std::thread::spawn(|| {
+        examples::greet_coroutine::test_greet_coroutine();
+    });
+    handle.join().unwrap();
+
+    // 示例 38: #[desugar] 过程宏生成的状态机（直线代码、if、循环）
+    let handle = std::thread::spawn(|| {
+        examples::desugared::test_desugared();
+    });
+    handle.join().unwrap();
+
+    // 示例 39: future 的大小和对齐，以及 boxed_if_larger_than
+    let handle = std::thread::spawn(|| {
+        examples::layout::test_layout();
+    });
+    handle.join().unwrap();
+
+    // 示例 40: pin_project! 宏：安全的结构化 pin 投影和 PinnedDrop
+    let handle = std::thread::spawn(|| {
+        examples::pin_project::test_pin_project();
+    });
+    handle.join().unwrap();
 }